@@ -0,0 +1,184 @@
+use crate::game_state::GameState;
+use crate::layer::LayerType;
+use crate::storage::DataDir;
+use crate::TOWER_WINDOW_HEIGHT;
+use crate::WINDOW_WIDTH;
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, queue, terminal};
+use std::fs;
+use std::io::{self, Write};
+
+const ACHIEVEMENTS_FILE: &str = "achievements";
+
+/// Things that happen during a game which achievements can react to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GameEvent {
+    FloorBuilt(LayerType),
+    InspectionPassed,
+    Tick,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Achievement {
+    FirstApartment,
+    FirstFood,
+    FirstRetail,
+    TenFloors,
+    FiveInspections,
+    FullHouse,
+    DeepPockets,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 7] = [
+        Achievement::FirstApartment,
+        Achievement::FirstFood,
+        Achievement::FirstRetail,
+        Achievement::TenFloors,
+        Achievement::FiveInspections,
+        Achievement::FullHouse,
+        Achievement::DeepPockets,
+    ];
+
+    /// Stable identifier used in the achievements file.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Achievement::FirstApartment => "first_apartment",
+            Achievement::FirstFood => "first_food",
+            Achievement::FirstRetail => "first_retail",
+            Achievement::TenFloors => "ten_floors",
+            Achievement::FiveInspections => "five_inspections",
+            Achievement::FullHouse => "full_house",
+            Achievement::DeepPockets => "deep_pockets",
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Achievement::FirstApartment => "Home Sweet Home",
+            Achievement::FirstFood => "Open For Lunch",
+            Achievement::FirstRetail => "Grand Opening",
+            Achievement::TenFloors => "Skyscraper",
+            Achievement::FiveInspections => "Good Standing",
+            Achievement::FullHouse => "Full House",
+            Achievement::DeepPockets => "Deep Pockets",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Achievement::FirstApartment => "Build your first apartment complex",
+            Achievement::FirstFood => "Build your first food court",
+            Achievement::FirstRetail => "Build your first retail store",
+            Achievement::TenFloors => "Have a tower with 10 floors",
+            Achievement::FiveInspections => "Survive 5 debt collector inspections in one run",
+            Achievement::FullHouse => "Reach 100% occupancy on every floor",
+            Achievement::DeepPockets => "Have $100000 in funds",
+        }
+    }
+
+    fn is_earned(&self, event: GameEvent, gs: &GameState) -> bool {
+        match (self, event) {
+            (Achievement::FirstApartment, GameEvent::FloorBuilt(LayerType::Apartment)) => true,
+            (Achievement::FirstFood, GameEvent::FloorBuilt(LayerType::Food)) => true,
+            (Achievement::FirstRetail, GameEvent::FloorBuilt(LayerType::Retail)) => true,
+            (Achievement::TenFloors, GameEvent::FloorBuilt(_)) => gs.layers.len() >= 10,
+            (Achievement::FiveInspections, GameEvent::InspectionPassed) => gs.period >= 5,
+            (Achievement::FullHouse, GameEvent::Tick) => {
+                !gs.layers.is_empty()
+                    && gs
+                        .layers
+                        .iter()
+                        .all(|layer| layer.occupancy == layer.max_occupancy)
+            }
+            (Achievement::DeepPockets, GameEvent::Tick) => gs.funds >= 100_000,
+            _ => false,
+        }
+    }
+}
+
+pub struct Achievements {
+    unlocked: Vec<Achievement>,
+}
+
+impl Achievements {
+    /// Loads the achievements unlocked in previous sessions. A missing or
+    /// unreadable file just means nothing has been unlocked yet.
+    pub fn load(data_dir: &DataDir) -> Self {
+        let contents = data_dir
+            .file(ACHIEVEMENTS_FILE)
+            .and_then(fs::read_to_string)
+            .unwrap_or_default();
+        let unlocked = Achievement::ALL
+            .into_iter()
+            .filter(|a| contents.lines().any(|line| line.trim() == a.id()))
+            .collect();
+        Achievements { unlocked }
+    }
+
+    pub fn save(&self, data_dir: &DataDir) -> io::Result<()> {
+        let mut contents = String::new();
+        for achievement in &self.unlocked {
+            contents += achievement.id();
+            contents += "\n";
+        }
+        fs::write(data_dir.file(ACHIEVEMENTS_FILE)?, contents)
+    }
+
+    pub fn is_unlocked(&self, achievement: Achievement) -> bool {
+        self.unlocked.contains(&achievement)
+    }
+
+    /// Returns the achievements that `event` unlocks, without unlocking them.
+    pub fn newly_earned(&self, event: GameEvent, gs: &GameState) -> Vec<Achievement> {
+        Achievement::ALL
+            .into_iter()
+            .filter(|a| !self.is_unlocked(*a) && a.is_earned(event, gs))
+            .collect()
+    }
+
+    pub fn unlock(&mut self, achievement: Achievement) {
+        if !self.is_unlocked(achievement) {
+            self.unlocked.push(achievement);
+        }
+    }
+}
+
+pub fn achievements_draw(mut writer: impl Write, achievements: &Achievements) -> io::Result<()> {
+    const TOP_ROW: u16 = 3;
+
+    queue!(
+        writer,
+        cursor::MoveTo(WINDOW_WIDTH, TOWER_WINDOW_HEIGHT),
+        terminal::Clear(terminal::ClearType::FromCursorUp),
+        cursor::MoveTo(0, TOP_ROW),
+        style::SetColors(Colors {
+            foreground: Some(Color::White),
+            background: Some(Color::Black),
+        }),
+        style::Print("ACHIEVEMENTS"),
+    )?;
+
+    for (row, achievement) in (TOP_ROW + 2..).zip(Achievement::ALL) {
+        let (mark, color) = if achievements.is_unlocked(achievement) {
+            ('x', Color::Green)
+        } else {
+            (' ', Color::DarkGrey)
+        };
+        queue!(
+            writer,
+            cursor::MoveTo(0, row),
+            style::SetColors(Colors {
+                foreground: Some(color),
+                background: Some(Color::Black),
+            }),
+            style::Print(format!(
+                "[{mark}] {:<18}{}",
+                achievement.name(),
+                achievement.description()
+            )),
+        )?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use crate::game_state::GameState;
+use crate::layer::{FloorTypes, LayerType};
+use crate::undo::{Reversal, UndoEntry};
+use std::io;
+
+/// Something the player can do, along with everything needed to do it, so
+/// the same action can be bound to a key, generated from data or performed
+/// from elsewhere.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    Build(LayerType),
+    /// Tear down a floor (an index into `GameState::layers`). Nothing is
+    /// refunded.
+    Demolish(usize),
+    /// Scroll to a floor (an index into `GameState::layers`) and select it.
+    SelectFloor(usize),
+    SetRent { floor: usize, rent: usize },
+    /// Borrow money, to be paid back with interest at the next inspection.
+    TakeLoan(i32),
+}
+
+impl Action {
+    /// Performs the action, remembering it so it can be undone.
+    pub fn perform(self, gs: &mut GameState) -> io::Result<()> {
+        if self.apply(gs)? {
+            gs.undo_history.clear_redo();
+        }
+        Ok(())
+    }
+
+    /// Performs the action without touching the redo history. Returns
+    /// whether it was remembered for undo.
+    pub fn apply(self, gs: &mut GameState) -> io::Result<bool> {
+        let reversal = match self {
+            Action::Build(style) if gs.mods.floor_types().get(style).stars > gs.stars => {
+                let floor_type = gs.mods.floor_types().get(style);
+                let msg = format!("{} need a {}-star tower", floor_type.label, floor_type.stars);
+                gs.show_message(&msg)?;
+                None
+            }
+            Action::Build(style) => {
+                let cost = gs.build_layer(style)?;
+                Some(Reversal::Build { cost })
+            }
+            Action::Demolish(floor) => gs
+                .demolish(floor)?
+                .map(|layer| Reversal::Demolish { floor, layer }),
+            Action::SelectFloor(floor) if floor < gs.layers.len() => {
+                gs.scroll_to_floor(floor)?;
+                gs.select_floor(floor)?;
+                None
+            }
+            Action::SelectFloor(floor) => {
+                gs.show_message(&format!("There is no floor {}", floor + 1))?;
+                None
+            }
+            Action::SetRent { floor, rent } => {
+                let old_rent = gs.layers.get(floor).map(|layer| layer.revenue_per_occupant);
+                gs.set_rent(floor, rent)?;
+                old_rent.map(|rent| Reversal::SetRent { floor, rent })
+            }
+            Action::TakeLoan(amount) => gs
+                .take_loan(amount)?
+                .then_some(Reversal::TakeLoan(amount)),
+        };
+        let Some(reversal) = reversal else {
+            return Ok(false);
+        };
+        gs.undo_history.record(UndoEntry {
+            action: self,
+            reversal,
+            tick: gs.tick,
+            period: gs.period,
+            stars: gs.stars,
+        });
+        Ok(true)
+    }
+
+    /// Short description for undo and redo messages.
+    pub fn describe(&self, floor_types: &FloorTypes) -> String {
+        match self {
+            Action::Build(style) => {
+                format!("building {}", floor_types.get(*style).label.to_lowercase())
+            }
+            Action::Demolish(floor) => format!("demolishing floor {}", floor + 1),
+            Action::SelectFloor(floor) => format!("selecting floor {}", floor + 1),
+            Action::SetRent { floor, .. } => format!("rent change on floor {}", floor + 1),
+            Action::TakeLoan(amount) => format!("${amount} loan"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_state::LOAN_AMOUNT;
+    use crate::ledger::TransactionCategory;
+    use crate::test_game::TestGame;
+    use crossterm::event::KeyCode;
+
+    #[test]
+    fn every_floor_type_in_the_catalog_can_be_built() {
+        let mut game = TestGame::new();
+        game.gs.stars = 5;
+        game.gs.funds = 1_000_000;
+        let styles: Vec<_> = game.gs.mods.floor_types().all().collect();
+        for &style in &styles {
+            let funds = game.gs.funds;
+            let price = game.gs.rules.price(game.floor_type(style).price);
+            game.build(style);
+            assert_eq!(game.gs.layers.last().unwrap().style, style);
+            assert_eq!(game.gs.funds, funds - price, "{}", game.floor_type(style).label);
+        }
+        assert_eq!(game.gs.layers.len(), styles.len());
+    }
+
+    #[test]
+    fn loans_are_paid_back_with_interest_at_the_inspection() {
+        let mut game = TestGame::new();
+        let funds = game.gs.funds;
+        game.press(KeyCode::Char('l'));
+        assert_eq!(game.gs.funds, funds + LOAN_AMOUNT);
+        assert_eq!(game.gs.loan, LOAN_AMOUNT);
+
+        game.gs.debt_collection_timer = 1;
+        game.tick();
+        assert_eq!(game.gs.loan, 0);
+        assert_eq!(game.gs.funds, funds - LOAN_AMOUNT / 10);
+        let repaid = game.gs.ledger.period_total(0, TransactionCategory::DebtPayment);
+        assert_eq!(repaid, -(LOAN_AMOUNT + LOAN_AMOUNT / 10));
+    }
+
+    #[test]
+    fn loans_the_bank_refuses_are_not_remembered() {
+        let mut game = TestGame::new();
+        for _ in 0..5 {
+            game.press(KeyCode::Char('l'));
+        }
+        assert_eq!(game.gs.loan, 4 * LOAN_AMOUNT);
+        assert!(game.gs.message.contains("won't lend"), "{}", game.gs.message);
+        game.press(KeyCode::Char('u'));
+        assert_eq!(game.gs.loan, 3 * LOAN_AMOUNT);
+    }
+}
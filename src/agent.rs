@@ -0,0 +1,291 @@
+use crate::frame::FrameBuffer;
+use crate::game_state::{init_game_state, GameState, Screen};
+use crate::json::Json;
+use crate::keymap::KeyBinding;
+use crate::mods::Mods;
+use crate::rules::Rules;
+use crate::storage::DataDir;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Bumped whenever a request or reply changes in a way that could break an
+/// agent.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Most ticks one step request can ask for.
+const MAX_STEPS: f64 = 10_000.0;
+
+/// A game played by another program, which sends requests and gets replies
+/// one JSON object per line.
+///
+/// Requests look like
+///
+/// ```text
+/// {"action": "build"}       do what a key in the current menu does
+/// {"key": "Ctrl+r"}         press a key, written as in keys.conf
+/// {"text": "12"}            type into a prompt, like go to floor
+/// {"step": 5}               move the clock on, in lock-step mode
+/// ```
+///
+/// and each is answered with the state of the game afterwards, or an error.
+/// Unless the agent controls the clock, the state is also sent every tick.
+pub struct Agent {
+    pub gs: GameState,
+    /// Whether the clock only moves when the agent asks it to.
+    lockstep: bool,
+}
+
+impl Agent {
+    pub fn new(mut gs: GameState, lockstep: bool) -> io::Result<Self> {
+        gs.draw_command_pool()?;
+        gs.draw_tower()?;
+        Ok(Agent { gs, lockstep })
+    }
+
+    /// The first line sent, so the agent knows what it is talking to.
+    pub fn hello(&self) -> Json {
+        Json::object([
+            ("type", "hello".into()),
+            ("version", PROTOCOL_VERSION.into()),
+            ("lockstep", self.lockstep.into()),
+        ])
+    }
+
+    /// Everything about the game an agent can see, and what it can do.
+    pub fn state(&self) -> Json {
+        let gs = &self.gs;
+        let layers = gs.layers.iter().enumerate().map(|(floor, layer)| {
+            Json::object([
+                ("floor", floor.into()),
+                ("type", layer.floor_type().id.as_str().into()),
+                ("occupancy", layer.occupancy.into()),
+                ("capacity", layer.max_occupancy.into()),
+                ("rent", layer.revenue_per_occupant.into()),
+                ("cleanliness", layer.cleanliness.into()),
+                ("broken_down", layer.broken_down.into()),
+            ])
+        });
+        let pool = gs.command_pool();
+        let commands = pool.actions().map(|(action, keys, description)| {
+            let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+            Json::object([
+                ("action", action.into()),
+                ("keys", keys.into()),
+                ("label", description.into()),
+            ])
+        });
+        Json::object([
+            ("type", "state".into()),
+            ("tick", gs.tick.into()),
+            ("funds", gs.funds.into()),
+            ("loan", gs.loan.into()),
+            ("back_taxes", gs.taxes.arrears.into()),
+            ("timer", gs.debt_collection_timer.into()),
+            ("inspections_passed", gs.period.into()),
+            ("stars", gs.stars.into()),
+            ("game_over", gs.game_over.into()),
+            ("rules", gs.rules.name.as_str().into()),
+            ("weather", gs.calendar.today().description().into()),
+            ("screen", screen_name(gs.screen).into()),
+            ("menu", pool.name().into()),
+            ("message", gs.message.as_str().into()),
+            ("selected_floor", gs.selected_floor.into()),
+            ("layers", Json::Array(layers.collect())),
+            ("commands", Json::Array(commands.collect())),
+        ])
+    }
+
+    /// Does what `line` asks, answering with the state afterwards or what
+    /// was wrong with it.
+    pub fn request(&mut self, line: &str) -> io::Result<Json> {
+        let request = match Json::parse(line) {
+            Ok(request @ Json::Object(_)) => request,
+            Ok(_) => return Ok(error("requests are JSON objects")),
+            Err(e) => return Ok(error(&e)),
+        };
+        if let Some(action) = request.get("action") {
+            let Some(action) = action.as_str() else {
+                return Ok(error("action has to be a string"));
+            };
+            let pool = self.gs.command_pool();
+            let Some(command) = pool.command_for_action(action) else {
+                let message = format!("there is no {action} action in the {} menu", pool.name());
+                return Ok(error(&message));
+            };
+            command.execute(&mut self.gs)?;
+        } else if let Some(key) = request.get("key") {
+            let Some(binding) = key.as_str().and_then(KeyBinding::parse) else {
+                return Ok(error(&format!("{key} is not a key")));
+            };
+            crate::handle_key_event(&mut self.gs, binding.key_event())?;
+        } else if let Some(text) = request.get("text") {
+            let Some(text) = text.as_str() else {
+                return Ok(error("text has to be a string"));
+            };
+            for c in text.chars() {
+                let key = KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE);
+                crate::handle_key_event(&mut self.gs, key)?;
+            }
+        } else if let Some(steps) = request.get("step") {
+            if !self.lockstep {
+                return Ok(error("the clock only takes steps in lock-step mode"));
+            }
+            let steps = match steps.as_f64() {
+                Some(steps) if steps.fract() == 0.0 && (1.0..=MAX_STEPS).contains(&steps) => steps,
+                _ => return Ok(error(&format!("step takes a whole number from 1 to {MAX_STEPS}"))),
+            };
+            for _ in 0..steps as u32 {
+                self.tick()?;
+            }
+        } else {
+            return Ok(error("expected an action, key, text or step"));
+        }
+        self.restart_if_asked()?;
+        Ok(self.state())
+    }
+
+    pub fn tick(&mut self) -> io::Result<()> {
+        crate::handle_time_tick(&mut self.gs)?;
+        self.restart_if_asked()
+    }
+
+    /// Starts a new game if the last one asked for it, as the terminal game
+    /// does.
+    fn restart_if_asked(&mut self) -> io::Result<()> {
+        if !self.gs.restarting {
+            return Ok(());
+        }
+        let recorded = self.gs.end_run();
+        let rules = self.gs.next_rules.take().unwrap_or_else(|| self.gs.rules.clone());
+        let frame = std::mem::replace(&mut self.gs.frame, FrameBuffer::new());
+        let (mods, data_dir) = (Rc::clone(&self.gs.mods), self.gs.data_dir.clone());
+        self.gs = init_game_state(frame, mods, rules, data_dir);
+        if let Err(e) = recorded {
+            self.gs.toast(format!("Could not save the last run: {e}"))?;
+        }
+        self.gs.draw_command_pool()?;
+        self.gs.draw_tower()
+    }
+}
+
+fn error(message: &str) -> Json {
+    Json::object([("type", "error".into()), ("message", message.into())])
+}
+
+fn screen_name(screen: Screen) -> &'static str {
+    match screen {
+        Screen::Tower => "tower",
+        Screen::Finances => "finances",
+        Screen::Achievements => "achievements",
+        Screen::HighScores => "high_scores",
+        Screen::Help => "help",
+        Screen::Staff => "staff",
+        Screen::Messages => "messages",
+        Screen::NewGame => "new_game",
+    }
+}
+
+/// Plays a game for the program on the other end of stdin and stdout until
+/// it quits or closes stdin.
+pub async fn run(mods: Rc<Mods>, lockstep: bool) -> io::Result<()> {
+    let gs = init_game_state(FrameBuffer::new(), mods, Rules::default(), DataDir::from_env());
+    let mut agent = Agent::new(gs, lockstep)?;
+    agent.gs.show_new_game()?;
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", agent.hello())?;
+    writeln!(stdout, "{}", agent.state())?;
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut speed = agent.gs.speed;
+    let mut heartbeat = tokio::time::interval(Duration::from_secs(1) / speed);
+    while agent.gs.running {
+        if agent.gs.speed != speed {
+            speed = agent.gs.speed;
+            heartbeat = tokio::time::interval(Duration::from_secs(1) / speed);
+            heartbeat.reset();
+        }
+        tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(line) if line.trim().is_empty() => {}
+                Some(line) => writeln!(stdout, "{}", agent.request(&line)?)?,
+                None => break,
+            },
+            _ = heartbeat.tick(), if !lockstep => {
+                agent.tick()?;
+                writeln!(stdout, "{}", agent.state())?;
+            }
+        }
+        stdout.flush()?;
+    }
+    // The program is gone, so there is no one to tell if this fails
+    let _ = agent.gs.end_run();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_game::TestGame;
+
+    /// Sends `request` to `agent`, expecting the state back.
+    fn ask(agent: &mut Agent, request: &str) -> Json {
+        let reply = agent.request(request).unwrap();
+        assert_eq!(reply.get("type"), Some(&Json::from("state")), "{reply}");
+        reply
+    }
+
+    fn field(json: &Json, name: &str) -> f64 {
+        json.get(name).and_then(Json::as_f64).unwrap()
+    }
+
+    #[test]
+    fn agent_picks_rules_builds_and_steps_the_clock() {
+        let TestGame { mut gs, data_dir: _data_dir } = TestGame::new();
+        gs.show_new_game().unwrap();
+        let mut agent = Agent::new(gs, true).unwrap();
+        let state = agent.state();
+        assert_eq!(state.get("menu"), Some(&Json::from("new_game")));
+
+        let state = ask(&mut agent, r#"{"action": "rules_1"}"#);
+        assert_eq!(state.get("rules"), Some(&Json::from("Easy")));
+        assert_eq!(field(&state, "funds"), 20_000.0);
+
+        ask(&mut agent, r#"{"key": "b"}"#);
+        let state = ask(&mut agent, r#"{"action": "food"}"#);
+        assert_eq!(field(&state, "funds"), 12_000.0);
+        let Some(Json::Array(layers)) = state.get("layers") else { panic!("{state}") };
+        assert_eq!(layers[0].get("type"), Some(&Json::from("food")));
+
+        let state = ask(&mut agent, r#"{"step": 3}"#);
+        assert_eq!(field(&state, "tick"), 3.0);
+        assert_eq!(field(&state, "timer"), 57.0);
+        let Some(Json::Array(commands)) = state.get("commands") else { panic!("{state}") };
+        let build = commands.iter().find(|c| c.get("action") == Some(&Json::from("build")));
+        assert_eq!(build.and_then(|c| c.get("keys")), Some(&Json::from(vec!["b"])));
+
+        ask(&mut agent, r#"{"key": "g"}"#);
+        let state = ask(&mut agent, r#"{"text": "1"}"#);
+        assert_eq!(state.get("menu"), Some(&Json::from("goto")));
+    }
+
+    #[test]
+    fn agent_is_told_what_is_wrong_with_a_request() {
+        let TestGame { gs, data_dir: _data_dir } = TestGame::new();
+        let mut agent = Agent::new(gs, false).unwrap();
+        for (request, message) in [
+            ("{\"action\": \"food\"}", "there is no food action in the main menu"),
+            ("{\"step\": 1}", "the clock only takes steps in lock-step mode"),
+            ("{\"key\": \"Hyper+q\"}", "\"Hyper+q\" is not a key"),
+            ("{\"build\": 1}", "expected an action, key, text or step"),
+            ("[]", "requests are JSON objects"),
+            ("{", "expected a field name at character 2"),
+        ] {
+            let reply = agent.request(request).unwrap();
+            assert_eq!(reply.get("type"), Some(&Json::from("error")), "{request}");
+            assert_eq!(reply.get("message"), Some(&Json::from(message)), "{request}");
+        }
+        assert_eq!(field(&agent.state(), "tick"), 0.0);
+    }
+}
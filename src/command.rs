@@ -1,34 +1,73 @@
-use crate::{GameState, CONTROLS_HEIGHT, WINDOW_HEIGHT};
+use crate::keymap::{KeyBinding, Keymap};
+use crate::{GameState, CONTROLS_HEIGHT, WINDOW_HEIGHT, WINDOW_WIDTH};
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use crossterm::event::{KeyCode, KeyModifiers};
 use crossterm::style::{self, style, Stylize};
 use crossterm::{cursor, queue, terminal};
 use std::io::{self, Write};
 
-pub type CommandFn = fn(&mut GameState) -> io::Result<()>;
+/// Rows of labels in the controls panel.
+const CONTROLS_ROWS: u16 = (CONTROLS_HEIGHT - 1) / 2;
+/// Columns between the longest label in a column and the next column.
+const CONTROLS_COL_GAP: u16 = 3;
+/// Widest a label can be before its description is cut short.
+const MAX_LABEL_WIDTH: usize = 32;
+const MORE_INDICATOR_WIDTH: u16 = 18;
+/// Action every pool binds to turning the page of the controls panel.
+const MORE_ACTION: &str = "more";
 
-#[derive(Copy, Clone)]
+pub type CommandFn = Rc<dyn Fn(&mut GameState) -> io::Result<()>>;
+pub type TextInputFn = fn(&mut GameState, char) -> io::Result<()>;
+
+#[derive(Clone)]
 pub struct Command {
     op: CommandFn,
     is_triggered_on_repeat: bool,
+    help: String,
+    rule: String,
 }
 
 impl Command {
-    pub fn new(op: CommandFn) -> Self {
-        Command {
-            op,
-            is_triggered_on_repeat: false,
-        }
+    pub fn new(op: impl Fn(&mut GameState) -> io::Result<()> + 'static) -> Self {
+        Command::with_triggered_on_repeat(op, false)
     }
 
-    pub fn with_triggered_on_repeat(op: CommandFn, is_triggered_on_repeat: bool) -> Self {
+    pub fn with_triggered_on_repeat(
+        op: impl Fn(&mut GameState) -> io::Result<()> + 'static,
+        is_triggered_on_repeat: bool,
+    ) -> Self {
         Command {
-            op,
+            op: Rc::new(op),
             is_triggered_on_repeat,
+            help: String::new(),
+            rule: String::new(),
         }
     }
 
+    /// Attaches the longer explanation shown on the help screen.
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = help.into();
+        self
+    }
+
+    pub fn help(&self) -> &str {
+        &self.help
+    }
+
+    /// Attaches the rule of the game that decides how the command plays
+    /// out, listed under RULES on the help screen.
+    pub fn with_rule(mut self, rule: impl Into<String>) -> Self {
+        self.rule = rule.into();
+        self
+    }
+
+    pub fn rule(&self) -> &str {
+        &self.rule
+    }
+
     pub fn is_triggered_on_repeat(&self) -> bool {
         self.is_triggered_on_repeat
     }
@@ -38,78 +77,322 @@ impl Command {
     }
 }
 
+struct CommandEntry {
+    action: Cow<'static, str>,
+    keys: Vec<KeyBinding>,
+    description: String,
+    command: Command,
+    /// Whether the entry gets a label in the controls panel.
+    in_panel: bool,
+}
+
+/// Where and how a command is labelled in the controls panel.
+#[derive(Clone)]
+struct Label {
+    /// Index into `CommandPool::entries`.
+    entry: usize,
+    key: String,
+    description: String,
+    col: u16,
+    row: u16,
+    width: u16,
+}
+
+impl Label {
+    fn new(entry: usize, key: &KeyBinding, description: &str) -> Self {
+        let key = key.to_string();
+        let key_width = key.chars().count().max(5);
+        let max_description_width = MAX_LABEL_WIDTH.saturating_sub(key_width + 2);
+        let description = if description.chars().count() > max_description_width {
+            let mut truncated: String = description
+                .chars()
+                .take(max_description_width.saturating_sub(1))
+                .collect();
+            truncated.push('…');
+            truncated
+        } else {
+            description.to_string()
+        };
+        let width = (key_width + 2 + description.chars().count()) as u16;
+        Label {
+            entry,
+            key,
+            description,
+            col: 0,
+            row: 0,
+            width,
+        }
+    }
+}
+
 pub struct CommandPool {
-    commands: HashMap<(KeyCode, KeyModifiers), Command>,
-    descriptions: Vec<String>,
+    name: &'static str,
+    entries: Vec<CommandEntry>,
+    commands: HashMap<KeyBinding, usize>,
+    text_input: Option<TextInputFn>,
 }
 
 impl CommandPool {
     pub fn get_command(&self, key_code: KeyCode, key_modifiers: KeyModifiers) -> Option<Command> {
-        self.commands.get(&(key_code, key_modifiers)).copied()
+        let index = self.commands.get(&KeyBinding::new(key_code, key_modifiers))?;
+        Some(self.entries[*index].command.clone())
     }
 
-    pub fn draw(&self, mut writer: impl Write) -> io::Result<()> {
-        const COL_WIDTH: u16 = 16;
+    /// The action bound to a key, for sending to another game to perform.
+    pub fn get_action(
+        &self,
+        key_code: KeyCode,
+        key_modifiers: KeyModifiers,
+    ) -> Option<&str> {
+        let index = self.commands.get(&KeyBinding::new(key_code, key_modifiers))?;
+        Some(&self.entries[*index].action)
+    }
+
+    /// The command an action names, as given in the key bindings.
+    pub fn command_for_action(&self, action: &str) -> Option<Command> {
+        let entry = self.entries.iter().find(|entry| entry.action == action)?;
+        Some(entry.command.clone())
+    }
+
+    /// The menu the pool is for in the key bindings.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The action, keys and short description of every entry, bound or
+    /// not, in the order they were added.
+    pub fn actions(&self) -> impl Iterator<Item = (&str, &[KeyBinding], &str)> {
+        self.entries
+            .iter()
+            .map(|entry| (&*entry.action, entry.keys.as_slice(), entry.description.as_str()))
+    }
+
+    /// The keys, short description and command of every bound entry, in the
+    /// order they were added.
+    pub fn bindings(&self) -> impl Iterator<Item = (&[KeyBinding], &str, &Command)> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.keys.is_empty())
+            .map(|entry| (entry.keys.as_slice(), entry.description.as_str(), &entry.command))
+    }
+
+    /// Like `bindings`, but only the entries with a label in the controls
+    /// panel.
+    pub fn panel_bindings(&self) -> impl Iterator<Item = (&[KeyBinding], &str, &Command)> {
+        self.bindings().zip(self.entries.iter().filter(|entry| !entry.keys.is_empty())).filter_map(
+            |(binding, entry)| entry.in_panel.then_some(binding),
+        )
+    }
 
+    /// Handler for typed characters that aren't bound to a command.
+    pub fn text_input(&self) -> Option<TextInputFn> {
+        self.text_input
+    }
+
+    /// Lays the labels of the commands out in columns as wide as their
+    /// longest label, starting a new page whenever the next column would not
+    /// fit on screen.
+    fn pages(&self) -> Vec<Vec<Label>> {
+        let labels: Vec<Label> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.in_panel && !entry.keys.is_empty())
+            .map(|(index, entry)| Label::new(index, &entry.keys[0], &entry.description))
+            .collect();
+
+        let layout = |available_width: u16| {
+            let mut pages: Vec<Vec<Label>> = vec![vec![]];
+            let mut col = 1;
+            for column in labels.chunks(CONTROLS_ROWS as usize) {
+                let width = column.iter().map(|label| label.width).max().unwrap_or(0);
+                if col + width > available_width && !pages[pages.len() - 1].is_empty() {
+                    pages.push(vec![]);
+                    col = 1;
+                }
+                let page = pages.last_mut().unwrap();
+                for (row, label) in (0..).zip(column) {
+                    page.push(Label {
+                        col,
+                        row: WINDOW_HEIGHT - CONTROLS_HEIGHT + 1 + 2 * row,
+                        ..label.clone()
+                    });
+                }
+                col += width + CONTROLS_COL_GAP;
+            }
+            pages
+        };
+
+        let pages = layout(WINDOW_WIDTH);
+        if pages.len() == 1 {
+            return pages;
+        }
+        layout(WINDOW_WIDTH - MORE_INDICATOR_WIDTH)
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages().len()
+    }
+
+    /// The label of the "more" indicator shown when the controls don't fit on
+    /// one page, so clicking it or pressing its key turns the page.
+    fn more_indicator(&self, page: usize, page_count: usize) -> Option<Label> {
+        if page_count < 2 {
+            return None;
+        }
+        let index = self.entries.iter().position(|entry| entry.action == MORE_ACTION)?;
+        let entry = &self.entries[index];
+        let key = entry.keys.first()?;
+        let description = format!("more… {}/{}", page + 1, page_count);
+        Some(Label {
+            col: WINDOW_WIDTH - MORE_INDICATOR_WIDTH + 1,
+            row: WINDOW_HEIGHT - CONTROLS_HEIGHT + 1 + 2 * (CONTROLS_ROWS - 1),
+            ..Label::new(index, key, &description)
+        })
+    }
+
+    /// The command whose label is drawn at `x`, `y` on `page`, if any.
+    pub fn command_at(&self, page: usize, x: u16, y: u16) -> Option<Command> {
+        let pages = self.pages();
+        let page = page.min(pages.len() - 1);
+        let more = self.more_indicator(page, pages.len());
+        pages[page]
+            .iter()
+            .chain(more.iter())
+            .find(|label| y == label.row && (label.col..label.col + label.width).contains(&x))
+            .map(|label| self.entries[label.entry].command.clone())
+    }
+
+    pub fn draw(&self, mut writer: impl Write, page: usize) -> io::Result<()> {
         queue!(
             writer,
             cursor::MoveTo(0, WINDOW_HEIGHT - CONTROLS_HEIGHT),
             terminal::Clear(terminal::ClearType::FromCursorDown),
         )?;
-        for (desc, (col, row)) in self.descriptions.iter().zip(iter_cols_rows()) {
+        let pages = self.pages();
+        let page = page.min(pages.len() - 1);
+        let more = self.more_indicator(page, pages.len());
+        for label in pages[page].iter().chain(more.iter()) {
             queue!(
                 writer,
-                cursor::MoveTo(
-                    1 + col * COL_WIDTH,
-                    WINDOW_HEIGHT - CONTROLS_HEIGHT + 1 + 2 * row
-                ),
-                style::Print(desc),
+                cursor::MoveTo(label.col, label.row),
+                style::Print(format!(
+                    "{}: {}",
+                    style(format!("{:>5}", label.key)).green(),
+                    label.description,
+                )),
             )?;
         }
         Ok(())
     }
+
+    /// Replaces the default keys with the ones `keymap` gives, then indexes
+    /// the commands by key. Returns a message for every conflict found; the
+    /// first action bound to a key keeps it.
+    fn bind_keys(&mut self, keymap: &Keymap) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.commands.clear();
+        for index in 0..self.entries.len() {
+            let action = &self.entries[index].action;
+            let keys = match keymap.bindings(self.name, action) {
+                Some(keys) => keys.to_vec(),
+                None => self.entries[index].keys.clone(),
+            };
+            let mut bound = Vec::new();
+            for key in keys {
+                if let Some(&other) = self.commands.get(&key) {
+                    errors.push(format!(
+                        "{key} is bound to both {}.{} and {}.{action}",
+                        self.name, self.entries[other].action, self.name
+                    ));
+                } else {
+                    self.commands.insert(key, index);
+                    bound.push(key);
+                }
+            }
+            self.entries[index].keys = bound;
+        }
+        errors
+    }
 }
 
 pub struct CommandPoolBuilder {
     pool: CommandPool,
+    with_help: bool,
 }
 
 impl CommandPoolBuilder {
-    pub fn new() -> Self {
+    /// `name` identifies the pool in the keymap file.
+    pub fn new(name: &'static str) -> Self {
         CommandPoolBuilder {
             pool: CommandPool {
+                name,
+                entries: Vec::new(),
                 commands: HashMap::new(),
-                descriptions: Vec::new(),
+                text_input: None,
             },
+            with_help: true,
         }
     }
 
-    pub fn on_letter_press(
+    /// Binds `command` to `keys` unless the keymap binds `action` to other
+    /// keys.
+    pub fn on_key_press(
         mut self,
-        key: char,
+        action: impl Into<Cow<'static, str>>,
+        keys: &[KeyBinding],
         description: impl AsRef<str>,
         command: Command,
     ) -> Self {
-        assert!(key.is_ascii_lowercase());
-        self.pool
-            .commands
-            .insert((KeyCode::Char(key), KeyModifiers::empty()), command);
-        self.pool.descriptions.push(format!(
-            "{:>5}: {}",
-            style(key).green(),
-            description.as_ref(),
-        ));
+        self.pool.entries.push(CommandEntry {
+            action: action.into(),
+            keys: keys.to_vec(),
+            description: description.as_ref().to_string(),
+            command,
+            in_panel: true,
+        });
         self
     }
 
-    pub fn build(self) -> CommandPool {
-        self.pool
+    /// Sends characters typed without Ctrl or Alt that no command is bound
+    /// to to `handler`.
+    pub fn on_text_input(mut self, handler: TextInputFn) -> Self {
+        self.pool.text_input = Some(handler);
+        self
     }
-}
 
-fn iter_cols_rows() -> impl Iterator<Item = (u16, u16)> {
-    const TOTAL_ROWS: u16 = (CONTROLS_HEIGHT - 1) / 2;
-    (0..).flat_map(|c| std::iter::repeat(c).zip(0..TOTAL_ROWS))
+    /// Leaves out the `?` binding for the help screen that every pool gets
+    /// by default.
+    pub fn without_help(mut self) -> Self {
+        self.with_help = false;
+        self
+    }
+
+    pub fn build(mut self) -> CommandPool {
+        // Pools that need Tab for something else can still turn the page by
+        // clicking the "more" indicator
+        let tab = KeyBinding::key(KeyCode::Tab);
+        let tab_taken = self.pool.entries.iter().any(|entry| entry.keys.contains(&tab));
+        // Turning the page is shown by the "more" indicator instead of a label
+        self.pool.entries.push(CommandEntry {
+            action: MORE_ACTION.into(),
+            keys: if tab_taken { vec![] } else { vec![tab] },
+            description: "More controls".to_string(),
+            command: Command::new(|gs| gs.next_controls_page())
+                .with_help("Show the next page of controls when they don't all fit."),
+            in_panel: false,
+        });
+        if self.with_help {
+            self = self.on_key_press(
+                "help",
+                &[KeyBinding::char('?')],
+                "Help",
+                Command::new(|gs| gs.show_help())
+                    .with_help("Show this screen, listing every control in the current menu."),
+            );
+        }
+        self.pool
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, Hash)]
@@ -118,6 +401,8 @@ pub struct CommandPoolId(usize);
 pub struct CommandPoolArray {
     pools: Vec<CommandPool>,
     id: CommandPoolId,
+    /// Page of the current pool's controls shown in the panel.
+    page: usize,
 }
 
 impl CommandPoolArray {
@@ -128,32 +413,136 @@ impl CommandPoolArray {
     pub fn set_id(&mut self, id: CommandPoolId) {
         assert!(id.0 < self.pools.len());
         self.id = id;
+        self.page = 0;
+    }
+
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    pub fn set_page(&mut self, page: usize) {
+        self.page = page;
     }
 
     pub fn cur(&self) -> &CommandPool {
         &self.pools[self.id.0]
     }
+
+    pub fn get(&self, id: CommandPoolId) -> &CommandPool {
+        &self.pools[id.0]
+    }
+
+    /// The rules attached to commands in every pool, each once, in the
+    /// order the commands were added.
+    pub fn rules(&self) -> Vec<&str> {
+        let mut rules: Vec<&str> = Vec::new();
+        let commands = self.pools.iter().flat_map(|pool| &pool.entries).map(|e| &e.command);
+        for rule in commands.map(Command::rule) {
+            if !rule.is_empty() && !rules.contains(&rule) {
+                rules.push(rule);
+            }
+        }
+        rules
+    }
 }
 
 pub struct CommandPoolArrayBuilder {
     pools: Vec<CommandPool>,
+    keymap: Keymap,
+    errors: Vec<String>,
 }
 
 impl CommandPoolArrayBuilder {
-    pub fn new() -> Self {
-        CommandPoolArrayBuilder { pools: Vec::new() }
+    pub fn new(keymap: Keymap) -> Self {
+        CommandPoolArrayBuilder {
+            pools: Vec::new(),
+            errors: keymap.errors().to_vec(),
+            keymap,
+        }
     }
 
-    pub fn add_pool(&mut self, pool: CommandPool) -> CommandPoolId {
+    pub fn add_pool(&mut self, mut pool: CommandPool) -> CommandPoolId {
+        let errors = pool.bind_keys(&self.keymap);
+        self.errors.extend(errors);
         self.pools.push(pool);
         CommandPoolId(self.pools.len() - 1)
     }
 
+    /// Problems with the keymap: parse errors, conflicting bindings and
+    /// bindings for actions that don't exist.
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = self.errors.clone();
+        for action in self.keymap.actions() {
+            let exists = self.pools.iter().any(|pool| {
+                pool.entries
+                    .iter()
+                    .any(|entry| action == format!("{}.{}", pool.name, entry.action))
+            });
+            if !exists {
+                errors.push(format!("unknown action {action}"));
+            }
+        }
+        errors
+    }
+
     pub fn with_initial_pool(self, id: CommandPoolId) -> CommandPoolArray {
         assert!(id.0 < self.pools.len());
         CommandPoolArray {
             pools: self.pools,
             id,
+            page: 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// First row of labels in the controls panel.
+    const TOP: u16 = WINDOW_HEIGHT - CONTROLS_HEIGHT + 1;
+
+    fn pool(keys: impl Iterator<Item = char>, description: &str) -> CommandPool {
+        keys.fold(CommandPoolBuilder::new("test").without_help(), |pool, c| {
+            let command = Command::new(|_| Ok(())).with_help(c.to_string());
+            pool.on_key_press(format!("press_{c}"), &[KeyBinding::char(c)], description, command)
+        })
+        .build()
+    }
+
+    fn help_at(pool: &CommandPool, page: usize, x: u16, y: u16) -> Option<String> {
+        pool.command_at(page, x, y).map(|command| command.help().to_string())
+    }
+
+    #[test]
+    fn clicks_on_a_label_find_its_command() {
+        let pool = pool('a'..='c', "Press");
+        // "    a: Press" starts a column in from the edge and is 12 wide
+        assert_eq!(help_at(&pool, 0, 1, TOP).as_deref(), Some("a"));
+        assert_eq!(help_at(&pool, 0, 12, TOP).as_deref(), Some("a"));
+        assert_eq!(help_at(&pool, 0, 5, TOP + 4).as_deref(), Some("c"));
+        assert_eq!(help_at(&pool, 0, 0, TOP), None, "the border isn't a label");
+        assert_eq!(help_at(&pool, 0, 13, TOP), None, "past the end of the label");
+        assert_eq!(help_at(&pool, 0, 1, TOP + 1), None, "between the rows");
+        assert_eq!(help_at(&pool, 0, 1, TOP + 6), None, "under the last label");
+        assert_eq!(help_at(&pool, 0, u16::MAX, u16::MAX), None);
+        assert_eq!(help_at(&pool, 0, 1, 0), None, "up in the tower");
+    }
+
+    #[test]
+    fn clicks_find_commands_on_the_page_shown() {
+        let pool = pool('a'..='z', "Press with a longish description");
+        assert!(pool.page_count() > 1);
+        let first = help_at(&pool, 0, 1, TOP).unwrap();
+        let second = help_at(&pool, 1, 1, TOP).unwrap();
+        assert_eq!(first, "a");
+        assert_ne!(second, first);
+        // Pages past the last show the last
+        let last_page = pool.page_count() - 1;
+        assert_eq!(help_at(&pool, usize::MAX, 1, TOP), help_at(&pool, last_page, 1, TOP));
+
+        let more = (WINDOW_WIDTH - MORE_INDICATOR_WIDTH + 1, TOP + 2 * (CONTROLS_ROWS - 1));
+        let help = help_at(&pool, 0, more.0, more.1).expect("the more indicator isn't clickable");
+        assert!(help.starts_with("Show the next page"), "{help}");
+    }
+}
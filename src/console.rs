@@ -0,0 +1,330 @@
+use crate::action::Action;
+use crate::game_state::{GameState, LOAN_AMOUNT, MAX_SPEED};
+use crate::layer::MAX_RENT;
+use crate::mods::Mods;
+use crate::save;
+use std::io;
+use std::rc::Rc;
+
+/// Longest line that can be typed into the console.
+const MAX_INPUT_LEN: usize = 60;
+/// Lines kept in the console history.
+const MAX_HISTORY: usize = 50;
+
+/// Commands the console understands, with their usage.
+const COMMANDS: [(&str, &str); 11] = [
+    ("build", "build <floor type>"),
+    ("demolish", "demolish <floor>"),
+    ("goto", "goto <floor>"),
+    ("rent", "rent <floor> <amount|+N%|-N%>"),
+    ("loan", "loan"),
+    ("undo", "undo"),
+    ("redo", "redo"),
+    ("save", "save <slot>"),
+    ("load", "load <slot>"),
+    ("speed", "speed <1-8>"),
+    ("help", "help [command]"),
+];
+
+/// The line being typed into the `:` console, and the lines typed before.
+#[derive(Default)]
+pub struct Console {
+    pub input: String,
+    history: Vec<String>,
+    /// Index into `history` of the line being shown while browsing it.
+    history_pos: Option<usize>,
+}
+
+impl Console {
+    pub fn open(&mut self) {
+        self.input.clear();
+        self.history_pos = None;
+    }
+
+    pub fn type_char(&mut self, c: char) {
+        if self.input.len() < MAX_INPUT_LEN && !c.is_control() {
+            self.input.push(c);
+        }
+    }
+
+    /// Takes the typed line, remembering it in the history.
+    pub fn take_input(&mut self) -> String {
+        let line = std::mem::take(&mut self.input);
+        let line = line.trim().to_string();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.history_pos = None;
+        line
+    }
+
+    /// Shows the previous line in the history.
+    pub fn history_back(&mut self) {
+        let pos = match self.history_pos {
+            Some(pos) => pos.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.history_pos = Some(pos);
+        self.input = self.history[pos].clone();
+    }
+
+    /// Shows the next line in the history, or an empty line past the end.
+    pub fn history_forward(&mut self) {
+        let Some(pos) = self.history_pos else {
+            return;
+        };
+        if pos + 1 < self.history.len() {
+            self.history_pos = Some(pos + 1);
+            self.input = self.history[pos + 1].clone();
+        } else {
+            self.history_pos = None;
+            self.input.clear();
+        }
+    }
+
+    /// Completes the word being typed as far as it can, returning the
+    /// candidates if there is more than one.
+    /// Commands and floor types added by `mods` are completed too.
+    pub fn complete(&mut self, mods: &Mods) -> Vec<String> {
+        let words: Vec<&str> = self.input.split(' ').collect();
+        let command_names = COMMANDS
+            .iter()
+            .map(|(name, _)| *name)
+            .chain(mods.commands().iter().map(|command| command.name.as_str()));
+        let candidates: Vec<String> = match words.as_slice() {
+            [word] | ["help", word] => command_names
+                .filter(|name| name.starts_with(word))
+                .map(str::to_string)
+                .collect(),
+            ["build", word] => mods
+                .floor_types()
+                .all()
+                .map(|style| &mods.floor_types().get(style).id)
+                .filter(|id| id.starts_with(word))
+                .cloned()
+                .collect(),
+            _ => Vec::new(),
+        };
+        let Some(first) = candidates.first() else {
+            return candidates;
+        };
+        let common = candidates.iter().fold(first.as_str(), |common, candidate| {
+            let len = common
+                .bytes()
+                .zip(candidate.bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            &common[..len]
+        });
+        let typed = words.last().map_or(0, |word| word.len());
+        self.input += &common[typed..];
+        if candidates.len() == 1 {
+            self.input.push(' ');
+            return Vec::new();
+        }
+        candidates
+    }
+}
+
+fn usage(command: &str) -> &'static str {
+    COMMANDS
+        .iter()
+        .find(|(name, _)| *name == command)
+        .map_or("", |(_, usage)| usage)
+}
+
+/// Parses a 1-based floor number into an index into `GameState::layers`.
+fn parse_floor(word: &str) -> Option<usize> {
+    word.parse::<usize>().ok().filter(|&floor| floor > 0).map(|floor| floor - 1)
+}
+
+/// Runs a line typed into the console.
+pub fn execute(gs: &mut GameState, line: &str) -> io::Result<()> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((&command, args)) = words.split_first() else {
+        return gs.draw_funds(0, 0);
+    };
+    match (command, args) {
+        ("build", [id]) => match gs.mods.floor_types().find(id) {
+            Some(style) => Action::Build(style).perform(gs),
+            None => gs.show_message(&format!("There is no floor type called {id}")),
+        },
+        ("demolish", [floor]) => match parse_floor(floor) {
+            Some(floor) => Action::Demolish(floor).perform(gs),
+            None => gs.show_message(&format!("There is no floor {floor}")),
+        },
+        ("goto", [floor]) => match parse_floor(floor) {
+            Some(floor) => Action::SelectFloor(floor).perform(gs),
+            None => gs.show_message(&format!("There is no floor {floor}")),
+        },
+        ("rent", [floor, amount]) => {
+            let Some(floor) = parse_floor(floor) else {
+                return gs.show_message(&format!("There is no floor {floor}"));
+            };
+            let percent = amount
+                .strip_suffix('%')
+                .and_then(|percent| percent.strip_prefix('+').unwrap_or(percent).parse().ok());
+            match (percent, amount.parse()) {
+                (Some(percent), _) => gs.adjust_rent(floor, percent),
+                (None, Ok(rent)) if (1..=MAX_RENT).contains(&rent) => {
+                    Action::SetRent { floor, rent }.perform(gs)
+                }
+                (None, _) => gs.show_message(&format!(
+                    "Usage: {}, with rent from $1 to ${MAX_RENT}",
+                    usage("rent")
+                )),
+            }
+        }
+        ("loan", []) => Action::TakeLoan(LOAN_AMOUNT).perform(gs),
+        ("undo", []) => gs.undo(),
+        ("redo", []) => gs.redo(),
+        ("save", [slot]) => match save::save_game(gs, slot) {
+            Ok(()) => gs.show_message(&format!("Saved to {slot}")),
+            Err(e) => gs.show_message(&format!("Could not save: {e}")),
+        },
+        ("load", [slot]) => match save::load_game(gs, slot) {
+            Ok(()) => {
+                gs.draw_screen()?;
+                gs.show_message(&format!("Loaded {slot}"))
+            }
+            Err(e) => gs.show_message(&format!("Could not load {slot}: {e}")),
+        },
+        ("speed", [speed]) => match speed.parse() {
+            Ok(speed) if (1..=MAX_SPEED).contains(&speed) => {
+                gs.speed = speed;
+                gs.show_message(&format!("Speed is now {speed}x"))
+            }
+            _ => gs.show_message(&format!("Usage: {}", usage("speed"))),
+        },
+        ("help", []) => {
+            let mut names: Vec<&str> = COMMANDS.iter().map(|(name, _)| *name).collect();
+            let mods = Rc::clone(&gs.mods);
+            names.extend(mods.commands().iter().map(|command| command.name.as_str()));
+            gs.show_message(&format!("Commands: {}", names.join(", ")))
+        }
+        ("help", [command]) => {
+            let mods = Rc::clone(&gs.mods);
+            let mod_command = mods.commands().iter().find(|c| c.name == *command);
+            match (usage(command), mod_command) {
+                ("", Some(mod_command)) => gs.show_message(&mod_command.help),
+                ("", None) => gs.show_message(&format!("Unknown command {command}")),
+                (usage, _) => gs.show_message(&format!("Usage: {usage}")),
+            }
+        }
+        (command, args) => match usage(command) {
+            "" => {
+                let mods = Rc::clone(&gs.mods);
+                mods.run_command(gs, command, args).unwrap_or_else(|| {
+                    gs.show_message(&format!("Unknown command {command}, try help"))
+                })
+            }
+            usage => gs.show_message(&format!("Usage: {usage}")),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::LayerType;
+    use crate::test_game::TestGame;
+
+    fn typed(line: &str) -> Console {
+        let mut console = Console::default();
+        line.chars().for_each(|c| console.type_char(c));
+        console
+    }
+
+    #[test]
+    fn tab_completes_commands_and_floor_types() {
+        let game = TestGame::new();
+        let mods = &game.gs.mods;
+        let mut console = typed("bu");
+        assert!(console.complete(mods).is_empty());
+        assert_eq!(console.input, "build ");
+        console.type_char('a');
+        console.complete(mods);
+        assert_eq!(console.input, "build apartment ");
+
+        let mut console = typed("s");
+        assert_eq!(console.complete(mods), ["save", "speed"]);
+        assert_eq!(console.input, "s");
+        let mut console = typed("help go");
+        console.complete(mods);
+        assert_eq!(console.input, "help goto ");
+        let mut console = typed("goto 1");
+        assert!(console.complete(mods).is_empty());
+        assert_eq!(console.input, "goto 1");
+    }
+
+    #[test]
+    fn history_brings_back_the_lines_typed_before() {
+        let mut console = Console::default();
+        for line in ["goto 1", "  loan ", "loan", ""] {
+            line.chars().for_each(|c| console.type_char(c));
+            console.take_input();
+        }
+        console.history_back();
+        assert_eq!(console.input, "loan", "repeats and blank lines aren't kept");
+        console.history_back();
+        assert_eq!(console.input, "goto 1");
+        console.history_back();
+        assert_eq!(console.input, "goto 1", "stops at the oldest line");
+        console.history_forward();
+        assert_eq!(console.input, "loan");
+        console.history_forward();
+        assert_eq!(console.input, "");
+        console.history_forward();
+        assert_eq!(console.input, "");
+    }
+
+    #[test]
+    fn rent_changes_by_a_percentage_of_itself() {
+        let mut game = TestGame::new();
+        game.build(LayerType::Apartment);
+        let rent = |game: &TestGame| game.gs.layers[0].revenue_per_occupant;
+
+        execute(&mut game.gs, "rent 1 100").unwrap();
+        assert_eq!(rent(&game), 100);
+        execute(&mut game.gs, "rent 1 +10%").unwrap();
+        assert_eq!(rent(&game), 110);
+        execute(&mut game.gs, "rent 1 -50%").unwrap();
+        assert_eq!(rent(&game), 55);
+        execute(&mut game.gs, "rent 1 10%").unwrap();
+        assert_eq!(rent(&game), 60);
+        execute(&mut game.gs, "rent 1 1").unwrap();
+        execute(&mut game.gs, "rent 1 +10%").unwrap();
+        assert_eq!(rent(&game), 2, "rent always changes by at least $1");
+        execute(&mut game.gs, "rent 1 +ten%").unwrap();
+        assert!(game.gs.message.starts_with("Usage: rent"), "{}", game.gs.message);
+        execute(&mut game.gs, "rent 2 +10%").unwrap();
+        assert_eq!(game.gs.message, "There is no floor 2");
+    }
+
+    #[test]
+    fn rent_stays_within_bounds() {
+        let mut game = TestGame::new();
+        game.build(LayerType::Apartment);
+        let rent = |game: &TestGame| game.gs.layers[0].revenue_per_occupant;
+
+        execute(&mut game.gs, "rent 1 3000000000").unwrap();
+        assert!(game.gs.message.starts_with("Usage: rent"), "{}", game.gs.message);
+        execute(&mut game.gs, "rent 1 0").unwrap();
+        assert!(game.gs.message.starts_with("Usage: rent"), "{}", game.gs.message);
+        execute(&mut game.gs, "rent 1 99999999999999999999%").unwrap();
+        assert!(game.gs.message.starts_with("Usage: rent"), "{}", game.gs.message);
+
+        execute(&mut game.gs, "rent 1 +1000000000%").unwrap();
+        assert_eq!(rent(&game), MAX_RENT);
+        execute(&mut game.gs, "rent 1 +50%").unwrap();
+        assert_eq!(rent(&game), MAX_RENT);
+        execute(&mut game.gs, "rent 1 -2000000000%").unwrap();
+        assert_eq!(rent(&game), 1);
+        game.gs.set_rent(0, usize::MAX).unwrap();
+        assert_eq!(rent(&game), MAX_RENT);
+    }
+}
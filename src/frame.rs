@@ -0,0 +1,474 @@
+use crate::{WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::style::{self, Attribute, Attributes, Color, Colors};
+use crossterm::{cursor, queue};
+use std::io::{self, Write};
+
+const ESC: u8 = 0x1b;
+
+/// One character on the screen and how it is drawn.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+    pub attributes: Attributes,
+}
+
+impl Cell {
+    fn blank() -> Cell {
+        Cell {
+            ch: ' ',
+            fg: Color::Reset,
+            bg: Color::Reset,
+            attributes: Attributes::default(),
+        }
+    }
+}
+
+/// The screen as it is drawn into, one cell per character.
+///
+/// All drawing code writes crossterm commands to a `FrameBuffer` as if it
+/// were the terminal. The buffer keeps track of what they would have done to
+/// each cell, and `render` sends only the cells that changed since the last
+/// frame to the real terminal, so nothing flickers and nothing old is left
+/// behind.
+pub struct FrameBuffer {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    /// What the terminal currently shows.
+    shown: Vec<Cell>,
+    cursor: (u16, u16),
+    /// Colors and attributes that printed characters get.
+    pen: Cell,
+    /// Bytes of an escape sequence or character that hasn't been written in
+    /// full yet.
+    pending: Vec<u8>,
+}
+
+impl FrameBuffer {
+    pub fn new() -> Self {
+        let (width, height) = (WINDOW_WIDTH, WINDOW_HEIGHT);
+        let cells = vec![Cell::blank(); width as usize * height as usize];
+        FrameBuffer {
+            width,
+            height,
+            shown: cells.clone(),
+            cells,
+            cursor: (0, 0),
+            pen: Cell::blank(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn index(&self, x: u16, y: u16) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    /// Sends the cells that changed since the last call to `out`.
+    pub fn render(&mut self, mut out: impl Write) -> io::Result<()> {
+        // Where the terminal's cursor and pen are, if known
+        let mut cursor = None;
+        let mut pen = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let i = self.index(x, y);
+                let cell = self.cells[i];
+                if cell == self.shown[i] {
+                    continue;
+                }
+                if cursor != Some((x, y)) {
+                    queue!(out, cursor::MoveTo(x, y))?;
+                }
+                let style = (cell.fg, cell.bg, cell.attributes);
+                if pen != Some(style) {
+                    queue!(
+                        out,
+                        style::SetAttribute(Attribute::Reset),
+                        style::SetColors(Colors::new(cell.fg, cell.bg)),
+                        style::SetAttributes(cell.attributes),
+                    )?;
+                    pen = Some(style);
+                }
+                queue!(out, style::Print(cell.ch))?;
+                cursor = Some((x + 1, y));
+            }
+        }
+        self.shown.copy_from_slice(&self.cells);
+        out.flush()
+    }
+
+    /// Copies the `size` block of `other` with its top left corner at `from`
+    /// to `to` in this buffer, as if it had been drawn here. Whatever falls
+    /// off the edge is dropped.
+    pub fn copy_from(
+        &mut self,
+        other: &FrameBuffer,
+        from: (u16, u16),
+        size: (u16, u16),
+        to: (u16, u16),
+    ) {
+        for dy in 0..size.1.min(other.height.saturating_sub(from.1)) {
+            for dx in 0..size.0.min(other.width.saturating_sub(from.0)) {
+                let (x, y) = (to.0 + dx, to.1 + dy);
+                if x < self.width && y < self.height {
+                    let i = self.index(x, y);
+                    self.cells[i] = other.cells[other.index(from.0 + dx, from.1 + dy)];
+                }
+            }
+        }
+    }
+
+    /// Forgets what the terminal shows, so the next `render` redraws every
+    /// cell. For when something else has drawn over the screen.
+    pub fn invalidate(&mut self) {
+        for cell in &mut self.shown {
+            cell.ch = '\0';
+        }
+    }
+
+    fn print(&mut self, ch: char) {
+        let (x, y) = self.cursor;
+        match ch {
+            '\r' => self.cursor.0 = 0,
+            '\n' => self.cursor.1 = (y + 1).min(self.height - 1),
+            // Line wrap is off, so anything past the right edge is lost
+            _ if x >= self.width => {}
+            _ => {
+                let i = self.index(x, y);
+                self.cells[i] = Cell { ch, ..self.pen };
+                self.cursor.0 = x + 1;
+            }
+        }
+    }
+
+    /// Blanks the cells from `start` up to but not including `end`, with the
+    /// current background color like a real terminal would.
+    fn clear(&mut self, start: usize, end: usize) {
+        let blank = Cell {
+            ch: ' ',
+            attributes: Attributes::default(),
+            ..self.pen
+        };
+        self.cells[start..end].fill(blank);
+    }
+
+    /// Carries out the control sequence `ESC [ params final`.
+    fn control_sequence(&mut self, params: &[u8], final_byte: u8) {
+        // Private sequences like showing the cursor don't change any cells
+        if params.first() == Some(&b'?') {
+            return;
+        }
+        let params = String::from_utf8_lossy(params);
+        let numbers: Vec<u16> = params.split(';').map(|n| n.parse().unwrap_or(0)).collect();
+        let n = |i: usize, default: u16| match numbers.get(i) {
+            Some(&0) | None => default,
+            Some(&n) => n,
+        };
+        let (x, y) = self.cursor;
+        let (max_x, max_y) = (self.width - 1, self.height - 1);
+        let here = self.index(x.min(max_x), y);
+        let len = self.cells.len();
+        match final_byte {
+            b'H' | b'f' => self.cursor = ((n(1, 1) - 1).min(max_x), (n(0, 1) - 1).min(max_y)),
+            b'G' => self.cursor.0 = (n(0, 1) - 1).min(max_x),
+            b'd' => self.cursor.1 = (n(0, 1) - 1).min(max_y),
+            b'A' => self.cursor.1 = y.saturating_sub(n(0, 1)),
+            b'B' => self.cursor.1 = (y + n(0, 1)).min(max_y),
+            b'C' => self.cursor.0 = (x + n(0, 1)).min(max_x),
+            b'D' => self.cursor.0 = x.saturating_sub(n(0, 1)),
+            b'J' => match numbers[0] {
+                0 => self.clear(here, len),
+                1 => self.clear(0, here + 1),
+                _ => self.clear(0, len),
+            },
+            b'K' => {
+                let line_start = self.index(0, y);
+                let line_end = line_start + self.width as usize;
+                match numbers[0] {
+                    0 => self.clear(here, line_end),
+                    1 => self.clear(line_start, here + 1),
+                    _ => self.clear(line_start, line_end),
+                }
+            }
+            b'm' => self.select_graphic_rendition(&params),
+            _ => {}
+        }
+    }
+
+    fn select_graphic_rendition(&mut self, params: &str) {
+        let mut codes = params.split(';');
+        while let Some(code) = codes.next() {
+            let attributes = &mut self.pen.attributes;
+            match code.parse::<u8>().unwrap_or(0) {
+                0 => self.pen = Cell::blank(),
+                code @ 1..=9 => {
+                    let attribute = [
+                        Attribute::Bold,
+                        Attribute::Dim,
+                        Attribute::Italic,
+                        Attribute::Underlined,
+                        Attribute::SlowBlink,
+                        Attribute::RapidBlink,
+                        Attribute::Reverse,
+                        Attribute::Hidden,
+                        Attribute::CrossedOut,
+                    ][code as usize - 1];
+                    attributes.set(attribute);
+                }
+                22 => {
+                    attributes.unset(Attribute::Bold);
+                    attributes.unset(Attribute::Dim);
+                }
+                23 => attributes.unset(Attribute::Italic),
+                24 => attributes.unset(Attribute::Underlined),
+                25 => {
+                    attributes.unset(Attribute::SlowBlink);
+                    attributes.unset(Attribute::RapidBlink);
+                }
+                27 => attributes.unset(Attribute::Reverse),
+                28 => attributes.unset(Attribute::Hidden),
+                29 => attributes.unset(Attribute::CrossedOut),
+                38 => self.pen.fg = parse_color(&mut codes),
+                39 => self.pen.fg = Color::Reset,
+                48 => self.pen.bg = parse_color(&mut codes),
+                49 => self.pen.bg = Color::Reset,
+                _ => {}
+            }
+        }
+    }
+
+    /// Works through as much of `pending` as has been written in full.
+    fn interpret(&mut self) {
+        let bytes = std::mem::take(&mut self.pending);
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] == ESC {
+                let Some(&kind) = bytes.get(i + 1) else {
+                    break;
+                };
+                if kind != b'[' {
+                    // Not a control sequence, and nothing we draw with
+                    i += 2;
+                    continue;
+                }
+                let end = bytes[i + 2..].iter().position(|b| (0x40..=0x7e).contains(b));
+                let Some(end) = end.map(|end| i + 2 + end) else {
+                    break;
+                };
+                self.control_sequence(&bytes[i + 2..end], bytes[end]);
+                i = end + 1;
+            } else {
+                let len = match bytes[i] {
+                    0xf0.. => 4,
+                    0xe0.. => 3,
+                    0xc0.. => 2,
+                    _ => 1,
+                };
+                let Some(char_bytes) = bytes.get(i..i + len) else {
+                    break;
+                };
+                let ch = std::str::from_utf8(char_bytes)
+                    .ok()
+                    .and_then(|s| s.chars().next())
+                    .unwrap_or(char::REPLACEMENT_CHARACTER);
+                self.print(ch);
+                i += len;
+            }
+        }
+        self.pending = bytes[i..].to_vec();
+    }
+}
+
+/// Looking at what was drawn, for tests.
+#[cfg(test)]
+impl FrameBuffer {
+    pub fn cell(&self, x: u16, y: u16) -> Cell {
+        self.cells[self.index(x, y)]
+    }
+
+    /// The characters on row `y`, without trailing spaces.
+    pub fn row_text(&self, y: u16) -> String {
+        let start = self.index(0, y);
+        let row = &self.cells[start..start + self.width as usize];
+        let text: String = row.iter().map(|cell| cell.ch).collect();
+        text.trim_end().to_string()
+    }
+
+    /// Every row of the screen, one per line.
+    pub fn text(&self) -> String {
+        (0..self.height).map(|y| self.row_text(y) + "\n").collect()
+    }
+
+    /// Where `needle` first appears on the screen.
+    pub fn find(&self, needle: &str) -> Option<(u16, u16)> {
+        (0..self.height).find_map(|y| {
+            let x = self.row_text(y).find(needle)?;
+            // `find` gives a byte offset, the art uses multibyte characters
+            let x = self.row_text(y)[..x].chars().count();
+            Some((x as u16, y))
+        })
+    }
+
+    /// The screen's text followed by its colors, one character per cell:
+    /// the initial of the foreground color, uppercase if the background
+    /// isn't black. Spaces are shown as `.` in the colors.
+    pub fn snapshot(&self) -> String {
+        let mut snapshot = self.text();
+        snapshot += "--- colors ---\n";
+        for y in 0..self.height {
+            let start = self.index(0, y);
+            let row = &self.cells[start..start + self.width as usize];
+            let colors: String = row
+                .iter()
+                .map(|cell| {
+                    if cell.ch == ' ' && matches!(cell.bg, Color::Black | Color::Reset) {
+                        return '.';
+                    }
+                    let c = color_initial(cell.fg);
+                    if matches!(cell.bg, Color::Black | Color::Reset) {
+                        c
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect();
+            snapshot += colors.trim_end_matches('.');
+            snapshot += "\n";
+        }
+        snapshot
+    }
+}
+
+#[cfg(test)]
+fn color_initial(color: Color) -> char {
+    match color {
+        Color::Reset => '-',
+        Color::Black => 'k',
+        Color::DarkGrey | Color::Grey => 'a',
+        Color::Red | Color::DarkRed => 'r',
+        Color::Green | Color::DarkGreen => 'g',
+        Color::Yellow | Color::DarkYellow => 'y',
+        Color::Blue | Color::DarkBlue => 'b',
+        Color::Magenta | Color::DarkMagenta => 'm',
+        Color::Cyan | Color::DarkCyan => 'c',
+        Color::White => 'w',
+        Color::Rgb { .. } | Color::AnsiValue(_) => '?',
+    }
+}
+
+/// Reads the color that follows a 38 or 48 in a graphic rendition sequence.
+fn parse_color<'a>(codes: &mut impl Iterator<Item = &'a str>) -> Color {
+    let color = match codes.next() {
+        Some("5") => codes.next().map(|n| format!("5;{n}")),
+        Some("2") => {
+            let rgb: Vec<&str> = codes.take(3).collect();
+            Some(format!("2;{}", rgb.join(";")))
+        }
+        _ => None,
+    };
+    color
+        .and_then(|color| Color::parse_ansi(&color))
+        .unwrap_or(Color::Reset)
+}
+
+impl Write for FrameBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        self.interpret();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::terminal::{self, ClearType};
+
+    fn frame_with(draw: impl FnOnce(&mut FrameBuffer) -> io::Result<()>) -> FrameBuffer {
+        let mut frame = FrameBuffer::new();
+        draw(&mut frame).unwrap();
+        frame
+    }
+
+    #[test]
+    fn prints_where_the_cursor_was_moved_to() {
+        let frame = frame_with(|frame| {
+            queue!(
+                frame,
+                cursor::MoveTo(3, 2),
+                style::SetColors(Colors::new(Color::Green, Color::Black)),
+                style::Print("héllo"),
+            )
+        });
+        assert_eq!(frame.row_text(2), "   héllo");
+        assert_eq!(frame.find("llo"), Some((5, 2)));
+        let cell = frame.cell(4, 2);
+        assert_eq!((cell.ch, cell.fg, cell.bg), ('é', Color::Green, Color::Black));
+    }
+
+    #[test]
+    fn styled_content_resets_its_color_afterwards() {
+        use crossterm::style::Stylize;
+        let frame = frame_with(|frame| {
+            queue!(frame, style::PrintStyledContent("ab".red().bold()), style::Print("c"))
+        });
+        assert_eq!(frame.cell(0, 0).fg, Color::Red);
+        assert!(frame.cell(1, 0).attributes.has(Attribute::Bold));
+        assert_eq!(frame.cell(2, 0).fg, Color::Reset);
+        assert!(!frame.cell(2, 0).attributes.has(Attribute::Bold));
+    }
+
+    #[test]
+    fn text_past_the_right_edge_is_dropped() {
+        let frame = frame_with(|frame| {
+            queue!(frame, cursor::MoveTo(WINDOW_WIDTH - 2, 0), style::Print("abcd"))
+        });
+        assert!(frame.row_text(0).ends_with("ab"));
+        assert_eq!(frame.row_text(1), "");
+    }
+
+    #[test]
+    fn clearing_from_the_cursor_up() {
+        let frame = frame_with(|frame| {
+            for y in 0..4 {
+                queue!(frame, cursor::MoveTo(0, y), style::Print("xxxx"))?;
+            }
+            queue!(frame, cursor::MoveTo(1, 2), terminal::Clear(ClearType::FromCursorUp))
+        });
+        assert_eq!(frame.text().lines().take(4).collect::<Vec<_>>(), ["", "", "  xx", "xxxx"]);
+    }
+
+    #[test]
+    fn escape_sequences_split_across_writes() {
+        let mut frame = FrameBuffer::new();
+        frame.write_all(b"\x1b[3;").unwrap();
+        frame.write_all(b"5Hok\xe2\x80").unwrap();
+        frame.write_all(b"\xa6").unwrap();
+        assert_eq!(frame.row_text(2), "    ok…");
+    }
+
+    #[test]
+    fn render_only_sends_changed_cells() {
+        let mut frame = FrameBuffer::new();
+        queue!(frame, cursor::MoveTo(10, 5), style::Print("tick")).unwrap();
+        let mut first = Vec::new();
+        frame.render(&mut first).unwrap();
+        assert!(String::from_utf8_lossy(&first).ends_with("tick"));
+
+        let mut unchanged = Vec::new();
+        frame.render(&mut unchanged).unwrap();
+        assert!(unchanged.is_empty());
+
+        queue!(frame, cursor::MoveTo(10, 5), style::Print("tock")).unwrap();
+        let mut second = Vec::new();
+        frame.render(&mut second).unwrap();
+        let second = String::from_utf8_lossy(&second);
+        assert!(second.ends_with('o'), "{second:?}");
+        assert!(!second.contains('t') && !second.contains('k'), "{second:?}");
+    }
+}
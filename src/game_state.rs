@@ -1,23 +1,156 @@
+use crate::achievements::*;
+use crate::action::*;
 use crate::command::*;
+use crate::frame::*;
+use crate::console::*;
+use crate::help::*;
+use crate::history::*;
+use crate::keymap::*;
 use crate::layer::*;
+use crate::ledger::*;
+use crate::message_log::*;
+use crate::mods::*;
+use crate::rating::*;
+use crate::rules::*;
+use crate::scenery::*;
+use crate::settings::*;
+use crate::staff::*;
+use crate::storage::DataDir;
+use crate::tax::*;
+use crate::undo::*;
+use crate::weather::*;
+use crate::TOWER_WINDOW_HEIGHT;
+use crossterm::event::{KeyCode, KeyModifiers};
 use rand::prelude::*;
+use rand::rngs::StdRng;
+use std::collections::HashSet;
+use std::fs;
 use std::io;
+use std::rc::Rc;
+
+/// Rows scrolled by page up and page down.
+const SCROLL_PAGE: i32 = TOWER_WINDOW_HEIGHT as i32 - 1;
+/// File in the data directory the ledger is exported to.
+const LEDGER_CSV_FILE: &str = "ledger.csv";
+/// How many ticks an achievement toast stays in the message area.
+const TOAST_TICKS: u32 = 5;
+/// Borrowed each time the player takes a loan.
+pub const LOAN_AMOUNT: i32 = 5_000;
+/// Most the player can owe at once.
+const MAX_LOAN: i32 = 20_000;
+/// Interest added to loans when the debt collector comes for them.
+const LOAN_INTEREST_PERCENT: i32 = 10;
+/// Fastest the game clock can be set to run, in ticks per second.
+pub const MAX_SPEED: u32 = 8;
+/// Actions that pick each rule set on the new game screen, one per number
+/// key, so at most this many can be offered.
+const RULE_SET_ACTIONS: [&str; 9] = [
+    "rules_1", "rules_2", "rules_3", "rules_4", "rules_5", "rules_6", "rules_7", "rules_8",
+    "rules_9",
+];
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Screen {
+    Tower,
+    Finances,
+    Achievements,
+    HighScores,
+    Help,
+    Staff,
+    Messages,
+    NewGame,
+}
 
 pub struct GameState {
-    pub stdout: io::StdoutLock<'static>,
+    /// What gets drawn, until it is rendered to the terminal.
+    pub frame: FrameBuffer,
     pub running: bool,
     pub restarting: bool,
     pub command_pool_array: CommandPoolArray,
 
     pub command_pool_main_id: CommandPoolId,
     pub command_pool_build_id: CommandPoolId,
+    pub command_pool_goto_id: CommandPoolId,
+    pub command_pool_console_id: CommandPoolId,
     pub command_pool_game_over_id: CommandPoolId,
+    pub command_pool_finances_id: CommandPoolId,
+    pub command_pool_achievements_id: CommandPoolId,
+    pub command_pool_high_scores_id: CommandPoolId,
+    pub command_pool_help_id: CommandPoolId,
+    pub command_pool_staff_id: CommandPoolId,
+    pub command_pool_messages_id: CommandPoolId,
+    pub command_pool_new_game_id: CommandPoolId,
 
+    pub screen: Screen,
+    /// Screens (and their command pools) to return to when leaving the
+    /// current one.
+    pub screen_stack: Vec<(Screen, CommandPoolId)>,
     pub layers: Vec<Layer>,
     pub scroll_pos: u16,
+    pub tower_view: TowerView,
+    pub scenery: Scenery,
+    /// Frames of scenery animation drawn each second. 0 turns it off.
+    pub animation_fps: u32,
+    /// Index into `layers` of the floor last clicked on.
+    pub selected_floor: Option<usize>,
+    /// Floor number typed so far in the go to floor prompt.
+    pub goto_input: String,
+    pub console: Console,
+
+    /// What was last shown in the message area, to show again when the
+    /// tower is redrawn on its own.
+    pub message: String,
+    pub message_log: MessageLog,
 
     pub funds: i32,
     pub debt_collection_timer: u32,
+    pub ledger: Ledger,
+    pub tick: u64,
+    /// Ticks per second.
+    pub speed: u32,
+    /// Number of debt collection inspections passed so far.
+    pub period: u32,
+    pub peak_funds: i32,
+    /// Money borrowed this period, repaid with interest at the next
+    /// inspection.
+    pub loan: i32,
+    pub floors_built: usize,
+    pub game_over: bool,
+    pub undo_history: UndoHistory,
+
+    pub calendar: Calendar,
+    /// People in the city looking for somewhere to live or work, when the
+    /// tower competes with another for them. Without one, there is always
+    /// someone.
+    pub tenant_pool: Option<Rc<std::cell::Cell<usize>>>,
+    pub staff: Staff,
+    pub taxes: Taxes,
+    /// The tower's star rating. Stars are never taken away once earned.
+    pub stars: u8,
+
+    pub seed: u64,
+    pub rng: StdRng,
+    pub rules: Rules,
+    /// The presets and custom rule sets a new game can be played with.
+    pub rule_sets: Vec<Rules>,
+    /// Rules to play the next game with, if not the same as this one.
+    pub next_rules: Option<Rules>,
+    pub mods: Rc<Mods>,
+    /// Mod scripts, by index, that failed in this game and are left alone
+    /// from then on. Other games sharing the mods still run them.
+    pub broken_mods: HashSet<usize>,
+
+    pub achievements: Achievements,
+    /// Message shown in place of the usual funds message, and the number of
+    /// ticks left to show it for.
+    pub toast: Option<(String, u32)>,
+
+    pub run_history: Vec<RunRecord>,
+    pub high_score_selected: usize,
+    /// Where achievements, run history, saves and settings are kept.
+    pub data_dir: DataDir,
+    /// Page of the help screen shown.
+    pub help_page: usize,
 }
 
 impl GameState {
@@ -27,35 +160,313 @@ impl GameState {
 
     pub fn draw_command_pool(&mut self) -> io::Result<()> {
         let pool = self.command_pool_array.cur();
-        pool.draw(&mut self.stdout)
+        pool.draw(&mut self.frame, self.command_pool_array.page())
+    }
+
+    pub fn next_controls_page(&mut self) -> io::Result<()> {
+        let page_count = self.command_pool().page_count();
+        let page = (self.command_pool_array.page() + 1) % page_count;
+        self.command_pool_array.set_page(page);
+        self.draw_command_pool()
     }
 
     pub fn draw_tower(&mut self) -> io::Result<()> {
-        layer_draw(&self.layers, &mut self.stdout, self.scroll_pos)
+        layer_draw(
+            &self.layers,
+            &mut self.frame,
+            self.scroll_pos,
+            self.tower_view,
+            self.selected_floor,
+            self.calendar.today(),
+            &self.scenery,
+        )
+    }
+
+    /// Scrolls the tower view to `scroll_pos`, clamped to the tower's height.
+    pub fn scroll_to(&mut self, scroll_pos: u16) -> io::Result<()> {
+        self.scroll_pos = scroll_pos.min(max_scroll(self.layers.len(), self.tower_view));
+        self.draw_tower()?;
+        self.draw_funds(0, 0)
+    }
+
+    pub fn scroll_by(&mut self, rows: i32) -> io::Result<()> {
+        let scroll_pos = (self.scroll_pos as i32 + rows).clamp(0, u16::MAX as i32);
+        self.scroll_to(scroll_pos as u16)
+    }
+
+    /// Scrolls so that `floor` (an index into `layers`) is in the middle of
+    /// the view.
+    pub fn scroll_to_floor(&mut self, floor: usize) -> io::Result<()> {
+        let height = self.tower_view.floor_height();
+        let scroll_pos =
+            floor_row(floor, self.tower_view).saturating_sub((TOWER_WINDOW_HEIGHT + height) / 2);
+        self.scroll_to(scroll_pos)
+    }
+
+    /// Moves the scenery on by a frame and redraws the tower to show it.
+    pub fn animate(&mut self) -> io::Result<()> {
+        let seconds = 1.0 / self.animation_fps.max(1) as f64;
+        self.scenery.advance(seconds, &self.layers);
+        if self.screen != Screen::Tower {
+            return Ok(());
+        }
+        self.draw_tower()?;
+        // Drawing the tower clears the funds and message beside it
+        let message = std::mem::take(&mut self.message);
+        self.show_message(&message)
+    }
+
+    /// Switches between the full and compact tower views, keeping the floor
+    /// in the middle of the view there.
+    pub fn toggle_tower_view(&mut self) -> io::Result<()> {
+        let middle = self.selected_floor.or_else(|| {
+            (TOWER_WINDOW_HEIGHT / 2..TOWER_WINDOW_HEIGHT).find_map(|y| {
+                floor_at(self.layers.len(), self.scroll_pos, self.tower_view, 0, y)
+            })
+        });
+        self.tower_view = self.tower_view.toggled();
+        match middle {
+            Some(floor) => self.scroll_to_floor(floor),
+            None => self.scroll_to(0),
+        }
+    }
+
+    /// Selects `floor` and shows its details in the message area.
+    pub fn select_floor(&mut self, floor: usize) -> io::Result<()> {
+        self.selected_floor = Some(floor);
+        self.draw_tower()?;
+        let layer = &self.layers[floor];
+        let mut msg = format!(
+            "FLOOR {}: {} - OCCUPANCY {}/{}, REVENUE ${}, {:.0}% CLEAN",
+            floor + 1,
+            layer.floor_type().name,
+            layer.occupancy,
+            layer.max_occupancy,
+            layer.revenue(),
+            layer.cleanliness * 100.0
+        );
+        if layer.broken_down {
+            msg += ", BROKEN DOWN";
+        }
+        self.show_message(&msg)
+    }
+
+    pub fn set_rent(&mut self, floor: usize, rent: usize) -> io::Result<()> {
+        let Some(layer) = self.layers.get_mut(floor) else {
+            return self.show_message(&format!("There is no floor {}", floor + 1));
+        };
+        let rent = rent.clamp(1, MAX_RENT);
+        layer.revenue_per_occupant = rent;
+        layer.set_string();
+        self.draw_tower()?;
+        let msg = format!("Rent on floor {} is now ${}", floor + 1, rent);
+        self.show_message(&msg)
+    }
+
+    /// Changes the rent on the selected floor by `percent` percent.
+    pub fn adjust_selected_rent(&mut self, percent: i32) -> io::Result<()> {
+        match self.selected_floor {
+            Some(floor) => self.adjust_rent(floor, percent),
+            None => self.show_message("Select a floor first by clicking it or going to it with g"),
+        }
+    }
+
+    /// Changes the rent on `floor` by `percent` percent.
+    pub fn adjust_rent(&mut self, floor: usize, percent: i32) -> io::Result<()> {
+        let Some(layer) = self.layers.get(floor) else {
+            return self.show_message(&format!("There is no floor {}", floor + 1));
+        };
+        let rent = layer.revenue_per_occupant as i64;
+        // Always change the rent by at least $1
+        let change = (rent * i64::from(percent) / 100).abs().max(1) * i64::from(percent.signum());
+        let rent = (rent + change).clamp(1, MAX_RENT as i64) as usize;
+        Action::SetRent { floor, rent }.perform(self)
+    }
+
+    pub fn show_goto_prompt(&mut self) -> io::Result<()> {
+        let msg = format!("GO TO FLOOR: {}_", self.goto_input);
+        self.show_message(&msg)
+    }
+
+    pub fn show_console_prompt(&mut self) -> io::Result<()> {
+        let msg = format!(":{}_", self.console.input);
+        self.show_message(&msg)
+    }
+
+    pub fn draw_screen(&mut self) -> io::Result<()> {
+        match self.screen {
+            Screen::Tower => self.draw_tower(),
+            Screen::Finances => finances_draw(
+                &mut self.frame,
+                &self.ledger,
+                self.funds,
+                self.loan,
+                self.taxes.arrears,
+                self.period,
+            ),
+            Screen::Achievements => achievements_draw(&mut self.frame, &self.achievements),
+            Screen::Staff => staff_draw(&mut self.frame, &self.staff, &self.layers),
+            Screen::Messages => message_log_draw(&mut self.frame, &self.message_log),
+            Screen::NewGame => rule_sets_draw(&mut self.frame, &self.rule_sets),
+            Screen::HighScores => {
+                high_scores_draw(&mut self.frame, &self.run_history, self.high_score_selected)
+            }
+            Screen::Help => {
+                // Help is about the menu it was opened from
+                let (_, command_pool_id) = self.screen_stack.last().copied().unwrap_or((
+                    Screen::Tower,
+                    self.command_pool_main_id,
+                ));
+                let pool = self.command_pool_array.get(command_pool_id);
+                let rules = self.command_pool_array.rules();
+                help_draw(&mut self.frame, pool, &rules, self.help_page)
+            }
+        }
     }
 
     pub fn draw_funds(&mut self, charges: i32, debits: i32) -> io::Result<()> {
         let mut msg: String = "".to_string();
         if charges > 0 {
-            if debits > 0{
+            if debits > 0 {
                 msg = format!("You were charged ${}, and made ${}", charges, debits);
+            } else {
+                msg = format!("You were charged ${}", charges);
             }
-            msg = format!("You were charged ${}", charges);
-        }else if debits > 0 {
+        } else if debits > 0 {
             msg = format!("You made ${}", debits);
         }
+        if let Some((toast, _)) = &self.toast {
+            msg = toast.clone();
+        }
         if self.debt_collection_timer == 0 {
-            if self.funds >= 0{
-                msg = "YOU PASSED THE DEBT COLLECTOR'S INSPECTION!".to_string();
-                self.debt_collection_timer = 50;
+            if self.loan > 0 {
+                let payment = self.loan + self.loan * LOAN_INTEREST_PERCENT / 100;
+                self.record(TransactionCategory::DebtPayment, None, -payment);
+                self.loan = 0;
+            }
+            if self.funds >= 0 || !self.rules.debt_game_over {
+                self.message_log.push(self.tick, "Passed the debt collector's inspection");
+                let taxes = self.collect_taxes()?;
+                msg = format!("YOU PASSED THE DEBT COLLECTOR'S INSPECTION! {taxes}");
+                self.debt_collection_timer = self.rules.inspection_interval;
+                self.period += 1;
+                self.show_message(&msg)?;
+                self.notify(GameEvent::InspectionPassed)?;
+                let mods = Rc::clone(&self.mods);
+                return mods.on_inspection(self);
             } else {
                 msg = "YOU DID NOT HAVE FUNDS TO PAY THE DEBT COLLECTOR. GAME OVER.".to_string();
-                self.enter_menu(self.command_pool_game_over_id)?;
+                if !self.game_over {
+                    self.game_over = true;
+                    self.enter_menu(self.command_pool_game_over_id)?;
+                    if let Err(e) = self.record_run() {
+                        msg += &format!(" (could not save run: {e})");
+                    }
+                }
             }
         }
-        self.funds += debits;
-        self.funds -= charges;
-        funds_draw(&mut self.stdout, self.funds, self.debt_collection_timer, &msg)
+        self.show_message(&msg)
+    }
+
+    /// Unlocks any achievements earned by `event` and toasts them.
+    pub fn notify(&mut self, event: GameEvent) -> io::Result<()> {
+        let earned = self.achievements.newly_earned(event, self);
+        if earned.is_empty() {
+            return Ok(());
+        }
+        for achievement in &earned {
+            self.achievements.unlock(*achievement);
+        }
+        let names: Vec<&str> = earned.iter().map(|a| a.name()).collect();
+        let mut msg = format!("ACHIEVEMENT UNLOCKED: {}", names.join(", "));
+        if let Err(e) = self.achievements.save(&self.data_dir) {
+            msg += &format!(" (could not save: {e})");
+        }
+        self.toast(msg)
+    }
+
+    /// Shows `msg` in place of the usual funds message for a few ticks.
+    pub fn toast(&mut self, msg: String) -> io::Result<()> {
+        self.show_message(&msg)?;
+        self.message_log.push(self.tick, msg.clone());
+        self.toast = Some((msg, TOAST_TICKS));
+        Ok(())
+    }
+
+    /// Counts down the current toast, removing it once it has been shown for
+    /// long enough.
+    pub fn age_toast(&mut self) {
+        if let Some((_, ticks)) = &mut self.toast {
+            *ticks -= 1;
+            if *ticks == 0 {
+                self.toast = None;
+            }
+        }
+    }
+
+    pub fn show_message(&mut self, msg: &str) -> io::Result<()> {
+        self.message = msg.to_string();
+        funds_draw(
+            &mut self.frame,
+            self.funds,
+            self.loan,
+            self.debt_collection_timer,
+            self.stars,
+            msg,
+        )?;
+        weather_draw(&mut self.frame, &self.calendar)
+    }
+
+    /// Applies `amount` to the funds and records it in the ledger. The funds
+    /// stop at the most and least they can hold rather than wrapping.
+    pub fn record(&mut self, category: TransactionCategory, floor: Option<usize>, amount: i32) {
+        self.funds = self.funds.saturating_add(amount);
+        self.peak_funds = self.peak_funds.max(self.funds);
+        self.ledger.record(Transaction {
+            tick: self.tick,
+            period: self.period,
+            category,
+            floor,
+            amount,
+        });
+    }
+
+    /// Switches to `screen`, remembering the current screen so `pop_screen`
+    /// can return to it.
+    pub fn push_screen(
+        &mut self,
+        screen: Screen,
+        command_pool_id: CommandPoolId,
+    ) -> io::Result<()> {
+        self.screen_stack
+            .push((self.screen, self.command_pool_array.id()));
+        self.screen = screen;
+        self.enter_menu(command_pool_id)?;
+        self.draw_screen()?;
+        self.draw_funds(0, 0)
+    }
+
+    pub fn pop_screen(&mut self) -> io::Result<()> {
+        let Some((screen, command_pool_id)) = self.screen_stack.pop() else {
+            return Ok(());
+        };
+        self.screen = screen;
+        self.enter_menu(command_pool_id)?;
+        self.draw_screen()?;
+        self.draw_funds(0, 0)
+    }
+
+    pub fn export_ledger(&mut self) -> io::Result<()> {
+        let path = match self.data_dir.file(LEDGER_CSV_FILE) {
+            Ok(path) => path,
+            Err(e) => return self.show_message(&format!("Could not export ledger: {e}")),
+        };
+        let result = fs::File::create(&path)
+            .and_then(|file| self.ledger.write_csv(io::BufWriter::new(file)));
+        match result {
+            Ok(()) => self.show_message(&format!("Exported ledger to {}", path.display())),
+            Err(e) => self.show_message(&format!("Could not export ledger: {e}")),
+        }
     }
 
     pub fn enter_menu(&mut self, command_pool_id: CommandPoolId) -> io::Result<()> {
@@ -63,114 +474,1085 @@ impl GameState {
         self.draw_command_pool()
     }
 
-    pub fn add_layer(&mut self, style: LayerType) {
-        let (max_occupancy, revenue_per_occupant) = match style {
-            LayerType::Apartment => (20, 17),
-            LayerType::Food => (30, 7),
-            LayerType::Retail => (10, 12),
+    pub fn run_record(&self) -> RunRecord {
+        RunRecord {
+            date: today(),
+            seed: self.seed,
+            peak_funds: self.peak_funds,
+            floors_built: self.floors_built,
+            inspections_survived: self.period,
+            ticks_lasted: self.tick,
+        }
+    }
+
+    pub fn record_run(&mut self) -> io::Result<()> {
+        append_history(&self.data_dir, &self.run_record())
+    }
+
+    /// Records the run when the game ends without a game over, by quitting
+    /// or starting another, as a game over records it straight away. Games
+    /// that never got going aren't runs.
+    pub fn end_run(&mut self) -> io::Result<()> {
+        if self.game_over || self.tick == 0 {
+            return Ok(());
+        }
+        self.record_run()
+    }
+
+    pub fn show_help(&mut self) -> io::Result<()> {
+        self.help_page = 0;
+        self.push_screen(Screen::Help, self.command_pool_help_id)
+    }
+
+    pub fn next_help_page(&mut self) -> io::Result<()> {
+        let Some(&(_, command_pool_id)) = self.screen_stack.last() else {
+            return Ok(());
         };
-        let mut new_layer = Layer::new(style, revenue_per_occupant, max_occupancy);
+        let pool = self.command_pool_array.get(command_pool_id);
+        let page_count = help_page_count(pool, &self.command_pool_array.rules());
+        self.help_page = (self.help_page + 1) % page_count;
+        self.draw_screen()?;
+        self.draw_funds(0, 0)
+    }
+
+    pub fn show_high_scores(&mut self) -> io::Result<()> {
+        self.run_history = load_history(&self.data_dir);
+        self.high_score_selected = 0;
+        self.push_screen(Screen::HighScores, self.command_pool_high_scores_id)
+    }
+
+    pub fn select_high_score(&mut self, selected: usize) -> io::Result<()> {
+        if selected < self.run_history.len() {
+            self.high_score_selected = selected;
+            self.draw_screen()?;
+            self.draw_funds(0, 0)?;
+        }
+        Ok(())
+    }
+
+    pub fn add_layer(&mut self, style: LayerType) {
+        let floor_type = self.mods.floor_types().get(style);
+        let mut new_layer = Layer::new(
+            self.mods.floor_types(),
+            style,
+            floor_type.base_rent,
+            floor_type.max_occupancy,
+        );
         new_layer.set_string();
         self.layers.push(new_layer);
     }
 
+    /// Builds a floor of `style` on top of the tower, returning what it cost.
+    pub fn build_layer(&mut self, style: LayerType) -> io::Result<i32> {
+        let cost = self.rules.price(self.mods.floor_types().get(style).price);
+        self.add_layer(style);
+        self.record(
+            TransactionCategory::Construction,
+            Some(self.layers.len() - 1),
+            -cost,
+        );
+        self.floors_built += 1;
+        self.scenery.start_construction(self.layers.len() - 1);
+        self.draw_tower()?;
+        self.draw_funds(cost, 0)?;
+        self.notify(GameEvent::FloorBuilt(style))?;
+        let mods = Rc::clone(&self.mods);
+        mods.on_build(self, style)?;
+        Ok(cost)
+    }
+
+    /// Removes `floor` from the tower, returning it so it can be put back.
+    pub fn demolish(&mut self, floor: usize) -> io::Result<Option<Layer>> {
+        if floor >= self.layers.len() {
+            self.show_message(&format!("There is no floor {}", floor + 1))?;
+            return Ok(None);
+        }
+        let mut layer = self.layers.remove(floor);
+        // The occupants go back to the city, and the floor remembers how
+        // many there were in case it is put back
+        let occupancy = layer.occupancy;
+        layer.set_occupancy(0, self.tenant_pool.as_deref());
+        layer.occupancy = occupancy;
+        self.fit_view();
+        self.draw_tower()?;
+        let msg = format!("Demolished floor {}: {}", floor + 1, layer.floor_type().name);
+        self.show_message(&msg)?;
+        Ok(Some(layer))
+    }
+
+    /// Demolishes the selected floor.
+    pub fn demolish_selected(&mut self) -> io::Result<()> {
+        match self.selected_floor {
+            Some(floor) => Action::Demolish(floor).perform(self),
+            None => self.show_message("Select a floor first by clicking it or going to it with g"),
+        }
+    }
+
+    /// Selects the floor `change` floors above the selected one, or the top
+    /// floor if none is selected.
+    pub fn select_next_floor(&mut self, change: i32) -> io::Result<()> {
+        let Some(top) = self.layers.len().checked_sub(1) else {
+            return self.show_message("There are no floors to select yet");
+        };
+        let floor = match self.selected_floor {
+            Some(floor) => (floor as i32 + change).clamp(0, top as i32) as usize,
+            None => top,
+        };
+        Action::SelectFloor(floor).perform(self)
+    }
+
+    /// Borrows `amount`, returning whether the lender agreed.
+    pub fn take_loan(&mut self, amount: i32) -> io::Result<bool> {
+        if self.loan + amount > MAX_LOAN {
+            let msg = format!("The bank won't lend you more than ${MAX_LOAN} at once");
+            self.show_message(&msg)?;
+            return Ok(false);
+        }
+        self.loan += amount;
+        self.record(TransactionCategory::Loan, None, amount);
+        let msg = format!(
+            "Borrowed ${amount}. The debt collector wants it back with {LOAN_INTEREST_PERCENT}% \
+             interest"
+        );
+        self.show_message(&msg)?;
+        Ok(true)
+    }
+
+    /// Keeps the selection and scroll position inside the tower after floors
+    /// are added or removed.
+    fn fit_view(&mut self) {
+        self.selected_floor = self.selected_floor.filter(|&floor| floor < self.layers.len());
+        self.scroll_pos = self.scroll_pos.min(max_scroll(self.layers.len(), self.tower_view));
+    }
+
+    pub fn undo(&mut self) -> io::Result<()> {
+        let entry = match self.undo_history.pop_undo(self.tick, self.period, self.stars) {
+            Ok(entry) => entry,
+            Err(msg) => return self.show_message(msg),
+        };
+        match entry.reversal {
+            Reversal::Build { cost } => {
+                if let Some(mut layer) = self.layers.pop() {
+                    layer.set_occupancy(0, self.tenant_pool.as_deref());
+                }
+                self.floors_built -= 1;
+                let floor = self.layers.len();
+                self.record(TransactionCategory::Construction, Some(floor), cost);
+            }
+            Reversal::Demolish { floor, mut layer } => {
+                let occupancy = std::mem::take(&mut layer.occupancy);
+                layer.set_occupancy(occupancy, self.tenant_pool.as_deref());
+                layer.set_string();
+                self.layers.insert(floor, layer);
+            }
+            Reversal::SetRent { floor, rent } => {
+                let layer = &mut self.layers[floor];
+                layer.revenue_per_occupant = rent;
+                layer.set_string();
+            }
+            Reversal::TakeLoan(amount) => {
+                self.loan -= amount;
+                self.record(TransactionCategory::Loan, None, -amount);
+            }
+        }
+        self.undo_history.push_redo(entry.action);
+        self.fit_view();
+        self.draw_tower()?;
+        let msg = format!("Undid {}", entry.action.describe(self.mods.floor_types()));
+        self.show_message(&msg)
+    }
+
+    pub fn redo(&mut self) -> io::Result<()> {
+        let Some(action) = self.undo_history.pop_redo() else {
+            return self.show_message("Nothing to redo");
+        };
+        if action.apply(self)? {
+            let msg = format!("Redid {}", action.describe(self.mods.floor_types()));
+            self.show_message(&msg)?;
+        }
+        Ok(())
+    }
+
+    /// Charges each floor what the weather costs it in utilities, returning
+    /// the total.
+    pub fn charge_utilities(&mut self) -> i32 {
+        let weather = self.calendar.today();
+        let mut total = 0;
+        for floor in 0..self.layers.len() {
+            let layer = &self.layers[floor];
+            let cost = layer.occupancy as i32 * weather.utility_cost(layer.style);
+            if cost > 0 {
+                self.record(TransactionCategory::Upkeep, Some(floor), -cost);
+            }
+            total += cost;
+        }
+        total
+    }
+
+    /// Hires (or with a negative `change`, lets go of) staff in `role`.
+    pub fn change_staff(&mut self, role: StaffRole, change: i32) -> io::Result<()> {
+        let headcount = self.staff.headcount(role);
+        let max_headcount = role.max_headcount(self.layers.len());
+        if change > 0 && headcount >= max_headcount {
+            let msg = format!("{}: the tower can't employ more than {max_headcount}", role.name());
+            return self.show_message(&msg);
+        }
+        let headcount = match usize::try_from(change) {
+            Ok(hired) => headcount.saturating_add(hired).min(max_headcount),
+            Err(_) => headcount.saturating_sub(change.unsigned_abs() as usize),
+        };
+        self.staff.set_headcount(role, headcount);
+        self.draw_screen()?;
+        let msg = format!(
+            "{}: {headcount}, looking after {} floors for ${} a tick",
+            role.name(),
+            (headcount * role.floors_covered()).min(self.layers.len()),
+            role.wage() * headcount as i32
+        );
+        self.show_message(&msg)
+    }
+
+    /// Bills the tower for this period's taxes at an inspection, logging
+    /// the bill line by line. What can't be paid is carried to the next
+    /// inspection with a penalty, and if it is still owed then, floors are
+    /// seized from the top of the tower and sold to cover it. Returns a
+    /// summary for the message area.
+    pub fn collect_taxes(&mut self) -> io::Result<String> {
+        let revenue = self.ledger.period_total(self.period, TransactionCategory::Rent);
+        let bill = self.taxes.bill(&self.layers, revenue, &self.rules, &mut self.rng);
+        let total = bill.total();
+        self.message_log.push(self.tick, format!("TAX BILL for period {}", self.period + 1));
+        for (item, amount) in &bill.items {
+            self.message_log.push(self.tick, format!("  {item:<48}{amount:>10}"));
+        }
+        self.message_log.push(self.tick, format!("  {:<48}{total:>10}", "Total"));
+
+        let paid = total.min(self.funds.max(0));
+        if paid > 0 {
+            self.record(TransactionCategory::Taxes, None, -paid);
+        }
+        let mut unpaid = total - paid;
+        let mut seized = 0;
+        if bill.overdue {
+            while unpaid > 0 {
+                let Some(mut layer) = self.layers.pop() else {
+                    break;
+                };
+                // The occupants go back to looking for somewhere to live
+                layer.set_occupancy(0, self.tenant_pool.as_deref());
+                let sale = self.taxes.seizure_sale(&layer, &self.rules);
+                let msg = format!(
+                    "  The city seized floor {} ({}) and sold it for ${sale}",
+                    self.layers.len() + 1,
+                    layer.floor_type().name
+                );
+                self.message_log.push(self.tick, msg);
+                seized += 1;
+                unpaid -= sale;
+            }
+            if unpaid < 0 {
+                // Whatever the sale raised over the back taxes comes back
+                self.record(TransactionCategory::Taxes, None, -unpaid);
+                unpaid = 0;
+            }
+        }
+        self.taxes.arrears = unpaid;
+        if seized > 0 {
+            self.selected_floor = self.selected_floor.filter(|&floor| floor < self.layers.len());
+            self.undo_history.clear();
+            self.fit_view();
+            self.draw_screen()?;
+        }
+
+        let summary = if seized > 0 {
+            let floors = if seized == 1 { "floor" } else { "floors" };
+            format!("The city seized {seized} {floors} for back taxes.")
+        } else if unpaid > 0 {
+            format!("${unpaid} of ${total} taxes unpaid: pay next time or lose floors!")
+        } else {
+            format!("Taxes paid: ${total}.")
+        };
+        self.message_log.push(self.tick, format!("  {summary}"));
+        Ok(summary + " (m: messages)")
+    }
+
+    /// Shows the rule sets a new game can be played with.
+    pub fn show_new_game(&mut self) -> io::Result<()> {
+        self.push_screen(Screen::NewGame, self.command_pool_new_game_id)
+    }
+
+    /// Throws away this game for a new one played by `rules`.
+    pub fn start_new_game(&mut self, rules: Rules) -> io::Result<()> {
+        self.next_rules = Some(rules);
+        self.restarting = true;
+        Ok(())
+    }
+
+    /// Awards any stars the tower has earned, celebrating each one and what
+    /// it unlocks.
+    pub fn update_rating(&mut self) -> io::Result<()> {
+        let stars = TowerStats::of(&self.layers, self.funds, self.loan).stars();
+        if stars <= self.stars {
+            return Ok(());
+        }
+        // Several stars can come at once, unlocking everything up to the last
+        let unlocked: Vec<_> = (self.stars + 1..=stars)
+            .flat_map(|star| unlocks(star, self.mods.floor_types()))
+            .collect();
+        let mut msg = format!("{} YOUR TOWER NOW HAS {stars} STARS!", stars_text(stars));
+        if !unlocked.is_empty() {
+            msg += &format!(" Unlocked: {}", unlocked.join(", "));
+        }
+        self.stars = stars;
+        self.toast(msg)
+    }
+
+    /// Says what the tower needs for its next star.
+    pub fn show_rating(&mut self) -> io::Result<()> {
+        let stats = TowerStats::of(&self.layers, self.funds, self.loan);
+        let msg = format!("{}: {}", stars_text(self.stars), stats.next_star(self.stars));
+        self.show_message(&msg)
+    }
+
+    /// Cleans and repairs the selected floor, for a fifth of its price.
+    pub fn renovate_selected(&mut self) -> io::Result<()> {
+        if self.stars < RENOVATE_STARS {
+            let msg = format!("Renovation needs a {RENOVATE_STARS}-star tower");
+            return self.show_message(&msg);
+        }
+        let Some(floor) = self.selected_floor.filter(|&floor| floor < self.layers.len()) else {
+            return self
+                .show_message("Select a floor first by clicking it or going to it with g");
+        };
+        let cost = self.rules.price(self.layers[floor].floor_type().price) / 5;
+        let layer = &mut self.layers[floor];
+        layer.cleanliness = 1.0;
+        layer.broken_down = false;
+        self.record(TransactionCategory::Construction, Some(floor), -cost);
+        self.draw_tower()?;
+        self.draw_funds(cost, 0)?;
+        self.show_message(&format!("Renovated floor {} for ${cost}", floor + 1))
+    }
+
+    /// Pays the staff for a tick, returning what it cost.
+    pub fn pay_wages(&mut self) -> i32 {
+        let wages = self.staff.wages();
+        if wages > 0 {
+            self.record(TransactionCategory::Wages, None, -wages);
+        }
+        wages
+    }
+
     pub fn update_occupancies(&mut self) {
         let total_occupants: usize = self.layers.iter().map(|x| x.occupancy).sum();
-        let move_in_thresh = 1.0 - 0.4 * std::f64::consts::E.powf(-0.01 * total_occupants as f64);
-        let move_out_thresh = 0.3 * std::f64::consts::E.powf(-0.005 * total_occupants as f64);
+        let rules = &self.rules;
+        let move_in_thresh = 1.0
+            - rules.move_in_base * (-rules.move_in_decay * total_occupants as f64).exp();
+        let move_out_thresh =
+            rules.move_out_base * (-rules.move_out_decay * total_occupants as f64).exp();
+        let weather = self.calendar.today();
         for layer in &mut self.layers {
-            let n = thread_rng().gen_range(0.0..1.0);
-            if n < move_out_thresh {
-                layer.occupancy = layer.occupancy.saturating_sub(1);
-            } else if n < move_in_thresh {
-                layer.occupancy = layer.max_occupancy.min(layer.occupancy + 1);
+            // Rent above the usual rate drives people out and keeps them from
+            // moving in, and rent below it does the opposite. The weather
+            // works the same way, by making floors busier or quieter, and so
+            // does how well the staff keep the floor.
+            let rent_factor = layer.rent_factor() / (weather.traffic(layer.style) * layer.appeal());
+            let move_out_thresh = move_out_thresh * rent_factor;
+            let move_in_thresh = move_out_thresh + (move_in_thresh - move_out_thresh) / rent_factor;
+            let n = self.rng.gen_range(0.0..1.0);
+            let tenant_pool = self.tenant_pool.as_deref();
+            if n < move_out_thresh && layer.occupancy > 0 {
+                layer.set_occupancy(layer.occupancy - 1, tenant_pool);
+            } else if n >= move_out_thresh && n < move_in_thresh {
+                layer.set_occupancy(layer.occupancy + 1, tenant_pool);
             }
             layer.set_string();
         }
     }
 }
 
-pub fn init_game_state(stdout: io::StdoutLock<'static>) -> GameState {
-    let mut pool_array_builder = CommandPoolArrayBuilder::new();
+/// A new game played by `rules`, with any changes mods make to them, that
+/// keeps its files in `data_dir`.
+pub fn init_game_state(
+    frame: FrameBuffer,
+    mods: Rc<Mods>,
+    mut rules: Rules,
+    data_dir: DataDir,
+) -> GameState {
+    mods.apply_rules(&mut rules);
+    let (custom_rules, mut rule_errors) = Rules::load_custom(&data_dir);
+    let mut rule_sets = Rules::presets();
+    rule_sets.extend(custom_rules);
+    // There is only a key for so many
+    if rule_sets.len() > RULE_SET_ACTIONS.len() {
+        let limit = RULE_SET_ACTIONS.len();
+        for rules in rule_sets.split_off(limit) {
+            let name = rules.name;
+            rule_errors.push(format!("{name}: left out, there can only be {limit} rule sets"));
+        }
+    }
+
+    let mut pool_array_builder = CommandPoolArrayBuilder::new(Keymap::load(&data_dir));
 
     let command_pool_main_id = pool_array_builder.add_pool(
-        CommandPoolBuilder::new()
-            .on_letter_press(
-                'w',
+        CommandPoolBuilder::new("main")
+            .on_key_press(
+                "scroll_up",
+                &[KeyBinding::char('w'), KeyBinding::key(KeyCode::Up)],
                 "Scroll up",
-                Command::new(|gs| {
-                    gs.scroll_pos += 1;
-                    gs.draw_tower()?;
-                    gs.draw_funds(0, 0)
-                }),
+                Command::with_triggered_on_repeat(|gs| gs.scroll_by(1), true)
+                    .with_help("Scroll the tower view up one row. Hold to keep scrolling."),
             )
-            .on_letter_press(
-                's',
+            .on_key_press(
+                "scroll_down",
+                &[KeyBinding::char('s'), KeyBinding::key(KeyCode::Down)],
                 "Scroll down",
+                Command::with_triggered_on_repeat(|gs| gs.scroll_by(-1), true)
+                    .with_help("Scroll the tower view down one row. Hold to keep scrolling."),
+            )
+            .on_key_press(
+                "page_up",
+                &[KeyBinding::key(KeyCode::PageUp)],
+                "Page up",
+                Command::with_triggered_on_repeat(|gs| gs.scroll_by(SCROLL_PAGE), true)
+                    .with_help("Scroll the tower view up a whole screen."),
+            )
+            .on_key_press(
+                "page_down",
+                &[KeyBinding::key(KeyCode::PageDown)],
+                "Page down",
+                Command::with_triggered_on_repeat(|gs| gs.scroll_by(-SCROLL_PAGE), true)
+                    .with_help("Scroll the tower view down a whole screen."),
+            )
+            .on_key_press(
+                "ground",
+                &[KeyBinding::key(KeyCode::Home)],
+                "Ground",
+                Command::new(|gs| gs.scroll_to(0))
+                    .with_help("Scroll all the way down to the ground floor."),
+            )
+            .on_key_press(
+                "roof",
+                &[KeyBinding::key(KeyCode::End)],
+                "Roof",
+                Command::new(|gs| gs.scroll_to(max_scroll(gs.layers.len(), gs.tower_view)))
+                    .with_help("Scroll all the way up to the roof."),
+            )
+            .on_key_press(
+                "compact",
+                &[KeyBinding::char('v')],
+                "Compact view",
+                Command::new(|gs| gs.toggle_tower_view()).with_help(
+                    "Switch between showing each floor in full and as a single line, to see \
+                     more of a tall tower at once.",
+                ),
+            )
+            .on_key_press(
+                "goto",
+                &[KeyBinding::char('g')],
+                "Go to floor",
                 Command::new(|gs| {
-                    gs.scroll_pos = gs.scroll_pos.saturating_sub(1);
-                    gs.draw_tower()?;
-                    gs.draw_funds(0, 0)
-                }),
+                    gs.goto_input.clear();
+                    gs.enter_menu(gs.command_pool_goto_id)?;
+                    gs.show_goto_prompt()
+                })
+                .with_help("Type a floor number and press Enter to jump to that floor."),
+            )
+            .on_key_press(
+                "console",
+                &[KeyBinding::char(':')],
+                "Console",
+                Command::new(|gs| {
+                    gs.console.open();
+                    gs.enter_menu(gs.command_pool_console_id)?;
+                    gs.show_console_prompt()
+                })
+                .with_help("Type commands like `build food` or `rent 3 +10%`. Try `help`."),
+            )
+            .on_key_press(
+                "rent_up",
+                &[KeyBinding::char('+'), KeyBinding::char('=')],
+                "Raise rent",
+                Command::with_triggered_on_repeat(|gs| gs.adjust_selected_rent(10), true)
+                    .with_help("Raise rent on the selected floor by 10%. Fewer people stay.")
+                    .with_rule(
+                        "Every tick, each floor's occupants pay rent: a floor earns its \
+                         occupancy times its rent per occupant.",
+                    ),
+            )
+            .on_key_press(
+                "rent_down",
+                &[KeyBinding::char('-')],
+                "Lower rent",
+                Command::with_triggered_on_repeat(|gs| gs.adjust_selected_rent(-10), true)
+                    .with_help("Lower rent on the selected floor by 10%. More people move in."),
+            )
+            .on_key_press(
+                "demolish",
+                &[KeyBinding::char('d')],
+                "Demolish",
+                Command::new(|gs| gs.demolish_selected())
+                    .with_help("Tear down the selected floor. You get nothing back for it."),
+            )
+            .on_key_press(
+                "loan",
+                &[KeyBinding::char('l')],
+                "Take loan",
+                Command::new(|gs| Action::TakeLoan(LOAN_AMOUNT).perform(gs))
+                    .with_help(format!(
+                        "Borrow ${LOAN_AMOUNT}. It is repaid with {LOAN_INTEREST_PERCENT}% \
+                         interest at the next inspection."
+                    )),
             )
-            .on_letter_press(
-                'b',
+            .on_key_press(
+                "undo",
+                &[KeyBinding::char('u')],
+                "Undo",
+                Command::new(|gs| gs.undo()).with_help(
+                    "Take back your last build, demolition, rent change or loan. Only works \
+                     for a couple of ticks.",
+                ),
+            )
+            .on_key_press(
+                "redo",
+                &[KeyBinding::new(KeyCode::Char('r'), KeyModifiers::CONTROL)],
+                "Redo",
+                Command::new(|gs| gs.redo()).with_help("Do the last thing you undid again."),
+            )
+            .on_key_press(
+                "build",
+                &[KeyBinding::char('b')],
                 "Build",
-                Command::new(|gs| gs.enter_menu(gs.command_pool_build_id)),
+                Command::new(|gs| gs.enter_menu(gs.command_pool_build_id))
+                    .with_help("Open the build menu to add a new floor on top of the tower.")
+                    .with_rule(
+                        "Floors cost money up front, so make sure rent will put you back in \
+                         the green before the next inspection.",
+                    ),
+            )
+            .on_key_press(
+                "finances",
+                &[KeyBinding::char('f')],
+                "Finances",
+                Command::new(|gs| gs.push_screen(Screen::Finances, gs.command_pool_finances_id))
+                    .with_help("Show income statements for each debt collection period.")
+                    .with_rule(rules.inspection_rule()),
+            )
+            .on_key_press(
+                "rating",
+                &[KeyBinding::char('R')],
+                "Rating",
+                Command::new(|gs| gs.show_rating())
+                    .with_help("See what the tower needs for its next star.")
+                    .with_rule(
+                        "People move in more readily and move out less often the more \
+                         occupants the tower has in total, so a busy tower fills up faster.",
+                    ),
+            )
+            .on_key_press(
+                "messages",
+                &[KeyBinding::char('m')],
+                "Messages",
+                Command::new(|gs| gs.push_screen(Screen::Messages, gs.command_pool_messages_id))
+                    .with_help("Read back over achievements, weather, incidents and tax bills."),
+            )
+            .on_key_press(
+                "staff",
+                &[KeyBinding::char('S')],
+                "Staff",
+                Command::new(|gs| gs.push_screen(Screen::Staff, gs.command_pool_staff_id))
+                    .with_help("Hire janitors, security and maintenance to look after floors."),
+            )
+            .on_key_press(
+                "achievements",
+                &[KeyBinding::char('a')],
+                "Achievements",
+                Command::new(|gs| {
+                    gs.push_screen(Screen::Achievements, gs.command_pool_achievements_id)
+                })
+                .with_help("List the achievements you have unlocked so far."),
+            )
+            .on_key_press(
+                "high_scores",
+                &[KeyBinding::char('h')],
+                "High scores",
+                Command::new(|gs| gs.show_high_scores())
+                    .with_help("Browse your finished runs, best score first."),
+            )
+            .build(),
+    );
+
+    let command_pool_goto_id = pool_array_builder.add_pool(
+        CommandPoolBuilder::new("goto")
+            .on_text_input(|gs, c| {
+                if c.is_ascii_digit() && gs.goto_input.len() < 4 {
+                    gs.goto_input.push(c);
+                }
+                gs.show_goto_prompt()
+            })
+            .on_key_press(
+                "go",
+                &[KeyBinding::key(KeyCode::Enter)],
+                "Go",
+                Command::new(|gs| {
+                    gs.enter_menu(gs.command_pool_main_id)?;
+                    match gs.goto_input.parse::<usize>() {
+                        Ok(floor) if floor > 0 => Action::SelectFloor(floor - 1).perform(gs),
+                        _ => gs.show_message(&format!("There is no floor {}", gs.goto_input)),
+                    }
+                })
+                .with_help("Jump to the floor number typed so far and select it."),
+            )
+            .on_key_press(
+                "delete",
+                &[KeyBinding::key(KeyCode::Backspace)],
+                "Delete",
+                Command::with_triggered_on_repeat(
+                    |gs| {
+                        gs.goto_input.pop();
+                        gs.show_goto_prompt()
+                    },
+                    true,
+                )
+                .with_help("Delete the last digit typed."),
+            )
+            .on_key_press(
+                "cancel",
+                &[KeyBinding::key(KeyCode::Esc)],
+                "Cancel",
+                Command::new(|gs| {
+                    gs.enter_menu(gs.command_pool_main_id)?;
+                    gs.draw_funds(0, 0)
+                })
+                .with_help("Go back to the main menu without moving."),
             )
             .build(),
     );
 
+    let command_pool_console_id = pool_array_builder.add_pool(
+        CommandPoolBuilder::new("console")
+            // `?` is for typing, and the console has its own help command
+            .without_help()
+            .on_text_input(|gs, c| {
+                gs.console.type_char(c);
+                gs.show_console_prompt()
+            })
+            .on_key_press(
+                "run",
+                &[KeyBinding::key(KeyCode::Enter)],
+                "Run",
+                Command::new(|gs| {
+                    let line = gs.console.take_input();
+                    gs.enter_menu(gs.command_pool_main_id)?;
+                    crate::console::execute(gs, &line)
+                })
+                .with_help("Run the command typed so far."),
+            )
+            .on_key_press(
+                "complete",
+                &[KeyBinding::key(KeyCode::Tab)],
+                "Complete",
+                Command::new(|gs| {
+                    let mods = Rc::clone(&gs.mods);
+                    let candidates = gs.console.complete(&mods);
+                    gs.show_console_prompt()?;
+                    if !candidates.is_empty() {
+                        let msg = format!(":{}_   {}", gs.console.input, candidates.join(" "));
+                        gs.show_message(&msg)?;
+                    }
+                    Ok(())
+                })
+                .with_help("Complete the command or floor type being typed."),
+            )
+            .on_key_press(
+                "previous",
+                &[KeyBinding::key(KeyCode::Up)],
+                "Previous",
+                Command::new(|gs| {
+                    gs.console.history_back();
+                    gs.show_console_prompt()
+                })
+                .with_help("Bring back the command typed before."),
+            )
+            .on_key_press(
+                "next",
+                &[KeyBinding::key(KeyCode::Down)],
+                "Next",
+                Command::new(|gs| {
+                    gs.console.history_forward();
+                    gs.show_console_prompt()
+                })
+                .with_help("Go forward again through the commands typed before."),
+            )
+            .on_key_press(
+                "delete",
+                &[KeyBinding::key(KeyCode::Backspace)],
+                "Delete",
+                Command::with_triggered_on_repeat(
+                    |gs| {
+                        gs.console.input.pop();
+                        gs.show_console_prompt()
+                    },
+                    true,
+                )
+                .with_help("Delete the last character typed."),
+            )
+            .on_key_press(
+                "cancel",
+                &[KeyBinding::key(KeyCode::Esc)],
+                "Cancel",
+                Command::new(|gs| {
+                    gs.enter_menu(gs.command_pool_main_id)?;
+                    gs.draw_funds(0, 0)
+                })
+                .with_help("Close the console without running anything."),
+            )
+            .build(),
+    );
+
+    let mut build_pool_builder = CommandPoolBuilder::new("build");
+    let floor_types = mods.floor_types();
+    for style in floor_types.all() {
+        let floor_type = floor_types.get(style);
+        let mut label = format!("{}- ${}", floor_type.label, rules.price(floor_type.price));
+        if floor_type.stars > 1 {
+            label += &format!(" {}★", floor_type.stars);
+        }
+        let mut command = Command::new(move |gs| {
+            Action::Build(style).perform(gs)?;
+            gs.enter_menu(gs.command_pool_main_id)
+        })
+        .with_help(format!(
+            "Build {}: room for {} {} paying ${} each per tick.",
+            floor_type.label.to_lowercase(),
+            floor_type.max_occupancy,
+            floor_type.occupants,
+            floor_type.base_rent
+        ));
+        if let Some(rule) = Weather::rule(style) {
+            command = command.with_rule(rule);
+        }
+        build_pool_builder = build_pool_builder.on_key_press(
+            floor_type.id.clone(),
+            &[KeyBinding::char(floor_type.key)],
+            label,
+            command,
+        );
+    }
     let command_pool_build_id = pool_array_builder.add_pool(
-        CommandPoolBuilder::new()
-            .on_letter_press('f', "Food court- $10000", Command::new(|gs| {
-                gs.add_layer(LayerType::Food);
-                gs.draw_tower()?;
-                gs.draw_funds(10000, 0)?;
-                gs.enter_menu(gs.command_pool_main_id)
-            }))
-            .on_letter_press('a', "Apartments- $12000", Command::new(|gs| {
-                gs.add_layer(LayerType::Apartment);
-                gs.draw_tower()?;
-                gs.draw_funds(12000, 0)?;
-                gs.enter_menu(gs.command_pool_main_id)
-            }))
-            .on_letter_press('r', "Retail- $8000", Command::new(|gs| {
-                gs.add_layer(LayerType::Retail);
-                gs.draw_tower()?;
-                gs.draw_funds(8000, 0)?;
-                gs.enter_menu(gs.command_pool_main_id)
-            }))
-            .on_letter_press('x', "Cancel", Command::new(|gs| {
-                gs.enter_menu(gs.command_pool_main_id)
-            }))
-            .build()
+        build_pool_builder
+            .on_key_press(
+                "renovate",
+                &[KeyBinding::char('n')],
+                format!("Renovate {RENOVATE_STARS}★"),
+                Command::new(|gs| {
+                    gs.renovate_selected()?;
+                    gs.enter_menu(gs.command_pool_main_id)
+                })
+                .with_help(format!(
+                    "Clean and repair the selected floor for a fifth of what it cost to \
+                     build. Needs a {RENOVATE_STARS}-star tower."
+                )),
+            )
+            .on_key_press(
+                "cancel",
+                &[KeyBinding::char('x')],
+                "Cancel",
+                Command::new(|gs| gs.enter_menu(gs.command_pool_main_id))
+                    .with_help("Leave the build menu without buying anything."),
+            )
+            .build(),
     );
 
     let command_pool_game_over_id = pool_array_builder.add_pool(
-        CommandPoolBuilder::new()
-            .on_letter_press('r', "Retry", Command::new(|gs| {
-                gs.restarting = true;
-                Ok(())
-            }))
-            .on_letter_press('x', "Exit", Command::new(|gs| {
-                gs.running = false;
-                Ok(())
-            }))
-            .build()
+        CommandPoolBuilder::new("game_over")
+            .on_key_press(
+                "retry",
+                &[KeyBinding::char('r')],
+                "Retry",
+                Command::new(|gs| {
+                    gs.restarting = true;
+                    Ok(())
+                })
+                .with_help("Start a new game from scratch with the same rules."),
+            )
+            .on_key_press(
+                "new_game",
+                &[KeyBinding::char('n')],
+                "New game",
+                Command::new(|gs| gs.show_new_game())
+                    .with_help("Choose a difficulty or custom rules for a new game."),
+            )
+            .on_key_press(
+                "high_scores",
+                &[KeyBinding::char('h')],
+                "High scores",
+                Command::new(|gs| gs.show_high_scores())
+                    .with_help("See how this run compares to your others."),
+            )
+            .on_key_press(
+                "exit",
+                &[KeyBinding::char('x')],
+                "Exit",
+                Command::new(|gs| {
+                    gs.running = false;
+                    Ok(())
+                })
+                .with_help("Quit the game."),
+            )
+            .build(),
+    );
+
+    let command_pool_finances_id = pool_array_builder.add_pool(
+        CommandPoolBuilder::new("finances")
+            .on_key_press(
+                "export",
+                &[KeyBinding::char('e')],
+                "Export CSV",
+                Command::new(|gs| gs.export_ledger())
+                    .with_help("Write every transaction so far to ledger.csv in the data folder."),
+            )
+            .on_key_press(
+                "back",
+                &[KeyBinding::char('x')],
+                "Back",
+                Command::new(|gs| gs.pop_screen()).with_help("Go back to the tower."),
+            )
+            .build(),
+    );
+
+    let command_pool_messages_id = pool_array_builder.add_pool(
+        CommandPoolBuilder::new("messages")
+            .on_key_press(
+                "back",
+                &[KeyBinding::char('x')],
+                "Back",
+                Command::new(|gs| gs.pop_screen()).with_help("Go back to the tower."),
+            )
+            .build(),
     );
 
+    let command_pool_staff_id = pool_array_builder.add_pool(
+        CommandPoolBuilder::new("staff")
+            .on_key_press(
+                "hire_janitor",
+                &[KeyBinding::char('j')],
+                "Hire janitor",
+                Command::new(|gs| gs.change_staff(StaffRole::Janitor, 1)).with_help(format!(
+                    "Hire a janitor for ${} a tick to keep {} floors clean. Dirty floors lose \
+                     occupants.",
+                    StaffRole::Janitor.wage(),
+                    StaffRole::Janitor.floors_covered()
+                )),
+            )
+            .on_key_press(
+                "fire_janitor",
+                &[KeyBinding::char('J')],
+                "Fire janitor",
+                Command::new(|gs| gs.change_staff(StaffRole::Janitor, -1))
+                    .with_help("Let a janitor go."),
+            )
+            .on_key_press(
+                "hire_guard",
+                &[KeyBinding::char('g')],
+                "Hire guard",
+                Command::new(|gs| gs.change_staff(StaffRole::Security, 1)).with_help(format!(
+                    "Hire a security guard for ${} a tick to watch {} floors. Break-ins drive \
+                     occupants away.",
+                    StaffRole::Security.wage(),
+                    StaffRole::Security.floors_covered()
+                )),
+            )
+            .on_key_press(
+                "fire_guard",
+                &[KeyBinding::char('G')],
+                "Fire guard",
+                Command::new(|gs| gs.change_staff(StaffRole::Security, -1))
+                    .with_help("Let a security guard go."),
+            )
+            .on_key_press(
+                "hire_maintenance",
+                &[KeyBinding::char('m')],
+                "Hire maintenance",
+                Command::new(|gs| gs.change_staff(StaffRole::Maintenance, 1)).with_help(format!(
+                    "Hire a maintenance worker for ${} a tick to look after {} floors. Broken \
+                     down floors are half as popular.",
+                    StaffRole::Maintenance.wage(),
+                    StaffRole::Maintenance.floors_covered()
+                )),
+            )
+            .on_key_press(
+                "fire_maintenance",
+                &[KeyBinding::char('M')],
+                "Fire maintenance",
+                Command::new(|gs| gs.change_staff(StaffRole::Maintenance, -1))
+                    .with_help("Let a maintenance worker go."),
+            )
+            .on_key_press(
+                "back",
+                &[KeyBinding::char('x')],
+                "Back",
+                Command::new(|gs| gs.pop_screen()).with_help("Go back to the tower."),
+            )
+            .build(),
+    );
+
+    let command_pool_achievements_id = pool_array_builder.add_pool(
+        CommandPoolBuilder::new("achievements")
+            .on_key_press(
+                "back",
+                &[KeyBinding::char('x')],
+                "Back",
+                Command::new(|gs| gs.pop_screen()).with_help("Go back to the tower."),
+            )
+            .build(),
+    );
+
+    let command_pool_high_scores_id = pool_array_builder.add_pool(
+        CommandPoolBuilder::new("high_scores")
+            .on_key_press(
+                "previous",
+                &[KeyBinding::char('w')],
+                "Previous run",
+                Command::new(|gs| gs.select_high_score(gs.high_score_selected.saturating_sub(1)))
+                    .with_help("Select the next better run."),
+            )
+            .on_key_press(
+                "next",
+                &[KeyBinding::char('s')],
+                "Next run",
+                Command::new(|gs| gs.select_high_score(gs.high_score_selected + 1))
+                    .with_help("Select the next worse run."),
+            )
+            .on_key_press(
+                "back",
+                &[KeyBinding::char('x')],
+                "Back",
+                Command::new(|gs| gs.pop_screen()).with_help("Go back to where you came from."),
+            )
+            .build(),
+    );
+
+    let command_pool_help_id = pool_array_builder.add_pool(
+        CommandPoolBuilder::new("help")
+            .without_help()
+            .on_key_press(
+                "back",
+                &[KeyBinding::char('x'), KeyBinding::char('?'), KeyBinding::key(KeyCode::Esc)],
+                "Back",
+                Command::new(|gs| gs.pop_screen()).with_help("Close the help screen."),
+            )
+            .on_key_press(
+                "next_page",
+                &[KeyBinding::key(KeyCode::Tab), KeyBinding::key(KeyCode::PageDown)],
+                "Next page",
+                Command::new(|gs| gs.next_help_page())
+                    .with_help("Show the next page of controls and rules."),
+            )
+            .build(),
+    );
+
+    let mut new_game_pool_builder = CommandPoolBuilder::new("new_game");
+    for ((key, action), rule_set) in ('1'..).zip(RULE_SET_ACTIONS).zip(&rule_sets) {
+        let rule_set = rule_set.clone();
+        new_game_pool_builder = new_game_pool_builder.on_key_press(
+            action,
+            &[KeyBinding::char(key)],
+            rule_set.name.clone(),
+            Command::new(move |gs| gs.start_new_game(rule_set.clone()))
+                .with_help("Start a new game with these rules."),
+        );
+    }
+    let command_pool_new_game_id = pool_array_builder.add_pool(
+        new_game_pool_builder
+            .on_key_press(
+                "back",
+                &[KeyBinding::char('x')],
+                "Back",
+                Command::new(|gs| gs.pop_screen())
+                    .with_help("Carry on with the game you were playing."),
+            )
+            .build(),
+    );
+
+    let settings = Settings::load(&data_dir);
+    let errors: Vec<String> = pool_array_builder
+        .errors()
+        .iter()
+        .map(|error| format!("Key bindings: {error}"))
+        .chain(settings.errors().iter().map(|error| format!("Settings: {error}")))
+        .chain(mods.errors().iter().map(|error| format!("Mods: {error}")))
+        .chain(rule_errors.iter().map(|error| format!("Rules: {error}")))
+        .collect();
+    let toast = errors.first().map(|error| {
+        let mut msg = error.clone();
+        if errors.len() > 1 {
+            msg += &format!(" (and {} more)", errors.len() - 1);
+        }
+        (msg, TOAST_TICKS)
+    });
+
+    let seed = thread_rng().gen();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let calendar = Calendar::new(&mut rng);
+
     GameState {
         running: true,
         restarting: false,
-        stdout,
+        frame,
         command_pool_array: pool_array_builder.with_initial_pool(command_pool_main_id),
         command_pool_main_id,
         command_pool_build_id,
+        command_pool_goto_id,
+        command_pool_console_id,
         command_pool_game_over_id,
+        command_pool_finances_id,
+        command_pool_achievements_id,
+        command_pool_high_scores_id,
+        command_pool_help_id,
+        command_pool_staff_id,
+        command_pool_messages_id,
+        command_pool_new_game_id,
+        screen: Screen::Tower,
+        screen_stack: vec![],
         layers: vec![],
         scroll_pos: 0,
-        funds: 10_000,
-        debt_collection_timer: 50,
+        tower_view: TowerView::default(),
+        scenery: Scenery::new(),
+        animation_fps: settings.animation_fps,
+        selected_floor: None,
+        goto_input: String::new(),
+        console: Console::default(),
+        message: String::new(),
+        message_log: MessageLog::default(),
+        funds: rules.starting_funds,
+        debt_collection_timer: rules.inspection_interval,
+        ledger: Ledger::new(),
+        tick: 0,
+        speed: 1,
+        period: 0,
+        peak_funds: rules.starting_funds,
+        loan: 0,
+        floors_built: 0,
+        game_over: false,
+        undo_history: UndoHistory::new(settings.undo_depth, settings.undo_grace_ticks),
+        calendar,
+        tenant_pool: None,
+        staff: Staff::default(),
+        taxes: Taxes::default(),
+        stars: 1,
+        seed,
+        rng,
+        rules,
+        rule_sets,
+        next_rules: None,
+        mods,
+        broken_mods: HashSet::new(),
+        achievements: Achievements::load(&data_dir),
+        data_dir,
+        toast,
+        run_history: vec![],
+        high_score_selected: 0,
+        help_page: 0,
     }
 }
@@ -0,0 +1,137 @@
+use crate::command::CommandPool;
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, queue, terminal};
+use std::io::{self, Write};
+
+const TOP_ROW: u16 = 3;
+const KEYS_WIDTH: usize = 14;
+const RULES_COL: u16 = 80;
+const RULES_WIDTH: usize = (WINDOW_WIDTH - RULES_COL) as usize;
+const HELP_WIDTH: usize = RULES_COL as usize - 2 - KEYS_WIDTH;
+/// Rows for controls and rules under their headings.
+const PAGE_ROWS: usize = (TOWER_WINDOW_HEIGHT - 2 - TOP_ROW) as usize;
+
+/// Splits `text` into lines at most `width` columns wide, breaking at spaces.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line += word;
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Splits `items`, each a run of lines that belong together, into pages
+/// of at most `PAGE_ROWS` lines. Items longer than a page are split.
+fn paginate<T>(items: Vec<Vec<T>>) -> Vec<Vec<T>> {
+    let mut pages = vec![];
+    let mut page = vec![];
+    for item in items {
+        if !page.is_empty() && page.len() + item.len() > PAGE_ROWS {
+            pages.push(std::mem::take(&mut page));
+        }
+        for line in item {
+            if page.len() == PAGE_ROWS {
+                pages.push(std::mem::take(&mut page));
+            }
+            page.push(line);
+        }
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+/// Pages of keys and the help for them, with the keys only on the first
+/// line of each control.
+fn control_pages(pool: &CommandPool) -> Vec<Vec<(String, String)>> {
+    let controls = pool.bindings().map(|(keys, description, command)| {
+        let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+        let help = if command.help().is_empty() {
+            description
+        } else {
+            command.help()
+        };
+        let mut keys = Some(keys.join("/"));
+        wrap(help, HELP_WIDTH)
+            .into_iter()
+            .map(|line| (keys.take().unwrap_or_default(), line))
+            .collect()
+    });
+    paginate(controls.collect())
+}
+
+fn rule_pages(rules: &[&str]) -> Vec<Vec<String>> {
+    let rules = rules.iter().map(|rule| {
+        wrap(rule, RULES_WIDTH - 2)
+            .into_iter()
+            .enumerate()
+            .map(|(i, line)| if i == 0 { format!("- {line}") } else { format!("  {line}") })
+            .collect()
+    });
+    paginate(rules.collect())
+}
+
+/// How many pages the help screen for `pool` takes.
+pub fn help_page_count(pool: &CommandPool, rules: &[&str]) -> usize {
+    control_pages(pool).len().max(rule_pages(rules).len()).max(1)
+}
+
+/// Draws page `page` of the help screen for `pool`: every control bound in
+/// it, followed by `rules`, the rules of the game.
+pub fn help_draw(
+    mut writer: impl Write,
+    pool: &CommandPool,
+    rules: &[&str],
+    page: usize,
+) -> io::Result<()> {
+    let white = Colors {
+        foreground: Some(Color::White),
+        background: Some(Color::Black),
+    };
+    let page_count = help_page_count(pool, rules);
+    let heading = if page_count > 1 {
+        format!("CONTROLS (page {} of {page_count})", page + 1)
+    } else {
+        "CONTROLS".to_string()
+    };
+    queue!(
+        writer,
+        cursor::MoveTo(WINDOW_WIDTH, TOWER_WINDOW_HEIGHT),
+        terminal::Clear(terminal::ClearType::FromCursorUp),
+        style::SetColors(white),
+        cursor::MoveTo(0, TOP_ROW),
+        style::Print(heading),
+    )?;
+
+    let controls = control_pages(pool).into_iter().nth(page).unwrap_or_default();
+    for (row, (keys, help)) in (TOP_ROW + 1..).zip(controls) {
+        queue!(
+            writer,
+            cursor::MoveTo(0, row),
+            style::SetForegroundColor(Color::Green),
+            style::Print(format!("{keys:<KEYS_WIDTH$}")),
+            style::SetForegroundColor(Color::White),
+            style::Print(help),
+        )?;
+    }
+
+    queue!(writer, cursor::MoveTo(RULES_COL, TOP_ROW), style::Print("RULES"))?;
+    let lines = rule_pages(rules).into_iter().nth(page).unwrap_or_default();
+    for (row, line) in (TOP_ROW + 1..).zip(lines) {
+        queue!(writer, cursor::MoveTo(RULES_COL, row), style::Print(line))?;
+    }
+
+    Ok(())
+}
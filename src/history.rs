@@ -0,0 +1,256 @@
+use crate::storage::DataDir;
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, queue, terminal};
+use std::fs;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HISTORY_FILE: &str = "history.csv";
+const HISTORY_HEADER: &str = "date,seed,peak_funds,floors_built,inspections_survived,ticks_lasted";
+
+/// Summary of a finished game, as stored in the run history file.
+#[derive(Clone, Debug)]
+pub struct RunRecord {
+    /// Date the run ended on, as `YYYY-MM-DD`.
+    pub date: String,
+    pub seed: u64,
+    pub peak_funds: i32,
+    pub floors_built: usize,
+    pub inspections_survived: u32,
+    pub ticks_lasted: u64,
+}
+
+impl RunRecord {
+    pub fn score(&self) -> u64 {
+        (self.peak_funds.max(0) as u64 / 100)
+            .saturating_add((self.floors_built as u64).saturating_mul(500))
+            .saturating_add(u64::from(self.inspections_survived) * 1000)
+            .saturating_add(self.ticks_lasted)
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{}",
+            self.date,
+            self.seed,
+            self.peak_funds,
+            self.floors_built,
+            self.inspections_survived,
+            self.ticks_lasted
+        )
+    }
+
+    fn from_csv(line: &str) -> Option<Self> {
+        let mut fields = line.split(',');
+        let record = RunRecord {
+            date: fields.next()?.to_string(),
+            seed: fields.next()?.parse().ok()?,
+            peak_funds: fields.next()?.parse().ok()?,
+            floors_built: fields.next()?.parse().ok()?,
+            inspections_survived: fields.next()?.parse().ok()?,
+            ticks_lasted: fields.next()?.parse().ok()?,
+        };
+        Some(record)
+    }
+}
+
+/// Loads every recorded run, best score first. Lines that can't be parsed
+/// are skipped.
+pub fn load_history(data_dir: &DataDir) -> Vec<RunRecord> {
+    let contents = data_dir
+        .file(HISTORY_FILE)
+        .and_then(fs::read_to_string)
+        .unwrap_or_default();
+    let mut runs: Vec<RunRecord> = contents
+        .lines()
+        .filter(|line| *line != HISTORY_HEADER)
+        .filter_map(RunRecord::from_csv)
+        .collect();
+    runs.sort_by_key(|run| std::cmp::Reverse(run.score()));
+    runs
+}
+
+pub fn append_history(data_dir: &DataDir, run: &RunRecord) -> io::Result<()> {
+    let path = data_dir.file(HISTORY_FILE)?;
+    let is_new = !path.exists();
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if is_new {
+        writeln!(file, "{HISTORY_HEADER}")?;
+    }
+    writeln!(file, "{}", run.to_csv())
+}
+
+/// Today's date (UTC) as `YYYY-MM-DD`.
+pub fn today() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    // Convert days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+pub fn high_scores_draw(
+    mut writer: impl Write,
+    runs: &[RunRecord],
+    selected: usize,
+) -> io::Result<()> {
+    const TOP_ROW: u16 = 3;
+    const VISIBLE_RUNS: usize = (TOWER_WINDOW_HEIGHT - TOP_ROW - 3) as usize;
+    const DETAILS_COL: u16 = 60;
+
+    queue!(
+        writer,
+        cursor::MoveTo(WINDOW_WIDTH, TOWER_WINDOW_HEIGHT),
+        terminal::Clear(terminal::ClearType::FromCursorUp),
+        cursor::MoveTo(0, TOP_ROW),
+        style::SetColors(Colors {
+            foreground: Some(Color::White),
+            background: Some(Color::Black),
+        }),
+        style::Print(format!("{:>4}  {:>8}  {:<10}", "RANK", "SCORE", "DATE")),
+    )?;
+
+    if runs.is_empty() {
+        queue!(
+            writer,
+            cursor::MoveTo(0, TOP_ROW + 2),
+            style::Print("No runs recorded yet."),
+        )?;
+        return Ok(());
+    }
+
+    // Keep the selected run in view
+    let first = selected.saturating_sub(VISIBLE_RUNS - 1);
+    let visible = runs.iter().enumerate().skip(first).take(VISIBLE_RUNS);
+    for (row, (rank, run)) in (TOP_ROW + 2..).zip(visible) {
+        let color = if rank == selected { Color::Green } else { Color::White };
+        queue!(
+            writer,
+            cursor::MoveTo(0, row),
+            style::SetColors(Colors {
+                foreground: Some(color),
+                background: Some(Color::Black),
+            }),
+            style::Print(format!("{:>4}  {:>8}  {:<10}", rank + 1, run.score(), run.date)),
+        )?;
+    }
+
+    let run = &runs[selected];
+    let details = [
+        format!("RUN #{}", selected + 1),
+        format!("{:<22}{}", "Date", run.date),
+        format!("{:<22}{}", "Seed", run.seed),
+        format!("{:<22}{}", "Score", run.score()),
+        format!("{:<22}{}", "Peak funds", run.peak_funds),
+        format!("{:<22}{}", "Floors built", run.floors_built),
+        format!("{:<22}{}", "Inspections survived", run.inspections_survived),
+        format!("{:<22}{}", "Ticks lasted", run.ticks_lasted),
+    ];
+    queue!(
+        writer,
+        style::SetColors(Colors {
+            foreground: Some(Color::White),
+            background: Some(Color::Black),
+        })
+    )?;
+    for (row, line) in (TOP_ROW..).zip(details.iter()) {
+        queue!(writer, cursor::MoveTo(DETAILS_COL, row), style::Print(line))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameBuffer;
+    use crate::game_state::init_game_state;
+    use crate::rules::Rules;
+    use crate::test_game::TestGame;
+    use std::rc::Rc;
+
+    #[test]
+    fn runs_that_end_any_way_are_kept_across_restarts() {
+        let mut game = TestGame::new();
+        let sandbox = Rules::presets().into_iter().find(|rules| rules.name == "Sandbox").unwrap();
+        game.gs.rules = sandbox;
+        game.gs.end_run().unwrap();
+        assert!(load_history(&game.gs.data_dir).is_empty(), "a game never played was recorded");
+
+        game.tick().tick();
+        game.gs.end_run().unwrap();
+        // A game over records the run itself
+        game.gs.funds = -1;
+        game.gs.rules.debt_game_over = true;
+        game.gs.debt_collection_timer = 1;
+        game.tick();
+        assert!(game.gs.game_over);
+        game.gs.end_run().unwrap();
+
+        let mods = Rc::clone(&game.gs.mods);
+        let data_dir = game.gs.data_dir.clone();
+        let mut gs = init_game_state(FrameBuffer::new(), mods, Rules::default(), data_dir);
+        gs.show_high_scores().unwrap();
+        let ticks: Vec<u64> = gs.run_history.iter().map(|run| run.ticks_lasted).collect();
+        assert_eq!(ticks, [3, 2]);
+    }
+
+    #[test]
+    fn best_runs_come_first_and_show_their_details() {
+        let game = TestGame::new();
+        let run = |seed, floors_built, ticks_lasted| RunRecord {
+            date: "2024-01-01".to_string(),
+            seed,
+            peak_funds: 0,
+            floors_built,
+            inspections_survived: 0,
+            ticks_lasted,
+        };
+        for run in [run(1, 1, 10), run(2, 3, 0), run(3, 1, 600)] {
+            append_history(&game.gs.data_dir, &run).unwrap();
+        }
+        let path = game.data_dir.0.join(HISTORY_FILE);
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(&path, format!("{contents}not,a,run\n")).unwrap();
+
+        let runs = load_history(&game.gs.data_dir);
+        let seeds: Vec<u64> = runs.iter().map(|run| run.seed).collect();
+        assert_eq!(seeds, [2, 3, 1]);
+        let scores: Vec<u64> = runs.iter().map(RunRecord::score).collect();
+        assert_eq!(scores, [1500, 1100, 510]);
+
+        let mut frame = FrameBuffer::new();
+        high_scores_draw(&mut frame, &runs, 1).unwrap();
+        assert!(frame.find("RUN #2").is_some());
+        let (_, y) = frame.find("Ticks lasted").unwrap();
+        assert!(frame.row_text(y).trim_end().ends_with(" 600"), "{}", frame.row_text(y));
+    }
+
+    #[test]
+    fn scores_of_the_longest_runs_saturate() {
+        let run = RunRecord {
+            date: "2024-01-01".to_string(),
+            seed: 0,
+            peak_funds: i32::MAX,
+            floors_built: usize::MAX,
+            inspections_survived: u32::MAX,
+            ticks_lasted: u64::MAX,
+        };
+        assert_eq!(run.score(), u64::MAX);
+    }
+}
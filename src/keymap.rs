@@ -0,0 +1,295 @@
+use crate::storage::DataDir;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+
+const KEYMAP_FILE: &str = "keys.conf";
+
+/// A key together with the modifiers that have to be held for it.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        // Terminals report shifted letters as the uppercase letter, sometimes
+        // with SHIFT set and sometimes without, so fold SHIFT into the letter.
+        // Control letters mostly come through lowercase whatever the shift
+        // state, so Ctrl+R, Ctrl+r and Ctrl+Shift+r are all the same key.
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::CONTROL) => {
+                KeyCode::Char(c.to_ascii_lowercase())
+            }
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            code => code,
+        };
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        KeyBinding { code, modifiers }
+    }
+
+    pub fn key(code: KeyCode) -> Self {
+        KeyBinding::new(code, KeyModifiers::empty())
+    }
+
+    pub fn char(c: char) -> Self {
+        KeyBinding::key(KeyCode::Char(c))
+    }
+
+    /// The key press this binding is triggered by.
+    pub fn key_event(&self) -> KeyEvent {
+        KeyEvent::new(self.code, self.modifiers)
+    }
+
+    /// Parses bindings like `w`, `W`, `PageUp`, `F5` or `Ctrl+Alt+x`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut modifiers = KeyModifiers::empty();
+        let mut rest = s.trim();
+        // A lone "+" is a key, not a separator
+        while let Some((prefix, key)) = rest.split_once('+').filter(|(_, key)| !key.is_empty()) {
+            modifiers |= match prefix.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" | "bksp" => KeyCode::Backspace,
+                "esc" | "escape" => KeyCode::Esc,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "insert" | "ins" => KeyCode::Insert,
+                "delete" | "del" => KeyCode::Delete,
+                name => {
+                    let n: u8 = name.strip_prefix('f')?.parse().ok()?;
+                    if !(1..=24).contains(&n) {
+                        return None;
+                    }
+                    KeyCode::F(n)
+                }
+            },
+        };
+        Some(KeyBinding::new(code, modifiers))
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            write!(f, "Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::BackTab => write!(f, "BackTab"),
+            KeyCode::Backspace => write!(f, "Bksp"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Up => write!(f, "Up"),
+            KeyCode::Down => write!(f, "Down"),
+            KeyCode::Left => write!(f, "Left"),
+            KeyCode::Right => write!(f, "Right"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::Insert => write!(f, "Ins"),
+            KeyCode::Delete => write!(f, "Del"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// User overrides for the keys bound to each action, read from `keys.conf`
+/// in the user data directory. Each line looks like
+///
+/// ```text
+/// main.scroll_up = Up, w
+/// ```
+///
+/// where `main` is the command pool and `scroll_up` the action in it.
+pub struct Keymap {
+    bindings: HashMap<String, Vec<KeyBinding>>,
+    errors: Vec<String>,
+}
+
+impl Keymap {
+    pub fn empty() -> Self {
+        Keymap {
+            bindings: HashMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Loads the keymap in `data_dir`. A missing file is the same as an
+    /// empty one.
+    pub fn load(data_dir: &DataDir) -> Self {
+        match data_dir.file(KEYMAP_FILE).and_then(fs::read_to_string) {
+            Ok(contents) => Keymap::parse(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Keymap::empty(),
+            Err(e) => {
+                let mut keymap = Keymap::empty();
+                keymap.errors.push(format!("{KEYMAP_FILE}: {e}"));
+                keymap
+            }
+        }
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut keymap = Keymap::empty();
+        for (line_number, line) in (1..).zip(contents.lines()) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((action, keys)) = line.split_once('=') else {
+                keymap
+                    .errors
+                    .push(format!("{KEYMAP_FILE}:{line_number}: expected `pool.action = key`"));
+                continue;
+            };
+            let mut bindings = Vec::new();
+            for key in keys.split(',') {
+                match KeyBinding::parse(key) {
+                    Some(binding) => bindings.push(binding),
+                    None => keymap.errors.push(format!(
+                        "{KEYMAP_FILE}:{line_number}: unknown key `{}`",
+                        key.trim()
+                    )),
+                }
+            }
+            if bindings.is_empty() {
+                keymap.errors.push(format!(
+                    "{KEYMAP_FILE}:{line_number}: no keys for `{}`, keeping its default",
+                    action.trim()
+                ));
+                continue;
+            }
+            keymap.bindings.insert(action.trim().to_string(), bindings);
+        }
+        keymap
+    }
+
+    /// The keys the user bound to `action` in `pool`, if they overrode it.
+    pub fn bindings(&self, pool: &str, action: &str) -> Option<&[KeyBinding]> {
+        self.bindings
+            .get(&format!("{pool}.{action}"))
+            .map(|keys| keys.as_slice())
+    }
+
+    /// Actions the keymap binds, as `pool.action`.
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(|action| action.as_str())
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{Command, CommandPoolArrayBuilder, CommandPoolBuilder};
+
+    #[test]
+    fn bindings_match_the_keys_terminals_report() {
+        let ctrl = KeyModifiers::CONTROL;
+        let shift = KeyModifiers::SHIFT;
+        let ctrl_r = KeyBinding::new(KeyCode::Char('r'), ctrl);
+        assert_eq!(KeyBinding::parse("Ctrl+R"), Some(ctrl_r));
+        assert_eq!(KeyBinding::parse("ctrl+shift+r"), Some(ctrl_r));
+        assert_eq!(KeyBinding::new(KeyCode::Char('R'), ctrl | shift), ctrl_r);
+        assert_eq!(KeyBinding::parse("Shift+w"), Some(KeyBinding::char('W')));
+        assert_eq!(KeyBinding::new(KeyCode::Char('w'), shift), KeyBinding::char('W'));
+        assert_ne!(KeyBinding::parse("Alt+W"), KeyBinding::parse("Alt+w"));
+        assert_eq!(
+            KeyBinding::parse("Alt+Shift+w"),
+            Some(KeyBinding::new(KeyCode::Char('W'), KeyModifiers::ALT))
+        );
+    }
+
+    #[test]
+    fn bindings_read_back_as_written() {
+        let keys = [
+            "w", "W", "+", "Space", "Enter", "BackTab", "Bksp", "PgUp", "PgDn", "Del", "F5",
+            "Ctrl+r", "Alt+W", "Ctrl+Alt+x", "Shift+Up",
+        ];
+        for key in keys {
+            let binding = KeyBinding::parse(key).unwrap();
+            assert_eq!(binding.to_string(), key);
+            assert_eq!(KeyBinding::parse(&binding.to_string()), Some(binding));
+        }
+    }
+
+    #[test]
+    fn keys_bound_to_two_actions_stay_with_the_first() {
+        let pool = ["build", "quit"]
+            .into_iter()
+            .fold(CommandPoolBuilder::new("main").without_help(), |pool, action| {
+                let command = Command::new(|_| Ok(())).with_help(action);
+                let key = KeyBinding::char(action.chars().next().unwrap());
+                pool.on_key_press(action, &[key], action, command)
+            })
+            .build();
+        let mut pools = CommandPoolArrayBuilder::new(Keymap::parse("main.quit = Ctrl+Q, b\n"));
+        let id = pools.add_pool(pool);
+        assert_eq!(pools.errors(), ["b is bound to both main.build and main.quit"]);
+        let pools = pools.with_initial_pool(id);
+        let help = |code, modifiers| {
+            let command = pools.cur().get_command(code, modifiers);
+            command.map(|command| command.help().to_string())
+        };
+        assert_eq!(help(KeyCode::Char('b'), KeyModifiers::empty()).as_deref(), Some("build"));
+        assert_eq!(help(KeyCode::Char('q'), KeyModifiers::CONTROL).as_deref(), Some("quit"));
+        assert_eq!(help(KeyCode::Char('q'), KeyModifiers::empty()), None);
+    }
+
+    #[test]
+    fn keeps_the_default_when_no_key_parses() {
+        let keymap = Keymap::parse("main.build = Nope, Ctrl+\nmain.quit = x, Nope\n");
+        assert_eq!(keymap.bindings("main", "build"), None);
+        assert_eq!(keymap.bindings("main", "quit").map(<[_]>::len), Some(1));
+        assert_eq!(
+            keymap.errors(),
+            [
+                "keys.conf:1: unknown key `Nope`",
+                "keys.conf:1: unknown key `Ctrl+`",
+                "keys.conf:1: no keys for `main.build`, keeping its default",
+                "keys.conf:2: unknown key `Nope`",
+            ]
+        );
+    }
+}
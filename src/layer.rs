@@ -1,86 +1,384 @@
-use crate::{CONTROLS_HEIGHT, TOWER_WINDOW_HEIGHT, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crate::rating;
+use crate::scenery::Scenery;
+use crate::weather::Weather;
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_WIDTH};
 use crossterm::style::{self, Color, Colors};
 use crossterm::{cursor, queue, terminal};
-use std::fs;
+use std::cell::Cell;
 use std::io::{self, Write};
+use std::rc::Rc;
+
+/// Rows each floor takes up in the tower view, including its ceiling.
+pub const FLOOR_HEIGHT: u16 = 5;
+/// Most rent a floor can charge each occupant, small enough that a full
+/// floor's revenue still fits the funds.
+pub const MAX_RENT: usize = 10_000;
+/// Rows of sky drawn above the top floor.
+const ROOF_HEIGHT: u16 = 8;
+
+/// Column the minimap starts at, between the tower and the funds.
+const MINIMAP_X: u16 = 28;
+/// Columns of the minimap. Floors fill it from the bottom of the first
+/// column up, then the next column.
+const MINIMAP_COLUMNS: u16 = 5;
+/// How full a floor is in the minimap, from empty to full.
+const MINIMAP_OCCUPANCY: [char; 5] = ['·', '░', '▒', '▓', '█'];
+
+/// How the tower is drawn.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum TowerView {
+    /// Each floor shows its rent, revenue and occupancy on separate lines.
+    #[default]
+    Full,
+    /// Each floor is a single line, to see more of a tall tower at once.
+    Compact,
+}
+
+impl TowerView {
+    /// Rows each floor takes up in the tower view.
+    pub fn floor_height(self) -> u16 {
+        match self {
+            TowerView::Full => FLOOR_HEIGHT,
+            TowerView::Compact => 1,
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            TowerView::Full => TowerView::Compact,
+            TowerView::Compact => TowerView::Full,
+        }
+    }
+}
+
+/// Scroll position at which the top of the tower is at the top of the view.
+pub fn max_scroll(num_layers: usize, view: TowerView) -> u16 {
+    let floors_height = usize::from(view.floor_height()).saturating_mul(num_layers);
+    let tower_height = floors_height.saturating_add(1 + usize::from(ROOF_HEIGHT));
+    saturate(tower_height.saturating_sub(TOWER_WINDOW_HEIGHT.into()))
+}
+
+/// `rows` as a row or scroll position, or the last one if it is too far up.
+fn saturate(rows: usize) -> u16 {
+    u16::try_from(rows).unwrap_or(u16::MAX)
+}
+
+const GROUND_ART: &str = include_str!("graphics/ground.txt");
+const ROOF_ART: &str = include_str!("graphics/roof.txt");
+
+/// Column the funds, weather and messages beside the tower start at.
+pub const INFO_MSG_WIDTH: u16 = 35;
+
+/// Columns inside the walls of the tower.
+const TOWER_WIDTH: usize = 24;
+
+/// Row, counting up from the ground, that the top line of `floor` is on.
+pub fn floor_row(floor: usize, view: TowerView) -> u16 {
+    saturate(match view {
+        TowerView::Full => usize::from(FLOOR_HEIGHT).saturating_mul(floor.saturating_add(1)),
+        TowerView::Compact => floor.saturating_add(2),
+    })
+}
+
+/// The floor drawn at column `x` and row `y` of the tower view, if any.
+pub fn floor_at(
+    num_layers: usize,
+    scroll_pos: u16,
+    view: TowerView,
+    x: u16,
+    y: u16,
+) -> Option<usize> {
+    if x as usize >= TOWER_WIDTH + 2 || y >= TOWER_WINDOW_HEIGHT {
+        return None;
+    }
+    // A floor spans from the line under its name up to and including the
+    // ceiling above it
+    let row = usize::from(TOWER_WINDOW_HEIGHT) + usize::from(scroll_pos) - usize::from(y);
+    let floor = row.checked_sub(2)? / usize::from(view.floor_height());
+    (floor < num_layers).then_some(floor)
+}
+
+/// Floors shown by each character of the minimap, so the whole tower fits.
+fn floors_per_minimap_cell(num_layers: usize) -> usize {
+    let cells = (MINIMAP_COLUMNS * TOWER_WINDOW_HEIGHT) as usize;
+    num_layers.div_ceil(cells).max(1)
+}
+
+/// The floor shown at column `x` and row `y` of the minimap, if any.
+pub fn minimap_floor_at(num_layers: usize, x: u16, y: u16) -> Option<usize> {
+    if !(MINIMAP_X..MINIMAP_X + MINIMAP_COLUMNS).contains(&x) || y >= TOWER_WINDOW_HEIGHT {
+        return None;
+    }
+    let cell = (x - MINIMAP_X) * TOWER_WINDOW_HEIGHT + (TOWER_WINDOW_HEIGHT - 1 - y);
+    let floor = cell as usize * floors_per_minimap_cell(num_layers);
+    (floor < num_layers).then_some(floor)
+}
 
 pub struct Layer {
     pub style: LayerType,
+    floor_types: FloorTypes,
     pub revenue_per_occupant: usize,
     pub occupancy: usize,
     pub max_occupancy: usize,
+    /// From 0 for filthy to 1 for spotless, kept up by janitors.
+    pub cleanliness: f64,
+    /// Broken lifts, leaks and the like, until maintenance fixes them.
+    pub broken_down: bool,
     pub text: String,
     //pub start_row: Option<usize>
 }
 
 impl Layer {
-    pub fn new(style: LayerType, revenue_per_occupant: usize, max_occupancy: usize) -> Self {
+    pub fn new(
+        floor_types: &FloorTypes,
+        style: LayerType,
+        revenue_per_occupant: usize,
+        max_occupancy: usize,
+    ) -> Self {
         Self {
             style,
+            floor_types: floor_types.clone(),
             revenue_per_occupant,
             occupancy: 0,
             max_occupancy,
+            cleanliness: 1.0,
+            broken_down: false,
             text: "".to_string(),
         }
     }
 
+    /// What this type of floor is like.
+    pub fn floor_type(&self) -> &FloorType {
+        self.floor_types.get(self.style)
+    }
+
     pub fn revenue(&self) -> usize {
         self.occupancy * self.revenue_per_occupant
     }
 
-    pub fn set_string(&mut self) {
-        const TOWER_WIDTH: usize = 24;
-        let name = match self.style {
-            LayerType::Apartment => "APARTMENT COMPLEX",
-            LayerType::Retail => "RETAIL STORE",
-            LayerType::Food => "FOOD COURT",
-        };
+    /// How pleasant the floor is to be on, from its cleanliness and whether
+    /// it has broken down. 1 is as good as it gets.
+    pub fn appeal(&self) -> f64 {
+        let appeal = 0.5 + 0.5 * self.cleanliness;
+        if self.broken_down {
+            appeal * 0.5
+        } else {
+            appeal
+        }
+    }
+
+    /// How the rent compares to the usual rent for this type of floor.
+    pub fn rent_factor(&self) -> f64 {
+        self.revenue_per_occupant as f64 / self.floor_type().base_rent as f64
+    }
+
+    /// Moves people in or out until `occupancy` are on the floor, up to its
+    /// capacity. When towers share a city, people who leave go back to its
+    /// `tenant_pool` and newcomers come from it, as long as there are any.
+    pub fn set_occupancy(&mut self, occupancy: usize, tenant_pool: Option<&Cell<usize>>) {
+        let occupancy = occupancy.min(self.max_occupancy);
+        match tenant_pool {
+            Some(pool) if occupancy > self.occupancy => {
+                let arrivals = (occupancy - self.occupancy).min(pool.get());
+                pool.set(pool.get() - arrivals);
+                self.occupancy += arrivals;
+            }
+            Some(pool) => {
+                pool.set(pool.get() + self.occupancy - occupancy);
+                self.occupancy = occupancy;
+            }
+            None => self.occupancy = occupancy,
+        }
+    }
 
-        let name = format!("|{name:^TOWER_WIDTH$}|\n|{:TOWER_WIDTH$}|\n", "");
+    pub fn set_string(&mut self) {
+        let name = &self.floor_type().name;
+        let rent = format!("RENT: ${}", self.revenue_per_occupant);
+        let name = format!("|{name:^TOWER_WIDTH$}|\n|{rent:^TOWER_WIDTH$}|\n");
         let revenue: String = format!("|       REVENUE: {:<4}    |\n", self.revenue());
         let occupancy: String = format!("|      OCCUPANCY: {:<4}   |\n", self.occupancy);
-        self.text = name + &revenue + &occupancy;
+        self.text = name + revenue.as_str() + occupancy.as_str();
+    }
+
+    /// The floor as a single line, for the compact view.
+    fn compact_text(&self) -> String {
+        let stats = format!(
+            "${} {}/{}",
+            self.revenue_per_occupant, self.occupancy, self.max_occupancy
+        );
+        let width = TOWER_WIDTH.saturating_sub(stats.len() + 1);
+        let line = format!("{:<width$.width$} {stats}", self.floor_type().name);
+        format!("|{line:.TOWER_WIDTH$}|\n")
+    }
+}
+
+/// Everything that sets one type of floor apart from another.
+#[derive(Clone, Debug)]
+pub struct FloorType {
+    /// Identifier used for the floor type in key bindings, commands and
+    /// save files.
+    pub id: String,
+    /// Shown on the floor itself.
+    pub name: String,
+    /// Shown in menus.
+    pub label: String,
+    /// Key that builds this type of floor in the build menu.
+    pub key: char,
+    /// What the player is charged to build the floor.
+    pub price: i32,
+    /// Rent each occupant pays per tick until the player changes it.
+    pub base_rent: usize,
+    pub max_occupancy: usize,
+    /// What the occupants of this type of floor are called.
+    pub occupants: String,
+    pub color: Color,
+    /// Tower rating needed before the floor type can be built.
+    pub stars: u8,
+}
+
+impl FloorType {
+    fn new(id: &str, name: &str, label: &str, key: char, occupants: &str, color: Color) -> Self {
+        FloorType {
+            id: id.to_string(),
+            name: name.to_string(),
+            label: label.to_string(),
+            key,
+            price: 0,
+            base_rent: 0,
+            max_occupancy: 0,
+            occupants: occupants.to_string(),
+            color,
+            stars: 1,
+        }
+    }
+
+    /// The floor types the game ships with, in the order of `LayerType`'s
+    /// built in variants.
+    pub fn builtin() -> Vec<FloorType> {
+        vec![
+            FloorType {
+                price: 10000,
+                base_rent: 7,
+                max_occupancy: 30,
+                ..FloorType::new("food", "FOOD COURT", "Food court", 'f', "diners", Color::Yellow)
+            },
+            FloorType {
+                price: 12000,
+                base_rent: 17,
+                max_occupancy: 20,
+                ..FloorType::new(
+                    "apartment",
+                    "APARTMENT COMPLEX",
+                    "Apartments",
+                    'a',
+                    "tenants",
+                    Color::Green,
+                )
+            },
+            FloorType {
+                price: 8000,
+                base_rent: 12,
+                max_occupancy: 10,
+                ..FloorType::new("retail", "RETAIL STORE", "Retail", 'r', "shops", Color::Magenta)
+            },
+            FloorType {
+                price: 15000,
+                base_rent: 10,
+                max_occupancy: 40,
+                stars: 2,
+                ..FloorType::new("office", "OFFICE SUITES", "Offices", 'o', "workers", Color::Blue)
+            },
+            FloorType {
+                price: 25000,
+                base_rent: 30,
+                max_occupancy: 25,
+                stars: 3,
+                ..FloorType::new("hotel", "HOTEL", "Hotel", 'h', "guests", Color::Cyan)
+            },
+            FloorType {
+                price: 60000,
+                base_rent: 80,
+                max_occupancy: 15,
+                stars: 5,
+                ..FloorType::new(
+                    "sky_lounge",
+                    "SKY LOUNGE",
+                    "Sky lounge",
+                    's',
+                    "visitors",
+                    Color::DarkYellow,
+                )
+            },
+        ]
+    }
+}
+
+/// The types of floor a game can build: the built in ones, as mods left
+/// them, followed by the ones mods added.
+#[derive(Clone)]
+pub struct FloorTypes(Rc<[FloorType]>);
+
+impl FloorTypes {
+    /// The first floor types must be the built in ones.
+    pub fn new(floor_types: Vec<FloorType>) -> Self {
+        FloorTypes(floor_types.into())
+    }
+
+    pub fn get(&self, style: LayerType) -> &FloorType {
+        &self.0[style.index()]
+    }
+
+    /// Every type of floor, in the order the build menu lists them.
+    pub fn all(&self) -> impl Iterator<Item = LayerType> {
+        (0..self.0.len()).map(LayerType::from_index)
+    }
+
+    /// The type of floor called `id`.
+    pub fn find(&self, id: &str) -> Option<LayerType> {
+        self.all().find(|&style| self.get(style).id == id)
     }
 }
 
+impl Default for FloorTypes {
+    fn default() -> Self {
+        FloorTypes::new(FloorType::builtin())
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum LayerType {
-    // Ground,
+    Food,
     Apartment,
     Retail,
-    Food, // Roof,
-          // Ceiling
+    Office,
+    Hotel,
+    SkyLounge,
+    /// A floor type added by a mod, as an index into the floor types.
+    Modded(usize),
 }
 
-trait Style {
-    fn colors(&self) -> Colors;
-}
-impl Style for LayerType {
-    fn colors(&self) -> Colors {
+impl LayerType {
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => LayerType::Food,
+            1 => LayerType::Apartment,
+            2 => LayerType::Retail,
+            3 => LayerType::Office,
+            4 => LayerType::Hotel,
+            5 => LayerType::SkyLounge,
+            index => LayerType::Modded(index),
+        }
+    }
+
+    fn index(self) -> usize {
         match self {
-            // LayerType::Ground => Colors {
-            //     foreground: Some(Color::White),
-            //     background: Some(Color::Black),
-            // },
-            // LayerType::Roof => Colors {
-            //     foreground: Some(Color::Cyan),
-            //     background: Some(Color::Black),
-            // },
-            LayerType::Apartment => Colors {
-                foreground: Some(Color::Green),
-                background: Some(Color::Black),
-            },
-            LayerType::Retail => Colors {
-                foreground: Some(Color::Magenta),
-                background: Some(Color::Black),
-            },
-            LayerType::Food => Colors {
-                foreground: Some(Color::Yellow),
-                background: Some(Color::Black),
-            },
-            // LayerType::Ceiling => Colors {
-            //     foreground: Some(Color::White),
-            //     background: Some(Color::Black),
-            // },
+            LayerType::Food => 0,
+            LayerType::Apartment => 1,
+            LayerType::Retail => 2,
+            LayerType::Office => 3,
+            LayerType::Hotel => 4,
+            LayerType::SkyLounge => 5,
+            LayerType::Modded(index) => index,
         }
     }
 }
@@ -90,30 +388,32 @@ struct LayerText {
     start_row: u16,
     colors: Colors,
 }
-impl LayerText {
-    pub fn new(text: String, num_rows: u16, start_row: u16) -> Self {
-        Self {
-            text: text,
-            start_row: start_row,
-            colors: Colors {
-                foreground: Some(Color::White),
-                background: Some(Color::Black),
-            },
-        }
-    }
-}
 
-pub fn layer_draw(layers: &Vec<Layer>, mut writer: impl Write, scroll_pos: u16) -> io::Result<()> {
-    
+/// Scenery drawn over the tower, with spaces letting what is underneath
+/// show through.
+struct Overlay {
+    text: String,
+    start_row: u16,
+    column: u16,
+    colors: Colors,
+}
 
+pub fn layer_draw(
+    layers: &[Layer],
+    mut writer: impl Write,
+    scroll_pos: u16,
+    view: TowerView,
+    selected: Option<usize>,
+    weather: Weather,
+    scenery: &Scenery,
+) -> io::Result<()> {
     // Set up vector of LayerText objects
     let mut layer_strings = vec![];
+    let mut overlays = vec![];
 
     // Set up Ground, add to LayerText vector
-    let filepaths = vec!["src/graphics/ground.txt", "src/graphics/roof.txt"];
-    let text = fs::read_to_string(filepaths[0]).expect("Was not able to read the file");
     layer_strings.push(LayerText {
-        text: text,
+        text: GROUND_ART.to_string(),
         start_row: 1,
         colors: Colors {
             foreground: Some(Color::White),
@@ -123,15 +423,38 @@ pub fn layer_draw(layers: &Vec<Layer>, mut writer: impl Write, scroll_pos: u16)
 
     // Set up floor layers, add to LayerText vector
     let mut row: u16 = 1;
-    let iterator = layers.iter();
+    let iterator = layers.iter().enumerate();
     let ceiling_text = "|________________________|\n";
-    for layer in iterator {
-        row += 4;
+    for (floor, layer) in iterator {
+        let mut colors = Colors::new(layer.floor_type().color, Color::Black);
+        if selected == Some(floor) {
+            colors.background = Some(Color::DarkGrey);
+        }
+        if view == TowerView::Compact {
+            // No ceilings between floors, they are just stacked
+            row += 1;
+            layer_strings.push(LayerText {
+                text: layer.compact_text(),
+                start_row: row,
+                colors,
+            });
+            continue;
+        }
+        row += FLOOR_HEIGHT - 1;
         layer_strings.push(LayerText {
             text: layer.text.to_string(),
             start_row: row,
-            colors: layer.style.colors(),
+            colors,
         });
+        // People walk in along the bottom line of the floor
+        for (_, column, figure) in scenery.walkers().filter(|(f, _, _)| *f == floor) {
+            overlays.push(Overlay {
+                text: figure.to_string(),
+                start_row: row - (FLOOR_HEIGHT - 2),
+                column,
+                colors,
+            });
+        }
         row += 1;
         layer_strings.push(LayerText {
             text: ceiling_text.to_string(),
@@ -143,11 +466,40 @@ pub fn layer_draw(layers: &Vec<Layer>, mut writer: impl Write, scroll_pos: u16)
         });
     }
 
-    // Set up roof/cloud graphics, add to LayerText vector
-    let text = fs::read_to_string(filepaths[1]).expect("Was not able to read the file");
-    row += 8;
+    // The weather fills the sky between the clouds
+    overlays.push(Overlay {
+        text: weather.sky_art(scenery.phase()),
+        start_row: row + ROOF_HEIGHT,
+        column: 0,
+        colors: Colors {
+            foreground: Some(weather.color()),
+            background: Some(Color::Black),
+        },
+    });
+
+    // A crane stands on the roof while the top floor is being built
+    if let Some((floor, crane)) = scenery.crane() {
+        if floor + 1 == layers.len() {
+            overlays.push(Overlay {
+                text: crane,
+                start_row: row + 6,
+                column: 0,
+                colors: Colors {
+                    foreground: Some(Color::Yellow),
+                    background: Some(Color::Black),
+                },
+            });
+        }
+    }
+
+    // Set up roof/cloud graphics, add to LayerText vector. The clouds drift
+    // but the roof itself stays put.
+    row += ROOF_HEIGHT;
+    let roof_lines: Vec<&str> = ROOF_ART.lines().collect();
+    let (roof, clouds) = roof_lines.split_last().unwrap_or((&"", &[]));
+    let sky: String = clouds.iter().map(|line| scenery.drift(line) + "\n").collect();
     layer_strings.push(LayerText {
-        text: text,
+        text: sky + *roof,
         start_row: row,
         colors: Colors {
             foreground: Some(Color::Cyan),
@@ -174,13 +526,76 @@ pub fn layer_draw(layers: &Vec<Layer>, mut writer: impl Write, scroll_pos: u16)
             }
         }
     }
+    for overlay in &overlays {
+        queue!(writer, style::SetColors(overlay.colors))?;
+        for (s, line) in (scroll_pos..).zip(overlay.text.lines()) {
+            if overlay.start_row > TOWER_WINDOW_HEIGHT + s || s >= overlay.start_row {
+                continue;
+            }
+            let y = TOWER_WINDOW_HEIGHT + s - overlay.start_row;
+            for (x, ch) in (overlay.column..).zip(line.chars()) {
+                if ch != ' ' {
+                    queue!(writer, cursor::MoveTo(x, y), style::Print(ch))?;
+                }
+            }
+        }
+    }
+
+    minimap_draw(layers, writer, scroll_pos, view, selected)
+}
 
+/// Draws the whole tower beside the tower view, one character per floor
+/// (or per few floors, for very tall towers), with the floors in view
+/// highlighted.
+fn minimap_draw(
+    layers: &[Layer],
+    mut writer: impl Write,
+    scroll_pos: u16,
+    view: TowerView,
+    selected: Option<usize>,
+) -> io::Result<()> {
+    let in_view: Vec<usize> = (0..TOWER_WINDOW_HEIGHT)
+        .filter_map(|y| floor_at(layers.len(), scroll_pos, view, 0, y))
+        .collect();
+    let per_cell = floors_per_minimap_cell(layers.len());
+    for (cell, floors) in layers.chunks(per_cell).enumerate() {
+        let first = cell * per_cell;
+        let occupancy: usize = floors.iter().map(|layer| layer.occupancy).sum();
+        let max_occupancy: usize = floors.iter().map(|layer| layer.max_occupancy).sum();
+        let level = match occupancy {
+            0 => 0,
+            _ => 1 + (occupancy * 4 - 1) / max_occupancy.max(1),
+        };
+        let floor_range = first..first + floors.len();
+        let background = if selected.is_some_and(|floor| floor_range.contains(&floor)) {
+            Color::White
+        } else if in_view.iter().any(|floor| floor_range.contains(floor)) {
+            Color::DarkGrey
+        } else {
+            Color::Black
+        };
+        let column = cell as u16 / TOWER_WINDOW_HEIGHT;
+        let row = TOWER_WINDOW_HEIGHT - 1 - cell as u16 % TOWER_WINDOW_HEIGHT;
+        queue!(
+            writer,
+            cursor::MoveTo(MINIMAP_X + column, row),
+            style::SetColors(Colors::new(floors[0].floor_type().color, background)),
+            style::Print(MINIMAP_OCCUPANCY[level.min(4)]),
+        )?;
+    }
     Ok(())
 }
 
-pub fn funds_draw(mut writer: impl Write, funds: i32, debt_timer: u32, msg:&str) -> io::Result<()> {
+pub fn funds_draw(
+    mut writer: impl Write,
+    funds: i32,
+    loan: i32,
+    debt_timer: u32,
+    stars: u8,
+    msg: &str,
+) -> io::Result<()> {
     // const MESSAGE_HEIGHT:u16 = T - 1;
-    const INFO_MSG_WIDTH:u16 = 35;
+    const MSG_WIDTH: usize = (WINDOW_WIDTH - INFO_MSG_WIDTH) as usize;
     // Print message regarding debt collection, purchase, or other above the controls menu
     queue!(
         writer,
@@ -189,11 +604,19 @@ pub fn funds_draw(mut writer: impl Write, funds: i32, debt_timer: u32, msg:&str)
             foreground: Some(Color::White),
             background: Some(Color::Black),
         }),
-        style::Print(msg),
+        // Pad to the full width so no part of the previous message is left over
+        style::Print(format!("{msg:<MSG_WIDTH$}")),
     )?;
 
     // Print current funds and time until debt collector on the right of the tower
-    let fund_string : &str = &(format!("CURRENT FUNDS: {}", funds));
+    let mut fund_string = format!("CURRENT FUNDS: {}", funds);
+    if loan > 0 {
+        fund_string += &format!("  (LOAN: {loan})");
+    }
+    fund_string += "  ";
+    // The tower's rating follows the funds, padded so a longer line from
+    // before doesn't leave anything behind
+    let rating_width = MSG_WIDTH.saturating_sub(fund_string.chars().count());
     queue!(
         writer,
         cursor::MoveTo(INFO_MSG_WIDTH,0),
@@ -202,6 +625,8 @@ pub fn funds_draw(mut writer: impl Write, funds: i32, debt_timer: u32, msg:&str)
             background: Some(Color::Black),
         }),
         style::Print(fund_string),
+        style::SetForegroundColor(Color::Yellow),
+        style::Print(format!("{:<rating_width$}", rating::stars_text(stars))),
     )?;
     let debt_string : &str = &(format!("TIME UNTIL DEBT COLLECTOR COMES: {}", debt_timer));
     queue!(
@@ -217,4 +642,96 @@ pub fn funds_draw(mut writer: impl Write, funds: i32, debt_timer: u32, msg:&str)
 
     Ok(())
 
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_game::TestGame;
+    use crossterm::event::KeyCode;
+
+    #[test]
+    fn scroll_positions_of_the_tallest_towers_fit() {
+        assert_eq!(max_scroll(0, TowerView::Full), 0);
+        assert_eq!(max_scroll(20_000, TowerView::Full), u16::MAX);
+        assert_eq!(max_scroll(usize::MAX, TowerView::Compact), u16::MAX);
+        assert_eq!(floor_row(20_000, TowerView::Full), u16::MAX);
+        assert_eq!(floor_row(usize::MAX, TowerView::Compact), u16::MAX);
+        assert_eq!(floor_row(2, TowerView::Full), 15);
+        assert_eq!(floor_row(2, TowerView::Compact), 4);
+        let top = TOWER_WINDOW_HEIGHT - 1;
+        assert_eq!(floor_at(usize::MAX, u16::MAX, TowerView::Compact, 0, top), Some(65_534));
+    }
+
+    #[test]
+    fn clicks_in_the_tower_find_the_floor_under_them() {
+        let ground = TOWER_WINDOW_HEIGHT - 1;
+        let at = |scroll_pos, view, x, y| floor_at(3, scroll_pos, view, x, y);
+        assert_eq!(at(0, TowerView::Full, 5, ground), None, "the ground isn't a floor");
+        assert_eq!(at(0, TowerView::Full, 5, ground - 1), Some(0));
+        assert_eq!(at(0, TowerView::Full, 5, ground - 5), Some(0), "the ceiling is the floor's");
+        assert_eq!(at(0, TowerView::Full, 5, ground - 6), Some(1));
+        assert_eq!(at(0, TowerView::Full, 0, ground - 1), Some(0), "the left wall is the floor's");
+        assert_eq!(at(0, TowerView::Full, 25, ground - 1), Some(0));
+        assert_eq!(at(0, TowerView::Full, 26, ground - 1), None, "right of the tower");
+        assert_eq!(at(0, TowerView::Full, 5, ground - 16), None, "above the top floor");
+        assert_eq!(at(0, TowerView::Full, 5, TOWER_WINDOW_HEIGHT), None, "in the controls");
+        assert_eq!(at(5, TowerView::Full, 5, ground), Some(0), "scrolled up a floor");
+        assert_eq!(at(0, TowerView::Compact, 5, ground - 3), Some(2));
+    }
+
+    #[test]
+    fn clicks_on_the_minimap_find_the_floors_it_shows() {
+        let bottom = TOWER_WINDOW_HEIGHT - 1;
+        assert_eq!(minimap_floor_at(25, MINIMAP_X, bottom), Some(0));
+        assert_eq!(minimap_floor_at(25, MINIMAP_X, 0), Some(bottom as usize));
+        assert_eq!(minimap_floor_at(25, MINIMAP_X + 1, bottom), Some(TOWER_WINDOW_HEIGHT.into()));
+        assert_eq!(minimap_floor_at(25, MINIMAP_X + 1, bottom - 6), None, "past the top floor");
+        assert_eq!(minimap_floor_at(25, MINIMAP_X - 1, bottom), None, "left of the minimap");
+        assert_eq!(minimap_floor_at(25, MINIMAP_X + MINIMAP_COLUMNS, bottom), None);
+        assert_eq!(minimap_floor_at(25, MINIMAP_X, TOWER_WINDOW_HEIGHT), None, "under it");
+        // Taller towers than fit share each character between floors
+        let cells = (MINIMAP_COLUMNS * TOWER_WINDOW_HEIGHT) as usize;
+        assert_eq!(minimap_floor_at(cells * 2, MINIMAP_X, bottom - 1), Some(2));
+    }
+
+    #[test]
+    fn home_and_end_scroll_to_the_ground_and_the_roof() {
+        let mut game = TestGame::new();
+        game.gs.funds = 1_000_000;
+        for _ in 0..10 {
+            game.build(LayerType::Food);
+        }
+        let roof = max_scroll(10, TowerView::Full);
+        game.press(KeyCode::Home);
+        assert_eq!(game.gs.scroll_pos, 0);
+        game.press(KeyCode::End);
+        assert_eq!(game.gs.scroll_pos, roof);
+        game.press(KeyCode::PageUp);
+        assert_eq!(game.gs.scroll_pos, roof, "scrolled past the roof");
+
+        game.press(KeyCode::Char('v'));
+        assert_eq!(game.gs.scroll_pos, max_scroll(10, TowerView::Compact));
+        game.gs.scroll_to(u16::MAX).unwrap();
+        assert_eq!(game.gs.scroll_pos, max_scroll(10, TowerView::Compact));
+    }
+
+    #[test]
+    fn going_to_a_floor_selects_it_and_scrolls_it_into_view() {
+        let mut game = TestGame::new();
+        game.gs.funds = 1_000_000;
+        for _ in 0..10 {
+            game.build(LayerType::Food);
+        }
+        game.press(KeyCode::Home).press(KeyCode::Char('g')).type_keys("8");
+        game.press(KeyCode::Enter);
+        assert_eq!(game.gs.selected_floor, Some(7));
+        let middle = TOWER_WINDOW_HEIGHT / 2;
+        let floor = floor_at(10, game.gs.scroll_pos, TowerView::Full, 0, middle);
+        assert_eq!(floor, Some(7), "floor 8 isn't in the middle of the view");
+
+        game.press(KeyCode::Char('g')).type_keys("11").press(KeyCode::Enter);
+        assert_eq!(game.gs.selected_floor, Some(7));
+        assert_eq!(game.gs.message, "There is no floor 11");
+    }
+}
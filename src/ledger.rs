@@ -0,0 +1,355 @@
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, queue, terminal};
+use std::io::{self, Write};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TransactionCategory {
+    Construction,
+    Rent,
+    Upkeep,
+    Wages,
+    Taxes,
+    Loan,
+    DebtPayment,
+    /// Money mods give or take.
+    Other,
+}
+
+impl TransactionCategory {
+    pub const ALL: [TransactionCategory; 8] = [
+        TransactionCategory::Rent,
+        TransactionCategory::Construction,
+        TransactionCategory::Upkeep,
+        TransactionCategory::Wages,
+        TransactionCategory::Taxes,
+        TransactionCategory::Loan,
+        TransactionCategory::DebtPayment,
+        TransactionCategory::Other,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionCategory::Construction => "construction",
+            TransactionCategory::Rent => "rent",
+            TransactionCategory::Upkeep => "upkeep",
+            TransactionCategory::Wages => "wages",
+            TransactionCategory::Taxes => "taxes",
+            TransactionCategory::Loan => "loan",
+            TransactionCategory::DebtPayment => "debt_payment",
+            TransactionCategory::Other => "other",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        TransactionCategory::ALL
+            .into_iter()
+            .find(|category| category.name() == name)
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            TransactionCategory::Construction => "Construction",
+            TransactionCategory::Rent => "Rent",
+            TransactionCategory::Upkeep => "Upkeep",
+            TransactionCategory::Wages => "Wages",
+            TransactionCategory::Taxes => "Taxes",
+            TransactionCategory::Loan => "Loans",
+            TransactionCategory::DebtPayment => "Debt payment",
+            TransactionCategory::Other => "Other",
+        }
+    }
+}
+
+/// A single change to the player's funds. Income is positive, spending is
+/// negative.
+#[derive(Copy, Clone, Debug)]
+pub struct Transaction {
+    pub tick: u64,
+    /// Index of the debt collection period the transaction happened in.
+    pub period: u32,
+    pub category: TransactionCategory,
+    /// Index into `GameState::layers`, if the transaction belongs to a floor.
+    pub floor: Option<usize>,
+    pub amount: i32,
+}
+
+pub struct Ledger {
+    transactions: Vec<Transaction>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Ledger {
+            transactions: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, transaction: Transaction) {
+        self.transactions.push(transaction);
+    }
+
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    pub fn period_total(&self, period: u32, category: TransactionCategory) -> i32 {
+        self.transactions
+            .iter()
+            .filter(|t| t.period == period && t.category == category)
+            .map(|t| t.amount)
+            .sum()
+    }
+
+    pub fn period_net(&self, period: u32) -> i32 {
+        self.transactions
+            .iter()
+            .filter(|t| t.period == period)
+            .map(|t| t.amount)
+            .sum()
+    }
+
+    /// Total spent on construction over the whole run, i.e. the book value of
+    /// the tower.
+    pub fn property_value(&self) -> i32 {
+        -self
+            .transactions
+            .iter()
+            .filter(|t| t.category == TransactionCategory::Construction)
+            .map(|t| t.amount)
+            .sum::<i32>()
+    }
+
+    pub fn write_csv(&self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(writer, "tick,period,category,floor,amount")?;
+        for t in &self.transactions {
+            let floor = t.floor.map(|f| (f + 1).to_string()).unwrap_or_default();
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                t.tick,
+                t.period + 1,
+                t.category.name(),
+                floor,
+                t.amount
+            )?;
+        }
+        writer.flush()
+    }
+}
+
+pub fn finances_draw(
+    mut writer: impl Write,
+    ledger: &Ledger,
+    funds: i32,
+    loan: i32,
+    tax_arrears: i32,
+    current_period: u32,
+) -> io::Result<()> {
+    const TOP_ROW: u16 = 3;
+    const LABEL_WIDTH: usize = 16;
+    const PERIOD_WIDTH: usize = 12;
+    const MAX_PERIODS: u32 = 5;
+    const BALANCE_SHEET_COL: u16 = 86;
+
+    let white = Colors {
+        foreground: Some(Color::White),
+        background: Some(Color::Black),
+    };
+
+    queue!(
+        writer,
+        cursor::MoveTo(WINDOW_WIDTH, TOWER_WINDOW_HEIGHT),
+        terminal::Clear(terminal::ClearType::FromCursorUp),
+        style::SetColors(white),
+    )?;
+
+    // Income statement, one column per debt collection period
+    let first_period = (current_period + 1).saturating_sub(MAX_PERIODS);
+    let periods = first_period..=current_period;
+
+    let mut header = format!("{:LABEL_WIDTH$}", "INCOME STATEMENT");
+    for period in periods.clone() {
+        let name = if period == current_period {
+            format!("P{}*", period + 1)
+        } else {
+            format!("P{}", period + 1)
+        };
+        header += &format!("{name:>PERIOD_WIDTH$}");
+    }
+    let mut lines = vec![header];
+    for category in TransactionCategory::ALL {
+        let mut line = format!("{:LABEL_WIDTH$}", category.label());
+        for period in periods.clone() {
+            line += &format!("{:>PERIOD_WIDTH$}", ledger.period_total(period, category));
+        }
+        lines.push(line);
+    }
+    let mut line = format!("{:LABEL_WIDTH$}", "Net income");
+    for period in periods {
+        line += &format!("{:>PERIOD_WIDTH$}", ledger.period_net(period));
+    }
+    lines.push(line);
+    lines.push("* period in progress".to_string());
+
+    for (row, line) in (TOP_ROW..).zip(lines.iter()) {
+        // Leave a blank line between the per-category rows and the totals
+        let row = if row > TOP_ROW + TransactionCategory::ALL.len() as u16 {
+            row + 1
+        } else {
+            row
+        };
+        queue!(writer, cursor::MoveTo(0, row), style::Print(line))?;
+    }
+
+    // Balance sheet
+    let cash = funds.max(0);
+    let overdraft = (-funds).max(0);
+    let property = ledger.property_value();
+    let assets = cash + property;
+    let balance_sheet = [
+        "BALANCE SHEET".to_string(),
+        format!("{:<18}{:>12}", "Cash", cash),
+        format!("{:<18}{:>12}", "Property at cost", property),
+        format!("{:<18}{:>12}", "Total assets", assets),
+        String::new(),
+        format!("{:<18}{:>12}", "Overdraft", overdraft),
+        format!("{:<18}{:>12}", "Loans", loan),
+        format!("{:<18}{:>12}", "Back taxes", tax_arrears),
+        format!("{:<18}{:>12}", "Equity", assets - overdraft - loan - tax_arrears),
+    ];
+    for (row, line) in (TOP_ROW..).zip(balance_sheet.iter()) {
+        queue!(
+            writer,
+            cursor::MoveTo(BALANCE_SHEET_COL, row),
+            style::Print(line)
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameBuffer;
+    use crate::layer::LayerType;
+    use crate::test_game::TestGame;
+    use crossterm::event::KeyCode;
+    use std::fs;
+
+    fn ledger_with(transactions: &[(u32, TransactionCategory, Option<usize>, i32)]) -> Ledger {
+        let mut ledger = Ledger::new();
+        for (tick, &(period, category, floor, amount)) in (1..).zip(transactions) {
+            ledger.record(Transaction {
+                tick,
+                period,
+                category,
+                floor,
+                amount,
+            });
+        }
+        ledger
+    }
+
+    #[test]
+    fn totals_by_period_and_category() {
+        use TransactionCategory::*;
+        let ledger = ledger_with(&[
+            (0, Construction, Some(0), -500),
+            (0, Rent, Some(0), 40),
+            (0, Rent, Some(1), 60),
+            (1, Construction, Some(1), -300),
+            (1, Rent, Some(0), 25),
+            (1, Upkeep, Some(0), -5),
+        ]);
+        assert_eq!(ledger.period_total(0, Rent), 100);
+        assert_eq!(ledger.period_total(1, Rent), 25);
+        assert_eq!(ledger.period_total(1, Upkeep), -5);
+        assert_eq!(ledger.period_total(2, Rent), 0);
+        assert_eq!(ledger.period_net(0), -400);
+        assert_eq!(ledger.period_net(1), -280);
+        assert_eq!(ledger.property_value(), 800);
+    }
+
+    #[test]
+    fn exports_one_csv_row_per_transaction() {
+        use TransactionCategory::*;
+        let ledger = ledger_with(&[(0, Construction, Some(0), -500), (2, DebtPayment, None, -70)]);
+        let mut csv = Vec::new();
+        ledger.write_csv(&mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "tick,period,category,floor,amount\n1,1,construction,1,-500\n2,3,debt_payment,,-70\n"
+        );
+        for category in TransactionCategory::ALL {
+            assert_eq!(TransactionCategory::from_name(category.name()), Some(category));
+        }
+    }
+
+    #[test]
+    fn each_game_exports_its_ledger_to_its_own_data_directory() {
+        let mut first = TestGame::new();
+        let mut second = TestGame::new();
+        first.build(LayerType::Food);
+        first.press(KeyCode::Char('f')).press(KeyCode::Char('e'));
+        second.press(KeyCode::Char('f')).press(KeyCode::Char('e'));
+
+        let csv = |game: &TestGame| fs::read_to_string(game.data_dir.0.join("ledger.csv")).unwrap();
+        assert_eq!(csv(&first).lines().count(), 2, "{}", csv(&first));
+        assert_eq!(csv(&second).lines().count(), 1, "{}", csv(&second));
+        assert!(first.gs.message.starts_with("Exported ledger to "), "{}", first.gs.message);
+    }
+
+    #[test]
+    fn running_a_tower_records_its_upkeep_and_debt_payments() {
+        use crate::staff::StaffRole;
+        use crate::weather::{Calendar, Weather};
+        use TransactionCategory::*;
+        let mut game = TestGame::new();
+        game.gs.calendar = Calendar::restore(0, &[Weather::Heatwave; 4], &mut game.gs.rng);
+        game.build(LayerType::Apartment);
+        game.gs.layers[0].occupancy = 10;
+        game.gs.change_staff(StaffRole::Janitor, 1).unwrap();
+        game.gs.take_loan(1000).unwrap();
+        game.gs.debt_collection_timer = 1;
+        game.tick();
+
+        let total = |category| game.gs.ledger.period_total(0, category);
+        assert_eq!(total(Loan), 1000);
+        assert_eq!(total(DebtPayment), -1100, "the loan is repaid with interest");
+        for category in [Construction, Rent, Upkeep, Wages] {
+            assert_ne!(total(category), 0, "nothing recorded for {}", category.name());
+        }
+        // Mods are the only source of other income
+        assert_eq!(total(Other), 0);
+    }
+
+    #[test]
+    fn finances_show_period_totals_and_the_balance_sheet() {
+        use TransactionCategory::*;
+        let ledger = ledger_with(&[
+            (0, Construction, Some(0), -500),
+            (0, Rent, Some(0), 40),
+            (1, Rent, Some(0), 60),
+            (1, Loan, None, 1000),
+        ]);
+        let mut frame = FrameBuffer::new();
+        finances_draw(&mut frame, &ledger, 600, 1000, 30, 1).unwrap();
+        let row = |label: &str| {
+            let (_, y) = frame.find(label).unwrap();
+            frame.row_text(y)
+        };
+        let words = |line: String| -> Vec<String> {
+            line.split_whitespace().map(str::to_string).collect()
+        };
+        assert_eq!(words(row("INCOME STATEMENT"))[2..4], ["P1", "P2*"]);
+        assert_eq!(words(row("Rent"))[1..3], ["40", "60"]);
+        assert_eq!(words(row("Construction"))[1..3], ["-500", "0"]);
+        assert_eq!(words(row("Net income"))[2..4], ["-460", "1060"]);
+        assert!(row("Property at cost").ends_with(" 500"));
+        assert!(row("Total assets").ends_with(" 1100"));
+        // 1100 in assets less 1000 of loans and 30 of back taxes
+        assert!(row("Equity").ends_with(" 70"));
+    }
+}
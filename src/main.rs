@@ -1,15 +1,54 @@
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
-use crossterm::{cursor, execute, terminal};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+    MouseButton, MouseEvent, MouseEventKind,
+};
+use crossterm::{cursor, execute, queue, terminal};
 use futures::prelude::*;
 use std::io;
 use std::pin::pin;
+use std::rc::Rc;
 use std::time::Duration;
+use tokio::time::MissedTickBehavior;
 
+mod achievements;
+mod agent;
+mod action;
 mod command;
+mod console;
+mod frame;
 mod game_state;
+mod help;
+mod history;
+mod json;
+mod keymap;
 mod layer;
+mod ledger;
+mod message_log;
+mod mods;
+mod net;
+mod rating;
+mod rules;
+mod save;
+mod scenery;
+mod settings;
+mod staff;
+mod storage;
+mod tax;
+mod telnet;
+#[cfg(test)]
+mod test_game;
+#[cfg(test)]
+mod screen_tests;
+mod undo;
+mod versus;
+mod weather;
 
-use game_state::GameState;
+use achievements::GameEvent;
+use action::Action;
+use game_state::{GameState, Screen};
+use ledger::TransactionCategory;
+use rules::Rules;
+use storage::DataDir;
 
 pub const WINDOW_WIDTH: u16 = 120;
 pub const WINDOW_HEIGHT: u16 = 30;
@@ -17,8 +56,128 @@ pub const WINDOW_HEIGHT: u16 = 30;
 const TOWER_WINDOW_HEIGHT: u16 = WINDOW_HEIGHT - CONTROLS_HEIGHT;
 pub const CONTROLS_HEIGHT: u16 = 11;
 
+/// Rows scrolled per notch of the mouse wheel.
+const WHEEL_SCROLL_ROWS: i32 = 2;
+
+const USAGE: &str = "\
+Usage: text_game [--versus [COLLECTIONS]] [--host [ADDRESS] | --join ADDRESS]
+       text_game --serve [ADDRESS]
+       text_game --agent [--lockstep]
+
+  --versus [COLLECTIONS]  Two players on one keyboard, each with a tower. The
+                          first to go bankrupt loses, or the richer after
+                          COLLECTIONS debt collections (5 by default) wins.
+  --host [ADDRESS]        Play versus against someone on another terminal,
+                          who joins on ADDRESS (0.0.0.0:7878 by default).
+  --join ADDRESS          Join a versus game hosted at ADDRESS, a host name
+                          or IP address with an optional :PORT.
+  --serve [ADDRESS]       Serve a game of their own to everyone who connects
+                          with telnet to ADDRESS (0.0.0.0:2323 by default).
+  --agent                 Let another program play, sending requests and
+                          getting the game's state as JSON lines on stdin
+                          and stdout.
+  --lockstep              With --agent, only move the clock on when the
+                          program asks.";
+
+/// How the game was asked to run.
+#[derive(Debug, PartialEq, Eq)]
+enum Mode {
+    Single,
+    Versus { collections: u32 },
+    Host { address: String, collections: u32 },
+    Join { address: String },
+    Serve { address: String },
+    Agent { lockstep: bool },
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Mode, String> {
+    let mut versus = None;
+    let mut host = None;
+    let mut join = None;
+    let mut serve = None;
+    let mut agent = false;
+    let mut lockstep = false;
+    let mut args = args.by_ref().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--versus" => {
+                let collections = match args.next_if(|arg| !arg.starts_with("--")) {
+                    Some(n) => n
+                        .parse()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .ok_or_else(|| format!("`{n}` is not a number of collections"))?,
+                    None => versus::DEFAULT_COLLECTIONS,
+                };
+                versus = Some(collections);
+            }
+            "--host" => {
+                let address = args.next_if(|arg| !arg.starts_with("--"));
+                host = Some(address.unwrap_or_else(|| format!("0.0.0.0:{}", net::DEFAULT_PORT)));
+            }
+            "--join" => {
+                let address = args.next_if(|arg| !arg.starts_with("--"));
+                join = Some(address.ok_or("--join needs the address of the host")?);
+            }
+            "--serve" => {
+                let address = args.next_if(|arg| !arg.starts_with("--"));
+                serve = Some(address.unwrap_or_else(|| telnet::DEFAULT_ADDRESS.to_string()));
+            }
+            "--agent" => agent = true,
+            "--lockstep" => lockstep = true,
+            "--help" | "-h" => return Err(String::new()),
+            _ => return Err(format!("unknown argument `{arg}`")),
+        }
+    }
+    if agent {
+        if versus.is_some() || host.is_some() || join.is_some() || serve.is_some() {
+            return Err("agents play single player games on their own".to_string());
+        }
+        return Ok(Mode::Agent { lockstep });
+    }
+    if lockstep {
+        return Err("--lockstep only works with --agent".to_string());
+    }
+    let collections = versus.unwrap_or(versus::DEFAULT_COLLECTIONS);
+    match (host, join, serve) {
+        (Some(address), None, None) => Ok(Mode::Host { address, collections }),
+        (None, Some(_), None) if versus.is_some() => {
+            Err("the host decides how many collections are played".to_string())
+        }
+        (None, Some(address), None) => Ok(Mode::Join { address }),
+        (None, None, Some(_)) if versus.is_some() => {
+            Err("only single player games can be served".to_string())
+        }
+        (None, None, Some(address)) => Ok(Mode::Serve { address }),
+        (None, None, None) if versus.is_some() => Ok(Mode::Versus { collections }),
+        (None, None, None) => Ok(Mode::Single),
+        _ => Err("choose one of --host, --join and --serve".to_string()),
+    }
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> io::Result<()> {
+    let mode = match parse_args(std::env::args().skip(1)) {
+        Ok(mode) => mode,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("{e}\n");
+            }
+            eprintln!("{USAGE}");
+            std::process::exit(if e.is_empty() { 0 } else { 2 });
+        }
+    };
+
+    let data_dir = DataDir::from_env();
+    // The server's own terminal just shows who comes and goes
+    if let Mode::Serve { address } = &mode {
+        return telnet::serve(Rc::new(mods::Mods::load(&data_dir)), address).await;
+    }
+    // Stdout is for the agent, so the terminal is left alone
+    if let Mode::Agent { lockstep } = mode {
+        return agent::run(Rc::new(mods::Mods::load(&data_dir)), lockstep).await;
+    }
+
     // Calling supports_ansi() on Windows may actually *cause* the terminal
     // to begin supporting ansi escape codes, so this check serves 2 purposes.
     #[cfg(windows)]
@@ -37,50 +196,127 @@ async fn main() -> io::Result<()> {
         terminal::EnterAlternateScreen,
         terminal::Clear(terminal::ClearType::All),
         terminal::DisableLineWrap,
+        event::EnableMouseCapture,
         cursor::Hide,
-        terminal::BeginSynchronizedUpdate,
     )?;
 
-    let mut gs = game_state::init_game_state(stdout);
+    // Where the terminal can tell key presses from auto-repeats, ask it to,
+    // so only commands meant to repeat do so when a key is held.
+    let keyboard_enhanced = matches!(terminal::supports_keyboard_enhancement(), Ok(true));
+    if keyboard_enhanced {
+        execute!(
+            stdout,
+            event::PushKeyboardEnhancementFlags(
+                KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+            )
+        )?;
+    }
+
+    // Mods are loaded once, since the floor types they add can't change
+    // while the game is running
+    let mods = Rc::new(mods::Mods::load(&data_dir));
+    let result = match mode {
+        Mode::Single => play(&mut stdout, mods, data_dir, event::EventStream::new()).await,
+        Mode::Versus { collections } => versus::run(&mut stdout, mods, collections).await,
+        Mode::Host { address, collections } => {
+            net::host(&mut stdout, mods, &address, collections).await
+        }
+        Mode::Join { address } => net::join(&mut stdout, &address).await,
+        Mode::Serve { .. } | Mode::Agent { .. } => {
+            unreachable!("played before the terminal was set up")
+        }
+    };
+
+    if keyboard_enhanced {
+        execute!(stdout, event::PopKeyboardEnhancementFlags)?;
+    }
+    execute!(
+        stdout,
+        event::DisableMouseCapture,
+        terminal::LeaveAlternateScreen,
+        cursor::Show,
+        terminal::SetSize(original_w, original_h)
+    )?;
+
+    result
+}
+
+/// Plays single player games keeping their files in `data_dir` until the
+/// player quits, drawing to `out` and taking key presses and the like from
+/// `event_stream`.
+async fn play(
+    mut out: impl io::Write,
+    mods: Rc<mods::Mods>,
+    data_dir: DataDir,
+    mut event_stream: impl Stream<Item = io::Result<Event>> + Unpin,
+) -> io::Result<()> {
+    let frame = frame::FrameBuffer::new();
+    let mut gs =
+        game_state::init_game_state(frame, Rc::clone(&mods), Rules::default(), data_dir);
 
-    let mut event_stream = event::EventStream::new();
     let mut heartbeat = tokio::time::interval(Duration::from_secs(1));
+    let mut speed = gs.speed;
+    // The scenery has a timer of its own, so it moves smoothly whatever the
+    // game speed and a slow frame never holds up the game
+    let mut animation = (gs.animation_fps > 0).then(|| {
+        let mut animation = tokio::time::interval(Duration::from_secs(1) / gs.animation_fps);
+        animation.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        animation
+    });
 
     gs.draw_command_pool()?;
     gs.draw_tower()?;
+    gs.show_new_game()?;
 
     'main_loop: while gs.running {
         if gs.restarting {
-            gs = game_state::init_game_state(gs.stdout);
+            let recorded = gs.end_run();
+            let rules = gs.next_rules.take().unwrap_or_else(|| gs.rules.clone());
+            let data_dir = gs.data_dir.clone();
+            gs = game_state::init_game_state(gs.frame, Rc::clone(&mods), rules, data_dir);
+            if let Err(e) = recorded {
+                gs.toast(format!("Could not save the last run: {e}"))?;
+            }
             gs.draw_command_pool()?;
             gs.draw_tower()?;
         }
 
+        if gs.speed != speed {
+            speed = gs.speed;
+            heartbeat = tokio::time::interval(Duration::from_secs(1) / speed);
+            // The first tick of a new interval is immediate
+            heartbeat.reset();
+        }
+
         let mut event_fut = event_stream.next().fuse();
         let mut heartbeat_fut = pin!(heartbeat.tick().fuse());
+        let mut animation_fut = pin!(async {
+            match &mut animation {
+                Some(animation) => animation.tick().await,
+                None => future::pending().await,
+            }
+        }
+        .fuse());
         futures::select! {
             event = event_fut => match event {
                 Some(Ok(Event::Key(ke))) => handle_key_event(&mut gs, ke)?,
+                Some(Ok(Event::Mouse(me))) => handle_mouse_event(&mut gs, me)?,
+                // Resizing can garble what the terminal shows
+                Some(Ok(Event::Resize(..))) => gs.frame.invalidate(),
                 None => break 'main_loop,
                 Some(Err(e)) => return Err(e),
                 _ => {}
             },
             _ = heartbeat_fut => handle_time_tick(&mut gs)?,
+            _ = animation_fut => gs.animate()?,
         };
-        execute!(
-            gs.stdout,
-            terminal::EndSynchronizedUpdate,
-            terminal::BeginSynchronizedUpdate,
-        )?;
+        queue!(out, terminal::BeginSynchronizedUpdate)?;
+        gs.frame.render(&mut out)?;
+        execute!(out, terminal::EndSynchronizedUpdate)?;
     }
-
-    execute!(
-        gs.stdout,
-        terminal::LeaveAlternateScreen,
-        cursor::Show,
-        terminal::SetSize(original_w, original_h)
-    )?;
-
+    // There is nowhere left to say so if the run can't be recorded
+    let _ = gs.end_run();
     Ok(())
 }
 
@@ -100,16 +336,22 @@ fn handle_key_event(gs: &mut GameState, ke: KeyEvent) -> io::Result<()> {
 
     let pool = gs.command_pool();
     let Some(command) = pool.get_command(ke.code, ke.modifiers) else {
+        if let (KeyCode::Char(c), Some(text_input)) = (ke.code, pool.text_input()) {
+            let typed = !ke
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+            if typed && ke.kind != KeyEventKind::Release {
+                text_input(gs, c)?;
+            }
+        }
         return Ok(());
     };
     match ke.kind {
         KeyEventKind::Press => {
             command.execute(gs)?;
         }
-        KeyEventKind::Repeat => {
-            if command.is_triggered_on_repeat() {
-                command.execute(gs)?;
-            }
+        KeyEventKind::Repeat if command.is_triggered_on_repeat() => {
+            command.execute(gs)?;
         }
         _ => {}
     }
@@ -117,21 +359,165 @@ fn handle_key_event(gs: &mut GameState, ke: KeyEvent) -> io::Result<()> {
     Ok(())
 }
 
+fn handle_mouse_event(gs: &mut GameState, me: MouseEvent) -> io::Result<()> {
+    match me.kind {
+        MouseEventKind::ScrollUp if gs.screen == Screen::Tower => gs.scroll_by(WHEEL_SCROLL_ROWS),
+        MouseEventKind::ScrollDown if gs.screen == Screen::Tower => {
+            gs.scroll_by(-WHEEL_SCROLL_ROWS)
+        }
+        MouseEventKind::Down(MouseButton::Left) => {
+            let page = gs.command_pool_array.page();
+            if let Some(command) = gs.command_pool().command_at(page, me.column, me.row) {
+                return command.execute(gs);
+            }
+            if gs.screen != Screen::Tower {
+                return Ok(());
+            }
+            if let Some(floor) = layer::minimap_floor_at(gs.layers.len(), me.column, me.row) {
+                return Action::SelectFloor(floor).perform(gs);
+            }
+            let (x, y) = (me.column, me.row);
+            match layer::floor_at(gs.layers.len(), gs.scroll_pos, gs.tower_view, x, y) {
+                Some(floor) => gs.select_floor(floor),
+                None => Ok(()),
+            }
+        }
+        _ => Ok(()),
+    }
+}
+
 fn handle_time_tick(gs: &mut GameState) -> io::Result<()> {
+    // The clock doesn't start until the player has picked the rules
+    if gs.screen == Screen::NewGame {
+        return Ok(());
+    }
     if gs.debt_collection_timer == 0 {
         gs.draw_funds(0, 0)?;
         return Ok(());
     }
 
+    gs.tick += 1;
     gs.debt_collection_timer -= 1;
+    gs.undo_history.expire(gs.tick, gs.period, gs.stars);
+    gs.age_toast();
+    let weather_changed = gs.calendar.advance(gs.tick, &mut gs.rng);
+    let incidents = gs.staff.tend(&mut gs.layers, gs.tenant_pool.as_deref(), &mut gs.rng);
     gs.update_occupancies();
-    gs.draw_tower()?;
+    gs.draw_screen()?;
 
     let mut revenue : i32 = 0;
-    for layer in &gs.layers {
-        revenue += layer.revenue() as i32;
+    for floor in 0..gs.layers.len() {
+        let layer_revenue = gs.layers[floor].revenue() as i32;
+        if layer_revenue > 0 {
+            gs.record(TransactionCategory::Rent, Some(floor), layer_revenue);
+        }
+        revenue += layer_revenue;
+    }
+    let charges = gs.charge_utilities() + gs.pay_wages();
+    gs.draw_funds(charges, revenue)?;
+    if weather_changed {
+        gs.toast(gs.calendar.today().description().to_string())?;
+    }
+    // Only the first incident is toasted, but they all go in the log
+    if let Some((first, rest)) = incidents.split_first() {
+        gs.toast(first.message())?;
+        for incident in rest {
+            gs.message_log.push(gs.tick, incident.message());
+        }
     }
-    gs.draw_funds(0, revenue)?;
+    gs.notify(GameEvent::Tick)?;
+    // A new star is bigger news than any achievement this tick
+    gs.update_rating()?;
+    let mods = Rc::clone(&gs.mods);
+    mods.on_tick(gs)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_game::TestGame;
+
+    fn click(game: &mut TestGame, column: u16, row: u16) {
+        let kind = MouseEventKind::Down(MouseButton::Left);
+        let modifiers = KeyModifiers::NONE;
+        handle_mouse_event(&mut game.gs, MouseEvent { kind, column, row, modifiers }).unwrap();
+    }
+
+    #[test]
+    fn clicks_run_controls_and_select_floors() {
+        let mut game = TestGame::new();
+        game.gs.funds = 1_000_000;
+        for _ in 0..3 {
+            game.build(layer::LayerType::Food);
+        }
+        let ground = TOWER_WINDOW_HEIGHT - 1;
+
+        click(&mut game, 5, ground - 6);
+        assert_eq!(game.gs.selected_floor, Some(1));
+        click(&mut game, 28, ground);
+        assert_eq!(game.gs.selected_floor, Some(0), "the minimap didn't select a floor");
+        click(&mut game, 26, ground - 6);
+        click(&mut game, 28, 0);
+        click(&mut game, u16::MAX, u16::MAX);
+        assert_eq!(game.gs.selected_floor, Some(0), "a click off the tower selected a floor");
+
+        // Floors can't be picked from behind another screen
+        game.press(KeyCode::Char('f'));
+        click(&mut game, 5, ground - 11);
+        assert_eq!(game.gs.selected_floor, Some(0));
+        game.press(KeyCode::Char('x'));
+
+        let (x, y) = game.gs.frame.find("b: Build").expect("no build control");
+        click(&mut game, x, y);
+        assert!(game.gs.frame.find("b: Build").is_none(), "the build menu didn't open");
+    }
+
+    fn parse(args: &[&str]) -> Result<Mode, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parses_versus_with_and_without_collections() {
+        assert_eq!(parse(&[]), Ok(Mode::Single));
+        let collections = versus::DEFAULT_COLLECTIONS;
+        assert_eq!(parse(&["--versus"]), Ok(Mode::Versus { collections }));
+        assert_eq!(parse(&["--versus", "3"]), Ok(Mode::Versus { collections: 3 }));
+        assert!(parse(&["--versus", "none"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn parses_host_and_join() {
+        let collections = versus::DEFAULT_COLLECTIONS;
+        let address = format!("0.0.0.0:{}", net::DEFAULT_PORT);
+        assert_eq!(parse(&["--host"]), Ok(Mode::Host { address, collections }));
+        assert_eq!(
+            parse(&["--versus", "2", "--host", "127.0.0.1:9000"]),
+            Ok(Mode::Host { address: "127.0.0.1:9000".to_string(), collections: 2 })
+        );
+        let address = "example.com".to_string();
+        assert_eq!(parse(&["--join", "example.com"]), Ok(Mode::Join { address }));
+        assert!(parse(&["--join"]).is_err());
+        assert!(parse(&["--host", "--join", "example.com"]).is_err());
+    }
+
+    #[test]
+    fn parses_serve() {
+        let address = telnet::DEFAULT_ADDRESS.to_string();
+        assert_eq!(parse(&["--serve"]), Ok(Mode::Serve { address }));
+        let address = "127.0.0.1:2300".to_string();
+        assert_eq!(parse(&["--serve", "127.0.0.1:2300"]), Ok(Mode::Serve { address }));
+        assert!(parse(&["--serve", "--host"]).is_err());
+        assert!(parse(&["--versus", "--serve"]).is_err());
+    }
+
+    #[test]
+    fn parses_agent() {
+        assert_eq!(parse(&["--agent"]), Ok(Mode::Agent { lockstep: false }));
+        assert_eq!(parse(&["--lockstep", "--agent"]), Ok(Mode::Agent { lockstep: true }));
+        assert!(parse(&["--lockstep"]).is_err());
+        assert!(parse(&["--agent", "--versus"]).is_err());
+    }
+}
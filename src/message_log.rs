@@ -0,0 +1,72 @@
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, queue, terminal};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+/// Most lines kept in the log. Older ones are forgotten.
+const MAX_LINES: usize = 200;
+
+/// Notable things that have happened this game, like achievements, weather
+/// and tax bills, kept so they can be read after they leave the message
+/// area.
+#[derive(Default)]
+pub struct MessageLog {
+    /// Tick each line was logged at and the line, oldest first.
+    lines: VecDeque<(u64, String)>,
+}
+
+impl MessageLog {
+    pub fn push(&mut self, tick: u64, line: impl Into<String>) {
+        if self.lines.len() == MAX_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((tick, line.into()));
+    }
+
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &(u64, String)> {
+        self.lines.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+}
+
+/// Shows the most recent lines of the log, newest at the bottom.
+pub fn message_log_draw(mut writer: impl Write, log: &MessageLog) -> io::Result<()> {
+    const TOP_ROW: u16 = 3;
+    const ROWS: usize = (TOWER_WINDOW_HEIGHT - TOP_ROW - 2) as usize;
+
+    queue!(
+        writer,
+        cursor::MoveTo(WINDOW_WIDTH, TOWER_WINDOW_HEIGHT),
+        terminal::Clear(terminal::ClearType::FromCursorUp),
+        style::SetColors(Colors {
+            foreground: Some(Color::White),
+            background: Some(Color::Black),
+        }),
+        cursor::MoveTo(0, TOP_ROW),
+        style::Print("MESSAGES"),
+    )?;
+    let mut lines: Vec<&(u64, String)> = log.lines().rev().take(ROWS).collect();
+    if lines.is_empty() {
+        return queue!(
+            writer,
+            cursor::MoveTo(0, TOP_ROW + 1),
+            style::Print("Nothing has happened yet."),
+        );
+    }
+    lines.reverse();
+    for (row, (tick, line)) in (TOP_ROW + 1..).zip(lines) {
+        queue!(
+            writer,
+            cursor::MoveTo(0, row),
+            style::SetForegroundColor(Color::DarkGrey),
+            style::Print(format!("{tick:>6}  ")),
+            style::SetForegroundColor(Color::White),
+            style::Print(line),
+        )?;
+    }
+    Ok(())
+}
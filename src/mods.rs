@@ -0,0 +1,678 @@
+use crate::game_state::GameState;
+use crate::layer::{FloorType, FloorTypes, LayerType, MAX_RENT};
+use crate::ledger::TransactionCategory;
+use crate::rules::Rules;
+use crate::rating::MAX_STARS;
+use crate::storage::DataDir;
+use crossterm::style::Color;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST, FLOAT, INT};
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+const MODS_DIR: &str = "mods";
+/// Most steps a script can take in one go, so a mod stuck in a loop can't
+/// freeze the game.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// A console command added by a mod.
+#[derive(Clone)]
+pub struct ModCommand {
+    pub name: String,
+    pub help: String,
+    script: usize,
+    function: String,
+}
+
+/// Something a mod wants to happen now and then, with `chance` of it
+/// happening each tick.
+#[derive(Clone)]
+struct ModEvent {
+    name: String,
+    chance: f64,
+    script: usize,
+    function: String,
+}
+
+/// What mods set up when they are loaded.
+#[derive(Clone)]
+struct Registry {
+    /// Index of the script being loaded, if one is.
+    loading: Option<usize>,
+    floor_types: Vec<FloorType>,
+    commands: Vec<ModCommand>,
+    events: Vec<ModEvent>,
+    /// Rules mods set, applied over whichever rules a game is played with.
+    rule_changes: Vec<(String, f64)>,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+}
+
+/// Changes a script asks for, made to the game once the script is done.
+enum Effect {
+    Money(i32),
+    Message(String),
+    SetOccupancy(usize, usize),
+    SetRent(usize, usize),
+}
+
+struct FloorInfo {
+    /// The id of the floor's type.
+    style: String,
+    rent: usize,
+    occupancy: usize,
+    max_occupancy: usize,
+}
+
+struct GameView {
+    funds: i32,
+    tick: u64,
+    period: u32,
+    floors: Vec<FloorInfo>,
+    rng: StdRng,
+    effects: Vec<Effect>,
+}
+
+/// The `game` scripts get passed: a copy of the game state to read from, and
+/// the changes the script made to it.
+#[derive(Clone)]
+pub struct GameApi(Rc<RefCell<GameView>>);
+
+impl GameApi {
+    fn new(gs: &mut GameState) -> Self {
+        let floors = gs
+            .layers
+            .iter()
+            .map(|layer| FloorInfo {
+                style: layer.floor_type().id.clone(),
+                rent: layer.revenue_per_occupant,
+                occupancy: layer.occupancy,
+                max_occupancy: layer.max_occupancy,
+            })
+            .collect();
+        GameApi(Rc::new(RefCell::new(GameView {
+            funds: gs.funds,
+            tick: gs.tick,
+            period: gs.period,
+            floors,
+            rng: StdRng::seed_from_u64(gs.rng.gen()),
+            effects: Vec::new(),
+        })))
+    }
+
+    fn floor<T>(&self, floor: INT, f: impl FnOnce(&mut FloorInfo) -> T) -> ScriptResult<T> {
+        let mut view = self.0.borrow_mut();
+        let info = usize::try_from(floor)
+            .ok()
+            .and_then(|floor| view.floors.get_mut(floor))
+            .ok_or_else(|| format!("there is no floor {floor}"))?;
+        Ok(f(info))
+    }
+
+    /// Adds `amount` to the funds, unless that would take them further
+    /// than they can go.
+    fn change_funds(&self, amount: i32) -> ScriptResult<()> {
+        let mut view = self.0.borrow_mut();
+        view.funds = view.funds.checked_add(amount).ok_or_else(|| {
+            format!("funds of {} can't change by {amount}", view.funds)
+        })?;
+        view.effects.push(Effect::Money(amount));
+        Ok(())
+    }
+
+    fn effect(&self, effect: Effect) {
+        self.0.borrow_mut().effects.push(effect);
+    }
+
+    fn apply(&self, gs: &mut GameState) -> io::Result<()> {
+        let effects = std::mem::take(&mut self.0.borrow_mut().effects);
+        let mut redraw = false;
+        for effect in effects {
+            match effect {
+                Effect::Money(amount) => gs.record(TransactionCategory::Other, None, amount),
+                Effect::Message(msg) => gs.toast(msg)?,
+                Effect::SetOccupancy(floor, occupancy) => {
+                    if let Some(layer) = gs.layers.get_mut(floor) {
+                        layer.set_occupancy(occupancy, gs.tenant_pool.as_deref());
+                        layer.set_string();
+                        redraw = true;
+                    }
+                }
+                Effect::SetRent(floor, rent) => {
+                    if let Some(layer) = gs.layers.get_mut(floor) {
+                        layer.revenue_per_occupant = rent.clamp(1, MAX_RENT);
+                        layer.set_string();
+                        redraw = true;
+                    }
+                }
+            }
+        }
+        if redraw {
+            gs.draw_screen()?;
+        }
+        Ok(())
+    }
+}
+
+fn to_amount(amount: INT) -> ScriptResult<i32> {
+    i32::try_from(amount)
+        .ok()
+        .filter(|amount| *amount >= 0)
+        .ok_or_else(|| format!("{amount} is not an amount of money").into())
+}
+
+fn to_count(n: INT) -> ScriptResult<usize> {
+    usize::try_from(n).map_err(|_| format!("{n} can't be negative").into())
+}
+
+/// The API scripts use to look at and change the game while it is running.
+fn register_game_api(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<GameApi>("Game")
+        .register_get("funds", |game: &mut GameApi| game.0.borrow().funds as INT)
+        .register_get("tick", |game: &mut GameApi| game.0.borrow().tick as INT)
+        .register_get("period", |game: &mut GameApi| game.0.borrow().period as INT)
+        .register_get("floor_count", |game: &mut GameApi| {
+            game.0.borrow().floors.len() as INT
+        })
+        .register_fn("floor_type", |game: &mut GameApi, floor: INT| {
+            game.floor(floor, |info| info.style.clone())
+        })
+        .register_fn("occupancy", |game: &mut GameApi, floor: INT| {
+            game.floor(floor, |info| info.occupancy as INT)
+        })
+        .register_fn("max_occupancy", |game: &mut GameApi, floor: INT| {
+            game.floor(floor, |info| info.max_occupancy as INT)
+        })
+        .register_fn("rent", |game: &mut GameApi, floor: INT| {
+            game.floor(floor, |info| info.rent as INT)
+        })
+        .register_fn("random", |game: &mut GameApi| -> FLOAT {
+            game.0.borrow_mut().rng.gen_range(0.0..1.0)
+        })
+        .register_fn("earn", |game: &mut GameApi, amount: INT| -> ScriptResult<()> {
+            game.change_funds(to_amount(amount)?)
+        })
+        .register_fn("spend", |game: &mut GameApi, amount: INT| -> ScriptResult<()> {
+            game.change_funds(-to_amount(amount)?)
+        })
+        .register_fn("message", |game: &mut GameApi, msg: &str| {
+            game.effect(Effect::Message(msg.to_string()));
+        })
+        .register_fn(
+            "set_occupancy",
+            |game: &mut GameApi, floor: INT, occupancy: INT| -> ScriptResult<()> {
+                let occupancy = to_count(occupancy)?;
+                let occupancy = game.floor(floor, |info| {
+                    info.occupancy = occupancy.min(info.max_occupancy);
+                    info.occupancy
+                })?;
+                game.effect(Effect::SetOccupancy(floor as usize, occupancy));
+                Ok(())
+            },
+        )
+        .register_fn(
+            "set_rent",
+            |game: &mut GameApi, floor: INT, rent: INT| -> ScriptResult<()> {
+                let rent = to_count(rent)?.clamp(1, MAX_RENT);
+                game.floor(floor, |info| info.rent = rent)?;
+                game.effect(Effect::SetRent(floor as usize, rent));
+                Ok(())
+            },
+        );
+}
+
+fn loading_script(registry: &Rc<RefCell<Registry>>, what: &str) -> ScriptResult<usize> {
+    registry
+        .borrow()
+        .loading
+        .ok_or_else(|| format!("{what} can only be called when the mod is loaded").into())
+}
+
+/// Reads a floor type definition like
+/// `#{ id: "gym", name: "GYM", key: "y", price: 9000, rent: 15, capacity: 12 }`
+/// into `floor_type`, which starts out as the type being changed, if any.
+fn read_floor_type(map: &Map, mut floor_type: FloorType) -> ScriptResult<FloorType> {
+    for (field, value) in map {
+        let text = || {
+            value
+                .clone()
+                .into_string()
+                .map_err(|_| format!("{field} should be a string"))
+        };
+        let number = || {
+            value
+                .as_int()
+                .ok()
+                .filter(|n| *n >= 0)
+                .ok_or_else(|| format!("{field} should be a whole number"))
+        };
+        match field.as_str() {
+            "id" => floor_type.id = text()?,
+            "name" => floor_type.name = text()?,
+            "label" => floor_type.label = text()?,
+            "occupants" => floor_type.occupants = text()?,
+            "key" => {
+                let key = text()?;
+                let mut chars = key.chars();
+                floor_type.key = match (chars.next(), chars.next()) {
+                    (Some(c), None) => c,
+                    _ => return Err("key should be a single character".into()),
+                };
+            }
+            "color" => {
+                floor_type.color = Color::try_from(text()?.as_str())
+                    .map_err(|_| format!("unknown color {value}"))?;
+            }
+            "price" => {
+                floor_type.price = i32::try_from(number()?)
+                    .map_err(|_| format!("{value} is too much for a floor to cost"))?;
+            }
+            "rent" => {
+                floor_type.base_rent = usize::try_from(number()?)
+                    .ok()
+                    .filter(|rent| (1..=MAX_RENT).contains(rent))
+                    .ok_or_else(|| format!("rent should be from 1 to {MAX_RENT}"))?;
+            }
+            "capacity" => floor_type.max_occupancy = number()?.max(1) as usize,
+            "stars" => floor_type.stars = number()?.clamp(1, MAX_STARS as i64) as u8,
+            _ => return Err(format!("floor types don't have a {field}").into()),
+        }
+    }
+    Ok(floor_type)
+}
+
+/// The functions mods call when they are loaded to add things to the game.
+fn register_loading_api(engine: &mut Engine, registry: &Rc<RefCell<Registry>>) {
+    let r = Rc::clone(registry);
+    engine.register_fn("floor_type", move |map: Map| -> ScriptResult<()> {
+        loading_script(&r, "floor_type")?;
+        let id = map
+            .get("id")
+            .and_then(|id| id.clone().into_string().ok())
+            .ok_or("floor types need an id")?;
+        let mut registry = r.borrow_mut();
+        match registry.floor_types.iter().position(|t| t.id == id) {
+            Some(index) => {
+                let floor_type = read_floor_type(&map, registry.floor_types[index].clone())?;
+                registry.floor_types[index] = floor_type;
+            }
+            None => {
+                for field in ["name", "key", "price", "rent", "capacity"] {
+                    if !map.contains_key(field) {
+                        return Err(format!("new floor type {id} needs a {field}").into());
+                    }
+                }
+                let template = FloorType {
+                    label: id.clone(),
+                    occupants: "occupants".to_string(),
+                    color: Color::Cyan,
+                    ..FloorType::builtin().remove(0)
+                };
+                let floor_type = read_floor_type(&map, template)?;
+                registry.floor_types.push(floor_type);
+            }
+        }
+        Ok(())
+    });
+
+    let r = Rc::clone(registry);
+    engine.register_fn(
+        "command",
+        move |name: &str, help: &str, function: &str| -> ScriptResult<()> {
+            let script = loading_script(&r, "command")?;
+            r.borrow_mut().commands.push(ModCommand {
+                name: name.to_string(),
+                help: help.to_string(),
+                script,
+                function: function.to_string(),
+            });
+            Ok(())
+        },
+    );
+
+    let r = Rc::clone(registry);
+    engine.register_fn(
+        "event",
+        move |name: &str, chance: FLOAT, function: &str| -> ScriptResult<()> {
+            let script = loading_script(&r, "event")?;
+            r.borrow_mut().events.push(ModEvent {
+                name: name.to_string(),
+                chance,
+                script,
+                function: function.to_string(),
+            });
+            Ok(())
+        },
+    );
+
+    let r = Rc::clone(registry);
+    engine.register_fn("set_rule", move |name: &str, value: FLOAT| -> ScriptResult<()> {
+        set_rule(&r, name, value)
+    });
+    let r = Rc::clone(registry);
+    engine.register_fn("set_rule", move |name: &str, value: INT| -> ScriptResult<()> {
+        set_rule(&r, name, value as FLOAT)
+    });
+}
+
+/// Remembers a rule a mod sets, checking it is one that can be set.
+fn set_rule(registry: &Rc<RefCell<Registry>>, name: &str, value: FLOAT) -> ScriptResult<()> {
+    loading_script(registry, "set_rule")?;
+    Rules::default().set(name, value)?;
+    registry.borrow_mut().rule_changes.push((name.to_string(), value));
+    Ok(())
+}
+
+/// Scripts from the mods directory in the user data directory, and what they
+/// added to the game.
+///
+/// Mods are written in Rhai. When a mod is loaded its top level code runs,
+/// and can call `floor_type`, `command`, `event` and `set_rule`. After that,
+/// the game calls the mod's `on_tick(game)`, `on_build(game, floor_type)`
+/// and `on_inspection(game)` functions if it has them. A mod that fails is
+/// reported in the message area and switched off.
+pub struct Mods {
+    engine: Engine,
+    scripts: Vec<Script>,
+    commands: Vec<ModCommand>,
+    events: Vec<ModEvent>,
+    rule_changes: Vec<(String, f64)>,
+    floor_types: FloorTypes,
+    errors: Vec<String>,
+    /// What scripts printed, shown in the message area.
+    printed: Rc<RefCell<Vec<String>>>,
+}
+
+impl Mods {
+    /// Loads every `.rhai` file in the mods directory of `data_dir`, in name
+    /// order, and makes the floor types they define available.
+    pub fn load(data_dir: &DataDir) -> Self {
+        let mut errors = Vec::new();
+        let mut paths = match data_dir.file(MODS_DIR).and_then(fs::read_dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "rhai"))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                errors.push(format!("{MODS_DIR}: {e}"));
+                Vec::new()
+            }
+        };
+        paths.sort();
+        let sources = paths
+            .into_iter()
+            .map(|path| {
+                let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                (name, fs::read_to_string(&path))
+            })
+            .collect();
+        Mods::from_sources(sources, errors)
+    }
+
+    /// Loads mods from `(file name, source)` pairs, on top of `errors` found
+    /// while reading them.
+    pub fn from_sources(
+        sources: Vec<(String, io::Result<String>)>,
+        mut errors: Vec<String>,
+    ) -> Self {
+        let registry = Rc::new(RefCell::new(Registry {
+            loading: None,
+            floor_types: FloorType::builtin(),
+            commands: Vec::new(),
+            events: Vec::new(),
+            rule_changes: Vec::new(),
+        }));
+        let printed = Rc::new(RefCell::new(Vec::new()));
+
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        let p = Rc::clone(&printed);
+        engine.on_print(move |s| p.borrow_mut().push(s.to_string()));
+        engine.on_debug(|_, _, _| {});
+        register_game_api(&mut engine);
+        register_loading_api(&mut engine, &registry);
+
+        let mut scripts = Vec::new();
+        for (name, source) in sources {
+            let ast = match source {
+                Ok(source) => engine.compile(source).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            let ast = match ast {
+                Ok(ast) => ast,
+                Err(e) => {
+                    errors.push(format!("{name}: {e}"));
+                    continue;
+                }
+            };
+            // Whatever a script set up before it failed is thrown away
+            let before = registry.borrow().clone();
+            registry.borrow_mut().loading = Some(scripts.len());
+            let result = engine.run_ast(&ast);
+            registry.borrow_mut().loading = None;
+            if let Err(e) = result {
+                errors.push(format!("{name}: {e}"));
+                *registry.borrow_mut() = before;
+                continue;
+            }
+            scripts.push(Script { name, ast });
+        }
+        printed.borrow_mut().clear();
+
+        let registry = registry.borrow().clone();
+        Mods {
+            floor_types: FloorTypes::new(registry.floor_types),
+            engine,
+            scripts,
+            commands: registry.commands,
+            events: registry.events,
+            rule_changes: registry.rule_changes,
+            errors,
+            printed,
+        }
+    }
+
+    /// Makes the changes mods asked for to `rules`.
+    pub fn apply_rules(&self, rules: &mut Rules) {
+        for (name, value) in &self.rule_changes {
+            // Every change was checked when the mod set it
+            let _ = rules.set(name, *value);
+        }
+    }
+
+    /// The built in floor types, as mods changed them, and the ones they
+    /// added.
+    pub fn floor_types(&self) -> &FloorTypes {
+        &self.floor_types
+    }
+
+    /// Problems found while loading mods.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn commands(&self) -> &[ModCommand] {
+        &self.commands
+    }
+
+    /// Calls `function` in `script` with the game and `args`, if the script
+    /// has such a function and hasn't failed before in this game.
+    fn call(
+        &self,
+        gs: &mut GameState,
+        index: usize,
+        function: &str,
+        args: Vec<Dynamic>,
+    ) -> io::Result<()> {
+        let script = &self.scripts[index];
+        let arity = args.len() + 1;
+        let defined = script
+            .ast
+            .iter_functions()
+            .any(|f| f.name == function && f.params.len() == arity);
+        if gs.broken_mods.contains(&index) || !defined {
+            return Ok(());
+        }
+
+        let game = GameApi::new(gs);
+        let mut call_args = vec![Dynamic::from(game.clone())];
+        call_args.extend(args);
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut Scope::new(),
+            &script.ast,
+            function,
+            call_args,
+        );
+        if let Err(e) = result {
+            gs.broken_mods.insert(index);
+            return gs.toast(format!("Mod {} failed and was switched off: {e}", script.name));
+        }
+        game.apply(gs)?;
+        let printed = self.printed.take();
+        if let Some(line) = printed.last() {
+            gs.toast(line.clone())?;
+        }
+        Ok(())
+    }
+
+    fn call_all(&self, gs: &mut GameState, function: &str, args: Vec<Dynamic>) -> io::Result<()> {
+        for script in 0..self.scripts.len() {
+            self.call(gs, script, function, args.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Runs mod events and `on_tick` hooks.
+    pub fn on_tick(&self, gs: &mut GameState) -> io::Result<()> {
+        for event in &self.events {
+            if gs.rng.gen_range(0.0..1.0) < event.chance {
+                gs.toast(event.name.clone())?;
+                self.call(gs, event.script, &event.function, vec![])?;
+            }
+        }
+        self.call_all(gs, "on_tick", vec![])
+    }
+
+    pub fn on_build(&self, gs: &mut GameState, style: LayerType) -> io::Result<()> {
+        let id = self.floor_types.get(style).id.clone();
+        self.call_all(gs, "on_build", vec![id.into()])
+    }
+
+    pub fn on_inspection(&self, gs: &mut GameState) -> io::Result<()> {
+        self.call_all(gs, "on_inspection", vec![])
+    }
+
+    /// Runs the mod command called `name`, if there is one, passing it the
+    /// rest of the words typed.
+    pub fn run_command(&self, gs: &mut GameState, name: &str, args: &[&str]) -> Option<io::Result<()>> {
+        let command = self.commands.iter().find(|command| command.name == name)?;
+        let args: rhai::Array = args.iter().map(|arg| Dynamic::from(arg.to_string())).collect();
+        Some(self.call(gs, command.script, &command.function, vec![args.into()]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameBuffer;
+    use crate::game_state::init_game_state;
+    use crate::test_game::TestGame;
+    use crossterm::event::KeyCode;
+
+    /// A new game of `game`'s rules with the mods in `sources` loaded.
+    fn with_mods(game: &TestGame, sources: &[(&str, &str)]) -> GameState {
+        let sources = sources
+            .iter()
+            .map(|(name, source)| (name.to_string(), Ok(source.to_string())))
+            .collect();
+        let mods = Rc::new(Mods::from_sources(sources, vec![]));
+        assert_eq!(mods.errors(), [] as [String; 0]);
+        init_game_state(FrameBuffer::new(), mods, game.gs.rules.clone(), game.gs.data_dir.clone())
+    }
+
+    #[test]
+    fn games_with_different_mods_have_their_own_floor_types() {
+        let mut game = TestGame::new();
+        let gym = r#"floor_type(#{ id: "gym", name: "GYM", key: "y", price: 9000, rent: 15,
+                                   capacity: 12 });"#;
+        let cheap_food = r#"floor_type(#{ id: "food", price: 1 });"#;
+        let gyms = with_mods(&game, &[("gym.rhai", gym)]);
+        let cheap = with_mods(&game, &[("food.rhai", cheap_food)]);
+
+        assert!(gyms.mods.floor_types().find("gym").is_some());
+        assert!(cheap.mods.floor_types().find("gym").is_none());
+        assert_eq!(cheap.mods.floor_types().get(LayerType::Food).price, 1);
+        assert_ne!(gyms.mods.floor_types().get(LayerType::Food).price, 1);
+        assert!(game.gs.mods.floor_types().find("gym").is_none());
+
+        game.gs = gyms;
+        game.gs.draw_command_pool().unwrap();
+        game.press(KeyCode::Char('b')).press(KeyCode::Char('y'));
+        assert_eq!(game.gs.layers.len(), 1);
+        assert_eq!(game.gs.layers[0].floor_type().name, "GYM");
+    }
+
+    #[test]
+    fn floor_types_charge_rent_within_bounds() {
+        for rent in ["0", "10001", "9223372036854775807"] {
+            let source = format!(r#"floor_type(#{{ id: "food", rent: {rent} }});"#);
+            let mods = Mods::from_sources(vec![("rent.rhai".into(), Ok(source))], vec![]);
+            assert_eq!(mods.errors().len(), 1, "rent {rent} was accepted");
+            let error = &mods.errors()[0];
+            assert!(error.contains("rent should be from 1 to 10000"), "{error}");
+        }
+        let source = r#"floor_type(#{ id: "food", rent: 10000 });"#.to_string();
+        let mods = Mods::from_sources(vec![("rent.rhai".into(), Ok(source))], vec![]);
+        assert_eq!(mods.floor_types().get(LayerType::Food).base_rent, MAX_RENT);
+    }
+
+    #[test]
+    fn mods_cannot_take_the_funds_past_what_they_hold() {
+        let mut game = TestGame::new();
+        let rich = r#"command("rich", "rich: get very rich", "rich");
+                      fn rich(game, args) { game.earn(2147483647); game.earn(2147483647); }"#;
+        game.gs = with_mods(&game, &[("rich.rhai", rich)]);
+        let funds = game.gs.funds;
+        game.type_keys(":rich").press(KeyCode::Enter);
+        assert_eq!(game.gs.funds, funds, "a mod that failed still changed the funds");
+        let (msg, _) = game.gs.toast.clone().expect("the mod failing wasn't reported");
+        assert!(msg.starts_with("Mod rich.rhai failed"), "{msg}");
+    }
+
+    #[test]
+    fn a_mod_failing_in_one_game_still_runs_in_others() {
+        let game = TestGame::new();
+        let moody = r#"command("moody", "moody: fail when broke", "moody");
+                       fn moody(game, args) {
+                           if game.funds < 0 { throw "broke"; }
+                           game.earn(1);
+                       }"#;
+        let mut broke = with_mods(&game, &[("moody.rhai", moody)]);
+        let mods = Rc::clone(&broke.mods);
+        let (rules, data_dir) = (game.gs.rules.clone(), game.gs.data_dir.clone());
+        let mut rich = init_game_state(FrameBuffer::new(), mods, rules, data_dir);
+        broke.funds = -1;
+
+        crate::console::execute(&mut broke, "moody").unwrap();
+        assert!(broke.toast.clone().unwrap().0.starts_with("Mod moody.rhai failed"));
+        broke.funds = 100;
+        crate::console::execute(&mut broke, "moody").unwrap();
+        assert_eq!(broke.funds, 100, "a mod that failed was run again");
+
+        let funds = rich.funds;
+        crate::console::execute(&mut rich, "moody").unwrap();
+        assert_eq!(rich.funds, funds + 1, "a mod that failed in another game was switched off");
+    }
+}
@@ -0,0 +1,705 @@
+use crate::frame::FrameBuffer;
+use crate::mods::Mods;
+use crate::rules::Rules;
+use crate::storage::DataDir;
+use crate::versus::{self, Versus};
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, execute, queue, terminal};
+use futures::prelude::*;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+/// Bumped whenever a message changes, so a host and client that can't
+/// understand each other say so instead of misbehaving.
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const DEFAULT_PORT: u16 = 7878;
+/// The player who joins the host. The host plays the other one.
+const REMOTE_PLAYER: usize = 1;
+/// Longest a frame can be, well over what redrawing every cell takes.
+const MAX_FRAME_LEN: usize = 1 << 20;
+/// Longest a message's line can be, newline included.
+const MAX_LINE_LEN: u64 = 4096;
+/// Longest a client waits to connect to the host and to be let in.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest the host waits for a new connection to say hello before hanging
+/// up on it.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Longest the host waits for a client to take a message, so a stalled
+/// client can't stall the game.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// Times a client tries to get back into a game before giving up.
+const RECONNECT_ATTEMPTS: u32 = 30;
+
+/// What hosts and clients say to each other.
+///
+/// Each message is a line of text starting with its name, except that a
+/// frame's line gives its length and the frame itself follows, so the
+/// protocol can be followed with `nc`:
+///
+/// ```text
+/// hello 1 0
+/// welcome 2 8046122335731245017
+/// action build_3
+/// frame 1523
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Message {
+    /// A client asking to play, with the token it was given when it first
+    /// joined, or 0 if it hasn't joined before.
+    Hello { version: u32, token: u64 },
+    /// The host letting a client in as `player`, counting from 1. The token
+    /// gets it back in if the connection drops.
+    Welcome { player: usize, token: u64 },
+    /// The host turning a client away, and why.
+    Refused(String),
+    /// A client doing something, named as in the key bindings.
+    Action(String),
+    /// What changed on the client's screen, to write to its terminal as is.
+    Frame(Vec<u8>),
+    /// The host ending the game, and why.
+    Bye(String),
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let line = match self {
+            Message::Hello { version, token } => format!("hello {version} {token}"),
+            Message::Welcome { player, token } => format!("welcome {player} {token}"),
+            Message::Refused(reason) => format!("refused {}", one_line(reason)),
+            Message::Action(action) => format!("action {}", one_line(action)),
+            Message::Frame(bytes) => {
+                let mut encoded = format!("frame {}\n", bytes.len()).into_bytes();
+                encoded.extend_from_slice(bytes);
+                return encoded;
+            }
+            Message::Bye(reason) => format!("bye {}", one_line(reason)),
+        };
+        (line + "\n").into_bytes()
+    }
+
+    /// Reads the next message, or `None` once the other end hangs up.
+    pub async fn read(reader: &mut (impl AsyncBufRead + Unpin)) -> io::Result<Option<Message>> {
+        let mut line = String::new();
+        let read = (&mut *reader).take(MAX_LINE_LEN).read_line(&mut line).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read as u64 == MAX_LINE_LEN && !line.ends_with('\n') {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "message too long"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        let (name, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let invalid =
+            || io::Error::new(io::ErrorKind::InvalidData, format!("bad message `{line}`"));
+        fn parse<T: FromStr>(text: Option<&str>) -> Option<T> {
+            text?.parse().ok()
+        }
+        let mut args = rest.split(' ');
+        let message = match name {
+            "hello" => Message::Hello {
+                version: parse(args.next()).ok_or_else(invalid)?,
+                token: parse(args.next()).ok_or_else(invalid)?,
+            },
+            "welcome" => Message::Welcome {
+                player: parse(args.next()).ok_or_else(invalid)?,
+                token: parse(args.next()).ok_or_else(invalid)?,
+            },
+            "refused" => Message::Refused(rest.to_string()),
+            "action" => Message::Action(rest.to_string()),
+            "frame" => {
+                let len: usize = parse(Some(rest)).ok_or_else(invalid)?;
+                if len > MAX_FRAME_LEN {
+                    return Err(invalid());
+                }
+                let mut bytes = vec![0; len];
+                reader.read_exact(&mut bytes).await?;
+                Message::Frame(bytes)
+            }
+            "bye" => Message::Bye(rest.to_string()),
+            _ => return Err(invalid()),
+        };
+        Ok(Some(message))
+    }
+}
+
+/// `text` with anything that would end a message early replaced.
+fn one_line(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+}
+
+/// Passes each message read from `reader` to `forward`, tagged by `tag`,
+/// and then `tag(None)` once the connection closes or stops making sense.
+///
+/// Reading a message can take several reads from the socket, so it happens
+/// in a task of its own rather than in a `select!` that could drop it half
+/// way through.
+fn spawn_reader<T: Send + 'static>(
+    reader: OwnedReadHalf,
+    forward: UnboundedSender<T>,
+    tag: impl Fn(Option<Message>) -> T + Send + 'static,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        while let Ok(Some(message)) = Message::read(&mut reader).await {
+            if forward.send(tag(Some(message))).is_err() {
+                return;
+            }
+        }
+        let _ = forward.send(tag(None));
+    })
+}
+
+/// Something that happened on one of the host's connections, which are
+/// numbered in the order they were accepted.
+#[derive(Debug)]
+pub enum HostEvent {
+    Message(u64, Message),
+    Closed(u64),
+    /// The connection has had long enough to say hello.
+    HelloTimedOut(u64),
+}
+
+/// One of the host's open connections, closed when dropped.
+struct Connection {
+    writer: OwnedWriteHalf,
+    /// The task passing on what the client says.
+    reader: JoinHandle<()>,
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// A versus game whose second player is on another terminal.
+///
+/// The host runs both towers. The client sends the actions its player's
+/// keys are bound to, and gets back what changed on the screen after every
+/// event, so the two can never disagree about the game.
+pub struct Host {
+    pub versus: Versus,
+    listener: TcpListener,
+    events: UnboundedReceiver<HostEvent>,
+    event_sender: UnboundedSender<HostEvent>,
+    /// Each open connection, to send messages on.
+    connections: HashMap<u64, Connection>,
+    next_connection: u64,
+    /// The connection the remote player is playing on.
+    remote: Option<u64>,
+    /// What lets the remote player back in, once someone has joined.
+    token: Option<u64>,
+    /// What the remote player's terminal shows.
+    remote_frame: FrameBuffer,
+}
+
+impl Host {
+    pub fn new(listener: TcpListener, mut versus: Versus) -> io::Result<Self> {
+        let address = listener.local_addr()?;
+        versus.remote_player = Some(REMOTE_PLAYER);
+        versus.notice = Some(format!(
+            "WAITING FOR PLAYER {} TO JOIN WITH --join {address}.",
+            REMOTE_PLAYER + 1
+        ));
+        versus.draw()?;
+        let (event_sender, events) = mpsc::unbounded_channel();
+        Ok(Host {
+            versus,
+            listener,
+            events,
+            event_sender,
+            connections: HashMap::new(),
+            next_connection: 0,
+            remote: None,
+            token: None,
+            remote_frame: FrameBuffer::new(),
+        })
+    }
+
+    fn accept(&mut self, stream: TcpStream) {
+        let _ = stream.set_nodelay(true);
+        let connection = self.next_connection;
+        self.next_connection += 1;
+        let (reader, writer) = stream.into_split();
+        let reader = spawn_reader(reader, self.event_sender.clone(), move |message| {
+            match message {
+                Some(message) => HostEvent::Message(connection, message),
+                None => HostEvent::Closed(connection),
+            }
+        });
+        self.connections.insert(connection, Connection { writer, reader });
+        let events = self.event_sender.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(HELLO_TIMEOUT).await;
+            let _ = events.send(HostEvent::HelloTimedOut(connection));
+        });
+    }
+
+    async fn handle(&mut self, event: HostEvent) -> io::Result<()> {
+        match event {
+            HostEvent::Message(connection, Message::Hello { version, token }) => {
+                self.greet(connection, version, token).await
+            }
+            HostEvent::Message(connection, Message::Action(action))
+                if self.remote == Some(connection) =>
+            {
+                self.versus.perform(REMOTE_PLAYER, &action)
+            }
+            // Clients have nothing else to say, and nothing to say before
+            // they are let in
+            HostEvent::Message(..) => Ok(()),
+            HostEvent::Closed(connection) => {
+                self.connections.remove(&connection);
+                if self.remote == Some(connection) {
+                    self.lost_remote()?;
+                }
+                Ok(())
+            }
+            HostEvent::HelloTimedOut(connection) => {
+                if self.remote != Some(connection) {
+                    self.connections.remove(&connection);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Lets a client in if it speaks our protocol and the remote player's
+    /// seat is free for it.
+    async fn greet(&mut self, connection: u64, version: u32, token: u64) -> io::Result<()> {
+        let refusal = if version != PROTOCOL_VERSION {
+            Some(format!(
+                "The host speaks protocol version {PROTOCOL_VERSION}, not {version}."
+            ))
+        } else if self.remote.is_some() || self.token.is_some_and(|t| t != token) {
+            Some("This game already has two players.".to_string())
+        } else {
+            None
+        };
+        if let Some(reason) = refusal {
+            self.send(connection, &Message::Refused(reason)).await;
+            self.connections.remove(&connection);
+            return Ok(());
+        }
+
+        let token = *self.token.get_or_insert_with(|| rand::random::<u64>().max(1));
+        let welcome = Message::Welcome {
+            player: REMOTE_PLAYER + 1,
+            token,
+        };
+        if !self.send(connection, &welcome).await {
+            return Ok(());
+        }
+        self.remote = Some(connection);
+        // Whatever the client shows now, it needs the whole screen
+        self.remote_frame.invalidate();
+        self.versus.notice = None;
+        self.versus.draw()
+    }
+
+    fn lost_remote(&mut self) -> io::Result<()> {
+        self.remote = None;
+        self.versus.notice = Some(format!(
+            "PLAYER {} LOST THEIR CONNECTION. WAITING FOR THEM TO REJOIN.",
+            REMOTE_PLAYER + 1
+        ));
+        self.versus.draw()
+    }
+
+    /// Sends `message` on `connection`, dropping the connection if that
+    /// fails or takes too long. Returns whether it was sent.
+    async fn send(&mut self, connection: u64, message: &Message) -> bool {
+        let Some(Connection { writer, .. }) = self.connections.get_mut(&connection) else {
+            return false;
+        };
+        let bytes = message.encode();
+        if let Ok(Ok(())) = timeout(WRITE_TIMEOUT, writer.write_all(&bytes)).await {
+            return true;
+        }
+        self.connections.remove(&connection);
+        false
+    }
+
+    /// Sends the remote player whatever changed on their screen.
+    pub async fn send_frame(&mut self) -> io::Result<()> {
+        let Some(connection) = self.remote else {
+            return Ok(());
+        };
+        let size = (WINDOW_WIDTH, WINDOW_HEIGHT);
+        self.remote_frame.copy_from(&self.versus.frame, (0, 0), size, (0, 0));
+        let mut bytes = Vec::new();
+        self.remote_frame.render(&mut bytes)?;
+        if !bytes.is_empty() && !self.send(connection, &Message::Frame(bytes)).await {
+            self.lost_remote()?;
+        }
+        Ok(())
+    }
+
+    /// Tells every client the game is over.
+    pub async fn close(&mut self, reason: &str) {
+        let connections: Vec<u64> = self.connections.keys().copied().collect();
+        for connection in connections {
+            self.send(connection, &Message::Bye(reason.to_string())).await;
+        }
+        self.connections.clear();
+    }
+}
+
+/// Driving a host one connection or message at a time, for tests.
+#[cfg(test)]
+impl Host {
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Waits for a connection or a message on one, deals with it and
+    /// updates the remote player's screen.
+    pub async fn step(&mut self) -> io::Result<()> {
+        tokio::select! {
+            accepted = self.listener.accept() => self.accept(accepted?.0),
+            Some(event) = self.events.recv() => self.handle(event).await?,
+        }
+        self.send_frame().await
+    }
+}
+
+/// A connection to a host, once it has let us in.
+pub struct Client {
+    writer: OwnedWriteHalf,
+    messages: UnboundedReceiver<Option<Message>>,
+    pub player: usize,
+    pub token: u64,
+}
+
+impl Client {
+    /// Joins the game at `address`, or rejoins it with the `token` the host
+    /// gave us last time. A host that turns us away gives a
+    /// `PermissionDenied` error saying why, and one that doesn't answer in
+    /// time a `TimedOut` error.
+    pub async fn connect(address: impl ToSocketAddrs, token: Option<u64>) -> io::Result<Self> {
+        let timed_out = |_| io::Error::new(io::ErrorKind::TimedOut, "The host didn't answer.");
+        let connecting = timeout(CONNECT_TIMEOUT, TcpStream::connect(address));
+        let stream = connecting.await.map_err(timed_out)??;
+        let _ = stream.set_nodelay(true);
+        let (reader, writer) = stream.into_split();
+        let (sender, messages) = mpsc::unbounded_channel();
+        spawn_reader(reader, sender, |message| message);
+        let mut client = Client {
+            writer,
+            messages,
+            player: 0,
+            token: 0,
+        };
+        let hello = Message::Hello {
+            version: PROTOCOL_VERSION,
+            token: token.unwrap_or(0),
+        };
+        client.send(&hello).await?;
+        match timeout(CONNECT_TIMEOUT, client.recv()).await.map_err(timed_out)? {
+            Some(Message::Welcome { player, token }) => {
+                client.player = player;
+                client.token = token;
+                Ok(client)
+            }
+            Some(Message::Refused(reason)) => {
+                Err(io::Error::new(io::ErrorKind::PermissionDenied, reason))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "The host didn't answer like a text_game host.",
+            )),
+        }
+    }
+
+    pub async fn send(&mut self, message: &Message) -> io::Result<()> {
+        self.writer.write_all(&message.encode()).await
+    }
+
+    /// The next message from the host, or `None` once the connection is
+    /// gone.
+    pub async fn recv(&mut self) -> Option<Message> {
+        self.messages.recv().await.flatten()
+    }
+}
+
+/// Hosts a versus game in the terminal on `address` until the host quits.
+pub async fn host(
+    mut out: impl Write,
+    mods: Rc<Mods>,
+    address: &str,
+    collections: u32,
+) -> io::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    let versus = Versus::new(mods, Rules::default(), collections, DataDir::from_env());
+    let mut host = Host::new(listener, versus)?;
+    let mut event_stream = event::EventStream::new();
+    let mut heartbeat = tokio::time::interval(Duration::from_secs(1));
+    let fps = host.versus.players[0].animation_fps;
+    let mut animation = tokio::time::interval(Duration::from_secs(1) / fps.max(1));
+    animation.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    while host.versus.running {
+        tokio::select! {
+            event = event_stream.next() => match event {
+                Some(Ok(Event::Key(ke))) => host.versus.handle_key(ke)?,
+                Some(Ok(Event::Resize(..))) => host.versus.frame.invalidate(),
+                None => break,
+                Some(Err(e)) => return Err(e),
+                _ => {}
+            },
+            _ = heartbeat.tick() => host.versus.tick()?,
+            _ = animation.tick(), if fps > 0 => host.versus.animate()?,
+            accepted = host.listener.accept() => host.accept(accepted?.0),
+            Some(event) = host.events.recv() => host.handle(event).await?,
+        }
+        queue!(out, terminal::BeginSynchronizedUpdate)?;
+        host.versus.frame.render(&mut out)?;
+        execute!(out, terminal::EndSynchronizedUpdate)?;
+        host.send_frame().await?;
+    }
+    host.close("The host ended the game.").await;
+    Ok(())
+}
+
+/// Plays in the game hosted at `address` until the player quits, rejoining
+/// if the connection drops.
+pub async fn join(mut out: impl Write, address: &str) -> io::Result<()> {
+    let address = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{DEFAULT_PORT}")
+    };
+    let client = Client::connect(&address, None).await?;
+    let controls = versus::remote_controls(client.player - 1);
+    let token = client.token;
+    let mut client = Some(client);
+    // Whether to keep trying to rejoin, and how many times we have
+    let mut reconnecting = false;
+    let mut attempts = 0;
+    let mut event_stream = event::EventStream::new();
+    let mut retry = tokio::time::interval(RECONNECT_INTERVAL);
+
+    loop {
+        tokio::select! {
+            event = event_stream.next() => match event {
+                Some(Ok(Event::Key(ke))) => {
+                    let quit = ke.code == KeyCode::Esc
+                        || (ke.code == KeyCode::Char('c') && ke.modifiers == KeyModifiers::CONTROL);
+                    if quit {
+                        break;
+                    }
+                    let pool = controls.cur();
+                    let (Some(action), Some(command), Some(connection)) = (
+                        pool.get_action(ke.code, ke.modifiers),
+                        pool.get_command(ke.code, ke.modifiers),
+                        &mut client,
+                    ) else {
+                        continue;
+                    };
+                    let send = ke.kind == KeyEventKind::Press
+                        || (ke.kind == KeyEventKind::Repeat && command.is_triggered_on_repeat());
+                    // A failed send shows up as the connection closing
+                    if send {
+                        let _ = connection.send(&Message::Action(action.to_string())).await;
+                    }
+                }
+                None => break,
+                Some(Err(e)) => return Err(e),
+                _ => {}
+            },
+            message = async { client.as_mut().unwrap().recv().await }, if client.is_some() => {
+                match message {
+                    Some(Message::Frame(bytes)) => {
+                        out.write_all(&bytes)?;
+                        out.flush()?;
+                    }
+                    Some(Message::Bye(reason)) => {
+                        client = None;
+                        show_notice(&mut out, &format!("{reason}  Esc: quit"))?;
+                    }
+                    Some(_) => {}
+                    None => {
+                        client = None;
+                        reconnecting = true;
+                        attempts = 0;
+                        retry.reset();
+                        show_notice(&mut out, "LOST THE CONNECTION TO THE HOST. REJOINING...")?;
+                    }
+                }
+            }
+            _ = retry.tick(), if reconnecting => {
+                attempts += 1;
+                match Client::connect(&address, Some(token)).await {
+                    Ok(rejoined) => {
+                        client = Some(rejoined);
+                        reconnecting = false;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                        reconnecting = false;
+                        show_notice(&mut out, &format!("{e}  Esc: quit"))?;
+                    }
+                    Err(_) if attempts >= RECONNECT_ATTEMPTS => {
+                        reconnecting = false;
+                        show_notice(&mut out, "COULDN'T REJOIN THE GAME.  Esc: quit")?;
+                    }
+                    Err(_) => {}
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Shows `text` on the status line of the client's screen.
+fn show_notice(mut out: impl Write, text: &str) -> io::Result<()> {
+    let width = WINDOW_WIDTH as usize;
+    execute!(
+        out,
+        cursor::MoveTo(0, TOWER_WINDOW_HEIGHT),
+        style::SetColors(Colors::new(Color::Yellow, Color::Black)),
+        style::Print(format!("{text:^width$}")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_game::{press_versus, versus, TestGame};
+
+    #[tokio::test]
+    async fn messages_survive_the_wire() {
+        let messages = [
+            Message::Hello { version: PROTOCOL_VERSION, token: 0 },
+            Message::Welcome { player: 2, token: u64::MAX },
+            Message::Refused("Go away.".to_string()),
+            Message::Action("build_3".to_string()),
+            Message::Frame(b"\x1b[1;1Hline one\nline two".to_vec()),
+            Message::Frame(Vec::new()),
+            Message::Bye("The host ended the game.".to_string()),
+        ];
+        let wire: Vec<u8> = messages.iter().flat_map(Message::encode).collect();
+        let mut reader = BufReader::new(wire.as_slice());
+        for message in messages {
+            assert_eq!(Message::read(&mut reader).await.unwrap(), Some(message));
+        }
+        assert_eq!(Message::read(&mut reader).await.unwrap(), None);
+
+        let mut garbage = BufReader::new(&b"frame lots\n"[..]);
+        assert!(Message::read(&mut garbage).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn numbers_that_dont_fit_and_endless_lines_are_refused() {
+        for wire in ["hello 4294967297 0\n", "welcome 2 18446744073709551616\n", "frame -1\n"] {
+            let mut reader = BufReader::new(wire.as_bytes());
+            assert!(Message::read(&mut reader).await.is_err(), "{wire}");
+        }
+
+        let long = format!("action {}\n", "x".repeat(MAX_LINE_LEN as usize));
+        let mut reader = BufReader::new(long.as_bytes());
+        assert!(Message::read(&mut reader).await.is_err());
+        let longest = format!("action {}\n", "x".repeat(MAX_LINE_LEN as usize - 8));
+        let mut reader = BufReader::new(longest.as_bytes());
+        assert!(Message::read(&mut reader).await.unwrap().is_some());
+    }
+
+    /// A networked versus game hosted on localhost, waiting for player 2.
+    async fn host(game: &TestGame) -> Host {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        Host::new(listener, versus(game, 1)).unwrap()
+    }
+
+    /// Joins `host` as player 2, letting the host accept and answer.
+    async fn join(host: &mut Host, token: Option<u64>) -> io::Result<Client> {
+        let address = host.local_addr().unwrap();
+        let (client, _) = tokio::join!(Client::connect(address, token), async {
+            host.step().await.unwrap();
+            host.step().await.unwrap();
+        });
+        client
+    }
+
+    /// Draws every frame the host has sent `client` so far onto `screen`.
+    async fn receive_frames(client: &mut Client, screen: &mut FrameBuffer) {
+        use std::io::Write;
+        let wait = Duration::from_millis(200);
+        while let Ok(Some(message)) = tokio::time::timeout(wait, client.recv()).await {
+            if let Message::Frame(bytes) = message {
+                screen.write_all(&bytes).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn remote_player_builds_on_the_host() {
+        let game = TestGame::new();
+        let mut host = host(&game).await;
+        assert!(host.versus.frame.find("WAITING FOR PLAYER 2").is_some());
+
+        let mut client = join(&mut host, None).await.unwrap();
+        assert_eq!(client.player, 2);
+        client.send(&Message::Action("build_3".to_string())).await.unwrap();
+        host.step().await.unwrap();
+        assert_eq!(host.versus.players[0].layers.len(), 0);
+        assert_eq!(host.versus.players[1].layers.len(), 1);
+
+        // Player 2's keys only work on their own terminal
+        press_versus(&mut host.versus, KeyCode::Char('9'));
+        assert_eq!(host.versus.players[1].layers.len(), 1);
+
+        let mut screen = FrameBuffer::new();
+        receive_frames(&mut client, &mut screen).await;
+        assert_eq!(screen.text(), host.versus.frame.text());
+    }
+
+    #[tokio::test]
+    async fn host_refuses_other_protocol_versions() {
+        let game = TestGame::new();
+        let mut host = host(&game).await;
+        let mut stream = TcpStream::connect(host.local_addr().unwrap()).await.unwrap();
+        stream.write_all(b"hello 99 0\n").await.unwrap();
+        host.step().await.unwrap();
+        host.step().await.unwrap();
+
+        let mut reader = BufReader::new(stream);
+        let reply = Message::read(&mut reader).await.unwrap();
+        let reason = "The host speaks protocol version 1, not 99.".to_string();
+        assert_eq!(reply, Some(Message::Refused(reason)));
+    }
+
+    #[tokio::test]
+    async fn remote_player_rejoins_after_losing_connection() {
+        let game = TestGame::new();
+        let mut host = host(&game).await;
+        let client = join(&mut host, None).await.unwrap();
+        let token = client.token;
+        host.versus.tick().unwrap();
+        assert_eq!(host.versus.players[0].tick, 1);
+
+        drop(client);
+        host.step().await.unwrap();
+        assert!(host.versus.frame.find("PLAYER 2 LOST THEIR CONNECTION").is_some());
+        host.versus.tick().unwrap();
+        assert_eq!(host.versus.players[0].tick, 1, "the clock should stop");
+
+        let stranger = join(&mut host, None).await;
+        assert_eq!(stranger.err().map(|e| e.kind()), Some(io::ErrorKind::PermissionDenied));
+
+        join(&mut host, Some(token)).await.unwrap();
+        assert_eq!(host.versus.notice, None);
+        host.versus.tick().unwrap();
+        assert_eq!(host.versus.players[0].tick, 2);
+    }
+}
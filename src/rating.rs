@@ -0,0 +1,157 @@
+use crate::layer::{FloorTypes, Layer};
+use std::collections::HashSet;
+
+pub const MAX_STARS: u8 = 5;
+/// Stars the tower needs before floors can be renovated.
+pub const RENOVATE_STARS: u8 = 4;
+
+/// What a tower needs for a star.
+struct Requirement {
+    population: usize,
+    /// Different types of floor built.
+    variety: usize,
+    satisfaction: f64,
+    funds: i32,
+}
+
+/// Requirements for each star after the first, which every tower has.
+const REQUIREMENTS: [Requirement; MAX_STARS as usize - 1] = [
+    Requirement { population: 40, variety: 2, satisfaction: 0.5, funds: 0 },
+    Requirement { population: 100, variety: 3, satisfaction: 0.7, funds: 10_000 },
+    Requirement { population: 250, variety: 4, satisfaction: 0.8, funds: 50_000 },
+    Requirement { population: 500, variety: 5, satisfaction: 0.9, funds: 150_000 },
+];
+
+/// The things about a tower its rating is based on.
+pub struct TowerStats {
+    pub population: usize,
+    pub variety: usize,
+    /// How happy the occupants are with how clean and working their floors
+    /// are and what they're paying, from 0 to 1.
+    pub satisfaction: f64,
+    /// Funds less any loan.
+    pub funds: i32,
+}
+
+impl TowerStats {
+    pub fn of(layers: &[Layer], funds: i32, loan: i32) -> Self {
+        let population: usize = layers.iter().map(|layer| layer.occupancy).sum();
+        let variety = layers.iter().map(|layer| layer.style).collect::<HashSet<_>>().len();
+        // Everyone's satisfaction counts the same, so busy floors count most
+        let satisfaction = if population == 0 {
+            0.0
+        } else {
+            let total: f64 = layers
+                .iter()
+                .map(|layer| {
+                    let fairness = 1.0 / layer.rent_factor().max(1.0);
+                    layer.appeal() * fairness * layer.occupancy as f64
+                })
+                .sum();
+            total / population as f64
+        };
+        TowerStats {
+            population,
+            variety,
+            satisfaction,
+            funds: funds - loan,
+        }
+    }
+
+    fn meets(&self, requirement: &Requirement) -> bool {
+        self.population >= requirement.population
+            && self.variety >= requirement.variety
+            && self.satisfaction >= requirement.satisfaction
+            && self.funds >= requirement.funds
+    }
+
+    /// Stars the tower deserves as it is now.
+    pub fn stars(&self) -> u8 {
+        1 + REQUIREMENTS.iter().take_while(|requirement| self.meets(requirement)).count() as u8
+    }
+
+    /// What the tower still needs for the star after `stars`.
+    pub fn next_star(&self, stars: u8) -> String {
+        let Some(requirement) = REQUIREMENTS.get(stars as usize - 1) else {
+            return "The tower has every star there is.".to_string();
+        };
+        format!(
+            "Next star needs {} people (have {}), {} floor types ({}), {:.0}% satisfaction \
+             ({:.0}%) and ${} ({})",
+            requirement.population,
+            self.population,
+            requirement.variety,
+            self.variety,
+            requirement.satisfaction * 100.0,
+            self.satisfaction * 100.0,
+            requirement.funds,
+            self.funds
+        )
+    }
+}
+
+/// `stars` out of five, as stars.
+pub fn stars_text(stars: u8) -> String {
+    (1..=MAX_STARS).map(|star| if star <= stars { '★' } else { '☆' }).collect()
+}
+
+/// What reaching `stars` makes available, out of `floor_types` and the
+/// rest of the game.
+pub fn unlocks(stars: u8, floor_types: &FloorTypes) -> Vec<String> {
+    let mut unlocks: Vec<String> = floor_types
+        .all()
+        .map(|style| floor_types.get(style))
+        .filter(|floor_type| floor_type.stars == stars)
+        .map(|floor_type| floor_type.label.clone())
+        .collect();
+    if stars == RENOVATE_STARS {
+        unlocks.push("Renovation".to_string());
+    }
+    unlocks
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::layer::LayerType;
+    use crate::test_game::TestGame;
+
+    #[test]
+    fn stars_unlock_new_floor_types() {
+        let mut game = TestGame::new();
+        game.gs.funds = 1_000_000;
+        game.build(LayerType::Office);
+        assert!(game.gs.layers.is_empty(), "offices were built without a second star");
+        assert!(game.gs.frame.find("Offices need a 2-star tower").is_some());
+
+        game.build(LayerType::Food).build(LayerType::Apartment);
+        for layer in &mut game.gs.layers {
+            layer.occupancy = layer.max_occupancy;
+        }
+        game.tick();
+        assert_eq!(game.gs.stars, 2);
+        assert!(game.gs.frame.find("YOUR TOWER NOW HAS 2 STARS! Unlocked: Offices").is_some());
+        assert!(game.gs.frame.find("★★☆☆☆").is_some());
+
+        game.build(LayerType::Office);
+        assert_eq!(game.gs.layers.last().unwrap().style, LayerType::Office);
+    }
+
+    #[test]
+    fn stars_gained_at_once_unlock_everything_they_bring() {
+        let mut game = TestGame::new();
+        game.gs.funds = 1_000_000;
+        game.build(LayerType::Food).build(LayerType::Food).build(LayerType::Apartment);
+        game.build(LayerType::Apartment).build(LayerType::Retail);
+        for layer in &mut game.gs.layers {
+            layer.occupancy = layer.max_occupancy;
+        }
+        game.tick();
+        assert_eq!(game.gs.stars, 3);
+
+        let floor_types = game.gs.mods.floor_types();
+        let third = floor_types.all().map(|style| floor_types.get(style)).find(|t| t.stars == 3);
+        let third = &third.unwrap().label;
+        let expected = format!("YOUR TOWER NOW HAS 3 STARS! Unlocked: Offices, {third}");
+        assert!(game.gs.frame.find(&expected).is_some(), "{}", game.gs.frame.text());
+    }
+}
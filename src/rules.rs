@@ -0,0 +1,404 @@
+use crate::storage::DataDir;
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, queue, terminal};
+use std::fs;
+use std::io::{self, Write};
+
+/// Directory in the user data directory custom rule sets are read from.
+const RULES_DIR: &str = "rules";
+const RULES_EXTENSION: &str = "rules";
+
+/// `percent` percent of `amount`, as much as fits an i32.
+pub fn percent_of(amount: i32, percent: i32) -> i32 {
+    let result = i64::from(amount) * i64::from(percent) / 100;
+    i32::try_from(result).unwrap_or(if result < 0 { i32::MIN } else { i32::MAX })
+}
+
+/// Numbers that decide how the game plays. They come from one of the preset
+/// difficulties or a custom rule file, chosen when a game starts, and mods
+/// can change them after that.
+#[derive(Clone, Debug)]
+pub struct Rules {
+    /// What the rule set is called, shown when choosing one.
+    pub name: String,
+    pub starting_funds: i32,
+    /// Ticks between debt collector inspections.
+    pub inspection_interval: u32,
+    /// Whether the game ends when the funds are negative at an inspection.
+    pub debt_game_over: bool,
+    /// What floors cost to build, as a percentage of their usual price.
+    pub price_percent: i32,
+    /// Chance per tick of someone moving in to an empty tower is
+    /// `1 - move_in_base`, rising towards 1 as the tower fills up at a rate
+    /// set by `move_in_decay`.
+    pub move_in_base: f64,
+    pub move_in_decay: f64,
+    /// Chance per tick of someone moving out of an empty tower, falling
+    /// towards 0 as the tower fills up at a rate set by `move_out_decay`.
+    pub move_out_base: f64,
+    pub move_out_decay: f64,
+    /// Property tax charged at each inspection, as a percentage of the
+    /// tower's assessed value.
+    pub property_tax_percent: i32,
+    /// Income tax charged at each inspection, as a percentage of the rent
+    /// taken since the last one.
+    pub income_tax_percent: i32,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            name: "Normal".to_string(),
+            starting_funds: 10_000,
+            inspection_interval: 50,
+            debt_game_over: true,
+            price_percent: 100,
+            move_in_base: 0.4,
+            move_in_decay: 0.01,
+            move_out_base: 0.3,
+            move_out_decay: 0.005,
+            property_tax_percent: 2,
+            income_tax_percent: 10,
+        }
+    }
+}
+
+impl Rules {
+    /// The difficulties every game can be played at, easiest first.
+    pub fn presets() -> Vec<Rules> {
+        vec![
+            Rules {
+                name: "Easy".to_string(),
+                starting_funds: 20_000,
+                inspection_interval: 60,
+                price_percent: 80,
+                move_in_base: 0.3,
+                move_out_base: 0.2,
+                property_tax_percent: 1,
+                income_tax_percent: 5,
+                ..Rules::default()
+            },
+            Rules::default(),
+            Rules {
+                name: "Hard".to_string(),
+                starting_funds: 6_000,
+                inspection_interval: 40,
+                price_percent: 125,
+                move_in_base: 0.5,
+                move_out_base: 0.4,
+                property_tax_percent: 3,
+                income_tax_percent: 15,
+                ..Rules::default()
+            },
+            Rules {
+                name: "Sandbox".to_string(),
+                starting_funds: 1_000_000,
+                debt_game_over: false,
+                property_tax_percent: 0,
+                income_tax_percent: 0,
+                ..Rules::default()
+            },
+        ]
+    }
+
+    /// What `price` comes to under these rules.
+    pub fn price(&self, price: i32) -> i32 {
+        percent_of(price, self.price_percent)
+    }
+
+    /// Sets the rule called `name`, as used by mods and rule files.
+    pub fn set(&mut self, name: &str, value: f64) -> Result<(), String> {
+        let (min, max, whole) = match name {
+            "starting_funds" => (0.0, 100_000_000.0, true),
+            "inspection_interval" => (1.0, 10_000.0, true),
+            "debt_game_over" => (0.0, 1.0, true),
+            "price_percent" => (0.0, 1_000.0, true),
+            "move_in_base" | "move_in_decay" | "move_out_base" | "move_out_decay" => {
+                (0.0, 1.0, false)
+            }
+            "property_tax_percent" | "income_tax_percent" => (0.0, 100.0, true),
+            _ => return Err(format!("there is no rule called {name}")),
+        };
+        if whole && value.fract() != 0.0 {
+            return Err(format!("{name} has to be a whole number, not {value}"));
+        }
+        if !(min..=max).contains(&value) {
+            return Err(format!("{name} has to be from {min} to {max}, not {value}"));
+        }
+        // Every whole rule's range fits an i32
+        let whole = value as i32;
+        match name {
+            "starting_funds" => self.starting_funds = whole,
+            "inspection_interval" => self.inspection_interval = whole as u32,
+            "debt_game_over" => self.debt_game_over = whole != 0,
+            "price_percent" => self.price_percent = whole,
+            "move_in_base" => self.move_in_base = value,
+            "move_in_decay" => self.move_in_decay = value,
+            "move_out_base" => self.move_out_base = value,
+            "move_out_decay" => self.move_out_decay = value,
+            "property_tax_percent" => self.property_tax_percent = whole,
+            _ => self.income_tax_percent = whole,
+        }
+        Ok(())
+    }
+
+    /// What happens at debt collector inspections under these rules, for
+    /// help.
+    pub fn inspection_rule(&self) -> String {
+        let mut rule = format!(
+            "The debt collector inspects every {} ticks, when the countdown at the top \
+             reaches zero.",
+            self.inspection_interval
+        );
+        rule += if self.debt_game_over {
+            " If your funds are negative when they arrive, the game is over."
+        } else {
+            " Being in debt when they arrive doesn't end the game under these rules."
+        };
+        if self.property_tax_percent > 0 || self.income_tax_percent > 0 {
+            rule += " Taxes are due then too: unpaid taxes cost you floors, not the game.";
+        }
+        rule
+    }
+
+    /// Every rule's name and value, as `set` takes them.
+    pub fn values(&self) -> Vec<(&'static str, f64)> {
+        vec![
+            ("starting_funds", self.starting_funds as f64),
+            ("inspection_interval", self.inspection_interval as f64),
+            ("debt_game_over", self.debt_game_over as u8 as f64),
+            ("price_percent", self.price_percent as f64),
+            ("move_in_base", self.move_in_base),
+            ("move_in_decay", self.move_in_decay),
+            ("move_out_base", self.move_out_base),
+            ("move_out_decay", self.move_out_decay),
+            ("property_tax_percent", self.property_tax_percent as f64),
+            ("income_tax_percent", self.income_tax_percent as f64),
+        ]
+    }
+
+    /// Reads a custom rule set called `name`. Each line looks like
+    ///
+    /// ```text
+    /// starting_funds = 15000
+    /// ```
+    ///
+    /// and a `base = hard` line starts from one of the presets instead of
+    /// Normal. Anything left out keeps the base's value.
+    pub fn parse(name: &str, contents: &str) -> Result<Self, String> {
+        let mut rules = Rules::default();
+        for (line_number, line) in (1..).zip(contents.lines()) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |e: &str| format!("{name}:{line_number}: {e}");
+            let Some((field, value)) = line.split_once('=') else {
+                return Err(error("expected `name = value`"));
+            };
+            let (field, value) = (field.trim(), value.trim());
+            if field == "base" {
+                rules = Rules::presets()
+                    .into_iter()
+                    .find(|preset| preset.name.eq_ignore_ascii_case(value))
+                    .ok_or_else(|| error(&format!("there is no preset called {value}")))?;
+                continue;
+            }
+            let value = value
+                .parse()
+                .map_err(|_| error(&format!("`{value}` is not a number")))?;
+            rules.set(field, value).map_err(|e| error(&e))?;
+        }
+        rules.name = name.to_string();
+        Ok(rules)
+    }
+
+    /// Custom rule sets from `.rules` files in the rules directory of
+    /// `data_dir`, in name order, along with any problems reading them.
+    pub fn load_custom(data_dir: &DataDir) -> (Vec<Rules>, Vec<String>) {
+        let dir = match data_dir.file(RULES_DIR).and_then(fs::read_dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return (vec![], vec![]),
+            Err(e) => return (vec![], vec![format!("{RULES_DIR}: {e}")]),
+        };
+        let mut paths: Vec<_> = dir
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == RULES_EXTENSION))
+            .collect();
+        paths.sort();
+
+        let mut custom = Vec::new();
+        let mut errors = Vec::new();
+        for path in paths {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+            let rules = fs::read_to_string(&path)
+                .map_err(|e| format!("{name}: {e}"))
+                .and_then(|contents| Rules::parse(&name, &contents));
+            match rules {
+                Ok(rules) => custom.push(rules),
+                Err(e) => errors.push(e),
+            }
+        }
+        (custom, errors)
+    }
+}
+
+/// Shows one of a rule set's rules.
+type RuleText = fn(&Rules) -> String;
+
+/// Lists `rule_sets` side by side to choose one for a new game, with the key
+/// that picks each above it.
+pub fn rule_sets_draw(mut writer: impl Write, rule_sets: &[Rules]) -> io::Result<()> {
+    const TOP_ROW: u16 = 3;
+    const LABEL_WIDTH: usize = 20;
+    const COLUMN_WIDTH: usize = 11;
+
+    let rows: [(&str, RuleText); 10] = [
+        ("Starting funds", |rules| format!("${}", rules.starting_funds)),
+        ("Inspection every", |rules| format!("{} ticks", rules.inspection_interval)),
+        ("Debt ends the game", |rules| {
+            if rules.debt_game_over { "yes" } else { "no" }.to_string()
+        }),
+        ("Build prices", |rules| format!("{}%", rules.price_percent)),
+        ("Move in base", |rules| rules.move_in_base.to_string()),
+        ("Move in decay", |rules| rules.move_in_decay.to_string()),
+        ("Move out base", |rules| rules.move_out_base.to_string()),
+        ("Move out decay", |rules| rules.move_out_decay.to_string()),
+        ("Property tax", |rules| format!("{}%", rules.property_tax_percent)),
+        ("Income tax", |rules| format!("{}%", rules.income_tax_percent)),
+    ];
+
+    queue!(
+        writer,
+        cursor::MoveTo(WINDOW_WIDTH, TOWER_WINDOW_HEIGHT),
+        terminal::Clear(terminal::ClearType::FromCursorUp),
+        style::SetColors(Colors {
+            foreground: Some(Color::White),
+            background: Some(Color::Black),
+        }),
+        cursor::MoveTo(0, TOP_ROW),
+        style::Print(format!("{:LABEL_WIDTH$}", "NEW GAME")),
+        style::SetForegroundColor(Color::Green),
+    )?;
+    for (key, rules) in (1..).zip(rule_sets) {
+        let name: String =
+            format!("{key}: {}", rules.name).chars().take(COLUMN_WIDTH - 1).collect();
+        queue!(writer, style::Print(format!("{name:>COLUMN_WIDTH$}")))?;
+    }
+    queue!(writer, style::SetForegroundColor(Color::White))?;
+    for (row, (label, value)) in (TOP_ROW + 2..).zip(rows) {
+        let mut line = format!("{label:LABEL_WIDTH$}");
+        for rules in rule_sets {
+            line += &format!("{:>COLUMN_WIDTH$}", value(rules));
+        }
+        queue!(writer, cursor::MoveTo(0, row), style::Print(line))?;
+    }
+    queue!(
+        writer,
+        cursor::MoveTo(0, TOP_ROW + 13),
+        style::SetForegroundColor(Color::DarkGrey),
+        style::Print(format!(
+            "Add your own rule sets as .{RULES_EXTENSION} files in the {RULES_DIR} directory of \
+             the user data directory."
+        )),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameBuffer;
+    use crate::game_state::init_game_state;
+    use crate::layer::LayerType;
+    use crate::test_game::TestGame;
+    use crossterm::event::KeyCode;
+    use std::rc::Rc;
+
+    #[test]
+    fn custom_rules_start_a_new_game() {
+        let mut game = TestGame::with_data_files(&[(
+            "rules/tough.rules",
+            "# Hard, but broke\nbase = hard\nstarting_funds = 1234\n",
+        )]);
+        game.gs.show_new_game().unwrap();
+        game.press(KeyCode::Char('5'));
+        assert!(game.gs.restarting);
+        let rules = game.gs.next_rules.take().expect("no rules chosen");
+        let mods = Rc::clone(&game.gs.mods);
+        game.gs = init_game_state(FrameBuffer::new(), mods, rules, game.gs.data_dir.clone());
+        assert_eq!(game.gs.rules.name, "tough");
+        assert_eq!(game.gs.funds, 1234);
+
+        // Hard makes building a quarter dearer
+        game.gs.funds = 100_000;
+        game.build(LayerType::Retail);
+        assert_eq!(game.gs.funds, 100_000 - game.floor_type(LayerType::Retail).price * 5 / 4);
+    }
+
+    #[test]
+    fn rule_sets_without_a_key_are_reported() {
+        let paths: Vec<_> = (1..=9).map(|n| format!("rules/custom{n}.rules")).collect();
+        let files: Vec<_> = paths.iter().map(|path| (path.as_str(), "base = easy\n")).collect();
+        let game = TestGame::with_data_files(&files);
+        let (msg, _) = game.gs.toast.clone().expect("the left out rule sets weren't reported");
+        let kept = 9 - Rules::presets().len();
+        let msg_start =
+            format!("Rules: custom{}: left out, there can only be 9 rule sets", kept + 1);
+        assert!(msg.starts_with(&msg_start), "{msg}");
+        assert!(msg.ends_with(&format!("(and {} more)", 9 - kept - 1)), "{msg}");
+    }
+
+    #[test]
+    fn rules_out_of_range_are_refused() {
+        let mut rules = Rules::default();
+        for (name, value) in [
+            ("price_percent", 1e12),
+            ("starting_funds", -1.0),
+            ("inspection_interval", 0.0),
+            ("income_tax_percent", 101.0),
+            ("move_in_base", 1.5),
+            ("move_out_decay", f64::NAN),
+        ] {
+            let error = rules.set(name, value).unwrap_err();
+            assert!(error.starts_with(&format!("{name} has to be from")), "{error}");
+        }
+        assert_eq!(rules.price_percent, Rules::default().price_percent);
+        assert!(rules.set("price_percent", 1_000.0).is_ok());
+        assert_eq!(rules.price(i32::MAX), i32::MAX);
+        assert_eq!(rules.price(3), 30);
+        for preset in Rules::presets() {
+            for (name, value) in preset.values() {
+                assert!(Rules::default().set(name, value).is_ok(), "{}: {name}", preset.name);
+            }
+        }
+    }
+
+    #[test]
+    fn sandbox_games_survive_debt() {
+        let mut game = TestGame::new();
+        let sandbox = Rules::presets().into_iter().find(|rules| rules.name == "Sandbox").unwrap();
+        game.gs.rules = sandbox;
+        game.gs.funds = -50_000;
+        game.gs.debt_collection_timer = 1;
+        game.tick();
+        assert!(!game.gs.game_over);
+        assert_eq!(game.gs.period, 1);
+    }
+
+    #[test]
+    fn help_explains_inspections_under_the_rules_played() {
+        let sandbox = Rules::presets().into_iter().find(|rules| rules.name == "Sandbox").unwrap();
+        let game = TestGame::new();
+        let mods = Rc::clone(&game.gs.mods);
+        let gs = init_game_state(FrameBuffer::new(), mods, sandbox, game.gs.data_dir.clone());
+        let rules = gs.command_pool_array.rules();
+        let rule = rules.iter().find(|rule| rule.starts_with("The debt collector")).unwrap();
+        assert!(rule.contains("every 50 ticks"), "{rule}");
+        assert!(rule.contains("doesn't end the game"), "{rule}");
+        assert!(!rule.contains("Taxes"), "{rule}");
+
+        let normal = Rules::default().inspection_rule();
+        assert!(normal.contains("the game is over") && normal.contains("Taxes"), "{normal}");
+    }
+}
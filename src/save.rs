@@ -0,0 +1,305 @@
+use crate::game_state::GameState;
+use crate::layer::{FloorTypes, Layer, MAX_RENT};
+use crate::ledger::{Ledger, Transaction, TransactionCategory};
+use crate::rating::MAX_STARS;
+use crate::rules::Rules;
+use crate::staff::StaffRole;
+use crate::tax::Taxes;
+use crate::storage::DataDir;
+use crate::weather::{Calendar, Weather};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+const SAVES_DIR: &str = "saves";
+const SAVE_VERSION: u32 = 1;
+
+fn slot_path(data_dir: &DataDir, slot: &str) -> io::Result<PathBuf> {
+    let valid = !slot.is_empty()
+        && slot
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "slot names can only have letters, digits, - and _",
+        ));
+    }
+    let dir = data_dir.file(SAVES_DIR)?;
+    fs::create_dir_all(&dir)?;
+    Ok(dir.join(format!("{slot}.sav")))
+}
+
+/// Writes the game in progress to `slot` in the saves directory. Each line
+/// is a `name = value` pair, with one `floor` line per floor, one
+/// `transaction` line per ledger entry, one `rule` line per rule and the
+/// weather for today and the days forecast.
+pub fn save_game(gs: &GameState, slot: &str) -> io::Result<()> {
+    let mut file = io::BufWriter::new(fs::File::create(slot_path(&gs.data_dir, slot)?)?);
+    writeln!(file, "version = {SAVE_VERSION}")?;
+    writeln!(file, "seed = {}", gs.seed)?;
+    writeln!(file, "tick = {}", gs.tick)?;
+    writeln!(file, "period = {}", gs.period)?;
+    writeln!(file, "funds = {}", gs.funds)?;
+    writeln!(file, "peak_funds = {}", gs.peak_funds)?;
+    writeln!(file, "loan = {}", gs.loan)?;
+    writeln!(file, "floors_built = {}", gs.floors_built)?;
+    writeln!(file, "debt_collection_timer = {}", gs.debt_collection_timer)?;
+    writeln!(file, "stars = {}", gs.stars)?;
+    writeln!(file, "rules = {}", gs.rules.name)?;
+    for (name, value) in gs.rules.values() {
+        writeln!(file, "rule = {name},{value}")?;
+    }
+    writeln!(file, "tax_assessment = {}", gs.taxes.assessment_percent)?;
+    writeln!(file, "tax_arrears = {}", gs.taxes.arrears)?;
+    for role in StaffRole::ALL {
+        writeln!(file, "{} = {}", role.id(), gs.staff.headcount(role))?;
+    }
+    let weather: Vec<&str> = gs.calendar.days().map(|weather| weather.id()).collect();
+    writeln!(file, "weather = {}", weather.join(","))?;
+    for layer in &gs.layers {
+        writeln!(
+            file,
+            "floor = {},{},{},{:.0},{}",
+            layer.floor_type().id,
+            layer.revenue_per_occupant,
+            layer.occupancy,
+            layer.cleanliness * 100.0,
+            layer.broken_down as u8
+        )?;
+    }
+    for t in gs.ledger.transactions() {
+        let floor = t.floor.map(|f| f.to_string()).unwrap_or_default();
+        writeln!(
+            file,
+            "transaction = {},{},{},{},{}",
+            t.tick,
+            t.period,
+            t.category.name(),
+            floor,
+            t.amount
+        )?;
+    }
+    file.flush()
+}
+
+fn invalid(line_number: usize, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {line_number}: {msg}"))
+}
+
+/// The whole number saved as `name`, if there is one, or an error if it is
+/// out of range for `T`.
+fn optional_number<T: TryFrom<i128>>(
+    values: &HashMap<&str, (usize, i128)>,
+    name: &str,
+) -> io::Result<Option<T>> {
+    let Some(&(line_number, value)) = values.get(name) else {
+        return Ok(None);
+    };
+    let value = T::try_from(value).map_err(|_| invalid(line_number, &format!("bad {name}")))?;
+    Ok(Some(value))
+}
+
+fn number<T: TryFrom<i128>>(values: &HashMap<&str, (usize, i128)>, name: &str) -> io::Result<T> {
+    optional_number(values, name)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("missing {name}")))
+}
+
+fn parse_floor(value: &str, floor_types: &FloorTypes) -> Option<Layer> {
+    let mut fields = value.split(',');
+    let style = floor_types.find(fields.next()?)?;
+    let max_occupancy = floor_types.get(style).max_occupancy;
+    let rent = fields.next()?.parse().ok().filter(|rent| (1..=MAX_RENT).contains(rent))?;
+    let mut layer = Layer::new(floor_types, style, rent, max_occupancy);
+    layer.occupancy = fields.next()?.parse::<usize>().ok()?.min(layer.max_occupancy);
+    // Saves from before floors needed looking after leave them spotless
+    if let Some(cleanliness) = fields.next() {
+        layer.cleanliness = (cleanliness.parse::<f64>().ok()? / 100.0).clamp(0.0, 1.0);
+        layer.broken_down = fields.next()? == "1";
+    }
+    layer.set_string();
+    Some(layer)
+}
+
+fn parse_transaction(value: &str) -> Option<Transaction> {
+    let mut fields = value.split(',');
+    let transaction = Transaction {
+        tick: fields.next()?.parse().ok()?,
+        period: fields.next()?.parse().ok()?,
+        category: TransactionCategory::from_name(fields.next()?)?,
+        floor: match fields.next()? {
+            "" => None,
+            floor => Some(floor.parse().ok()?),
+        },
+        amount: fields.next()?.parse().ok()?,
+    };
+    Some(transaction)
+}
+
+/// Replaces the game in progress with the one saved in `slot`. Nothing is
+/// changed if the save can't be read.
+pub fn load_game(gs: &mut GameState, slot: &str) -> io::Result<()> {
+    let contents = fs::read_to_string(slot_path(&gs.data_dir, slot)?)?;
+    let mut layers = Vec::new();
+    let mut ledger = Ledger::new();
+    let mut weather = Vec::new();
+    // Saves from before there were rule sets were played by the defaults
+    let mut rules = Rules::default();
+    let mut values = HashMap::new();
+    for (line_number, line) in (1..).zip(contents.lines()) {
+        let Some((name, value)) = line.split_once('=') else {
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        match name {
+            "floor" => {
+                let layer = parse_floor(value, gs.mods.floor_types())
+                    .ok_or_else(|| invalid(line_number, "bad floor"))?;
+                layers.push(layer);
+            }
+            "transaction" => {
+                let transaction = parse_transaction(value)
+                    .ok_or_else(|| invalid(line_number, "bad transaction"))?;
+                ledger.record(transaction);
+            }
+            "rules" => rules.name = value.to_string(),
+            "rule" => {
+                value
+                    .split_once(',')
+                    .and_then(|(name, value)| Some((name, value.parse().ok()?)))
+                    .and_then(|(name, value)| rules.set(name, value).ok())
+                    .ok_or_else(|| invalid(line_number, "bad rule"))?;
+            }
+            "weather" => {
+                weather = value
+                    .split(',')
+                    .map(Weather::from_id)
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| invalid(line_number, "bad weather"))?;
+            }
+            _ => {
+                let value: i128 = value
+                    .parse()
+                    .map_err(|_| invalid(line_number, &format!("bad {name}")))?;
+                values.insert(name, (line_number, value));
+            }
+        }
+    }
+    if number::<u32>(&values, "version")? != SAVE_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "saved by a different version of the game",
+        ));
+    }
+    // Check everything before changing anything
+    let seed: u64 = number(&values, "seed")?;
+    let tick: u64 = number(&values, "tick")?;
+    let period: u32 = number(&values, "period")?;
+    let funds: i32 = number(&values, "funds")?;
+    let peak_funds: i32 = number(&values, "peak_funds")?;
+    let loan: i32 = number(&values, "loan")?;
+    let floors_built: usize = number(&values, "floors_built")?;
+    let debt_collection_timer: u32 = number(&values, "debt_collection_timer")?;
+    // Saves from before there was staff have none
+    let mut headcounts = Vec::new();
+    for role in StaffRole::ALL {
+        let headcount: usize = optional_number(&values, role.id())?.unwrap_or(0);
+        headcounts.push((role, headcount.min(role.max_headcount(layers.len()))));
+    }
+    // Saves from before there were taxes have nothing owing
+    let default_taxes = Taxes::default();
+    let taxes = Taxes {
+        assessment_percent: optional_number(&values, "tax_assessment")?
+            .unwrap_or(default_taxes.assessment_percent),
+        arrears: optional_number(&values, "tax_arrears")?.unwrap_or(0),
+    };
+
+    gs.seed = seed;
+    gs.tick = tick;
+    gs.period = period;
+    gs.funds = funds;
+    gs.peak_funds = peak_funds;
+    gs.loan = loan;
+    gs.floors_built = floors_built;
+    gs.debt_collection_timer = debt_collection_timer;
+    gs.layers = layers;
+    gs.rules = rules;
+    // Saves from before there were ratings start over at one star
+    gs.stars = values
+        .get("stars")
+        .map_or(1, |&(_, stars)| stars.clamp(1, MAX_STARS as i128) as u8);
+    for (role, headcount) in headcounts {
+        gs.staff.set_headcount(role, headcount);
+    }
+    gs.ledger = ledger;
+    gs.taxes = taxes;
+    gs.message_log.clear();
+    // The generator's state isn't saved, so carry on from a new one derived
+    // from the seed
+    gs.rng = StdRng::seed_from_u64(gs.seed.wrapping_add(gs.tick));
+    // Saves from before there was weather get a new forecast
+    gs.calendar = Calendar::restore(gs.tick, &weather, &mut gs.rng);
+    gs.game_over = false;
+    gs.selected_floor = None;
+    gs.scroll_pos = 0;
+    gs.undo_history.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::LayerType;
+    use crate::save;
+    use crate::test_game::TestGame;
+
+    /// Saves the game, sets `name` to `value` in the save and loads it back.
+    fn load_edited_save(game: &mut TestGame, name: &str, value: &str) -> io::Result<()> {
+        save::save_game(&game.gs, "edited").unwrap();
+        let path = game.gs.data_dir.file("saves/edited.sav").unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let contents: String = contents
+            .lines()
+            .map(|line| match line.split_once(" = ") {
+                Some((n, _)) if n == name => format!("{name} = {value}\n"),
+                _ => format!("{line}\n"),
+            })
+            .collect();
+        fs::write(&path, contents).unwrap();
+        save::load_game(&mut game.gs, "edited")
+    }
+
+    #[test]
+    fn saves_with_numbers_out_of_range_are_not_loaded() {
+        let mut game = TestGame::new();
+        let error = load_edited_save(&mut game, "funds", "4294967296").unwrap_err();
+        assert_eq!(error.to_string(), "line 5: bad funds");
+        let error = load_edited_save(&mut game, "period", "-1").unwrap_err();
+        assert_eq!(error.to_string(), "line 4: bad period");
+
+        game.build(LayerType::Food);
+        let error = load_edited_save(&mut game, "tick", "18446744073709551616").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: bad tick");
+        assert_eq!(game.gs.layers.len(), 1, "a save that failed to load changed the game");
+        let error = load_edited_save(&mut game, "janitors", "-1").unwrap_err();
+        assert!(error.to_string().ends_with("bad janitors"), "{error}");
+        let error = load_edited_save(&mut game, "tax_arrears", "2147483648").unwrap_err();
+        assert!(error.to_string().ends_with("bad tax_arrears"), "{error}");
+        assert!(load_edited_save(&mut game, "funds", "-5").is_ok());
+        assert_eq!(game.gs.funds, -5);
+    }
+
+    #[test]
+    fn saves_with_too_many_staff_keep_what_the_tower_can_employ() {
+        let mut game = TestGame::new();
+        game.build(LayerType::Food).build(LayerType::Apartment);
+        load_edited_save(&mut game, "janitors", "9223372036854775807").unwrap();
+        let janitors = game.gs.staff.headcount(StaffRole::Janitor);
+        assert_eq!(janitors, StaffRole::Janitor.max_headcount(2));
+        assert!(!game.gs.staff.covers(StaffRole::Janitor, usize::MAX - 1));
+        assert_eq!(game.gs.staff.wages(), StaffRole::Janitor.wage() * janitors as i32);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::layer::Layer;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Columns of sky the clouds drift across before coming back round.
+const SKY_WIDTH: usize = 26;
+/// Columns the clouds drift each second.
+const CLOUD_SPEED: f64 = 0.5;
+/// Columns a figure walks each second.
+const WALK_SPEED: f64 = 3.0;
+/// Column inside a floor where figures walk in from the left wall, and the
+/// column where they reach the door and disappear inside.
+const WALK_START: u16 = 1;
+const WALK_DOOR: u16 = 6;
+/// Chance each second of someone walking into a full floor. Emptier floors
+/// get fewer visitors.
+const WALKER_CHANCE: f64 = 0.15;
+/// A figure mid-stride, one frame for each foot.
+const WALKER_FRAMES: [char; 2] = ['⡷', '⢾'];
+/// Seconds a crane stands over a newly built floor.
+const CONSTRUCTION_SECONDS: f64 = 4.0;
+
+/// Someone on their way into a floor.
+struct Walker {
+    floor: usize,
+    /// When they started walking, in seconds of scenery time.
+    started: f64,
+}
+
+/// Purely decorative goings on around the tower, advanced by a render timer
+/// of their own so they move smoothly whatever the game speed. Nothing here
+/// affects the game, and it has its own random numbers so it doesn't change
+/// how a seeded game plays out.
+pub struct Scenery {
+    /// Seconds of animation so far.
+    time: f64,
+    walkers: Vec<Walker>,
+    /// The floor being built and when its crane comes down.
+    construction: Option<(usize, f64)>,
+    rng: StdRng,
+}
+
+impl Scenery {
+    pub fn new() -> Self {
+        Scenery {
+            time: 0.0,
+            walkers: Vec::new(),
+            construction: None,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Moves everything on by `seconds`, sending people into `layers` in
+    /// proportion to how full they are.
+    pub fn advance(&mut self, seconds: f64, layers: &[Layer]) {
+        self.time += seconds;
+        let time = self.time;
+        self.walkers.retain(|walker| {
+            walker.floor < layers.len()
+                && walker_column(time - walker.started) <= WALK_DOOR
+        });
+        for (floor, layer) in layers.iter().enumerate() {
+            if layer.occupancy == 0 || self.walkers.iter().any(|walker| walker.floor == floor) {
+                continue;
+            }
+            let fullness = layer.occupancy as f64 / layer.max_occupancy.max(1) as f64;
+            if self.rng.gen_bool((WALKER_CHANCE * fullness * seconds).clamp(0.0, 1.0)) {
+                self.walkers.push(Walker {
+                    floor,
+                    started: self.time,
+                });
+            }
+        }
+        if self.construction.is_some_and(|(_, until)| until <= self.time) {
+            self.construction = None;
+        }
+    }
+
+    /// Puts up a crane over `floor` for a few seconds.
+    pub fn start_construction(&mut self, floor: usize) {
+        self.construction = Some((floor, self.time + CONSTRUCTION_SECONDS));
+    }
+
+    /// Steps into looping animations like falling rain, four a second.
+    pub fn phase(&self) -> usize {
+        (self.time * 4.0) as usize
+    }
+
+    /// `line` of the sky, with the clouds drifted along.
+    pub fn drift(&self, line: &str) -> String {
+        let mut chars: Vec<char> = line.chars().collect();
+        chars.resize(SKY_WIDTH.max(chars.len()), ' ');
+        let offset = (self.time * CLOUD_SPEED) as usize % chars.len();
+        chars.rotate_right(offset);
+        chars.into_iter().collect::<String>().trim_end().to_string()
+    }
+
+    /// The floor, column and look of everyone walking into a floor.
+    pub fn walkers(&self) -> impl Iterator<Item = (usize, u16, char)> + '_ {
+        self.walkers.iter().map(|walker| {
+            let column = walker_column(self.time - walker.started);
+            let frame = WALKER_FRAMES[column as usize % WALKER_FRAMES.len()];
+            (walker.floor, column, frame)
+        })
+    }
+
+    /// The floor under construction, if any, and the crane over it, with
+    /// its hook bobbing up and down.
+    pub fn crane(&self) -> Option<(usize, String)> {
+        let (floor, _) = self.construction?;
+        let cable = 1 + (self.time * 2.0) as usize % 2;
+        let mut crane = String::from(" __________________\n |X|/\\/\\/\\/\\/\\/\\/\\|\n");
+        for row in 1..=3 {
+            crane += match row.cmp(&cable) {
+                std::cmp::Ordering::Less => " |X|            |\n",
+                std::cmp::Ordering::Equal => " |X|           [_]\n",
+                std::cmp::Ordering::Greater => " |X|\n",
+            };
+        }
+        Some((floor, crane))
+    }
+}
+
+fn walker_column(seconds_walking: f64) -> u16 {
+    WALK_START + (seconds_walking * WALK_SPEED) as u16
+}
@@ -0,0 +1,284 @@
+//! Tests that play the game without a terminal and look at what ends up on
+//! the screen.
+//!
+//! Each screen is compared against a golden snapshot in `tests/snapshots`.
+//! After changing how something is drawn, run
+//! `UPDATE_SNAPSHOTS=1 cargo test` to rewrite the snapshots and check the
+//! differences with `git diff`.
+
+use crate::command::{Command, CommandPoolArrayBuilder, CommandPoolBuilder};
+use crate::frame::FrameBuffer;
+use crate::keymap::{KeyBinding, Keymap};
+use crate::layer::LayerType;
+use crate::test_game::{press_versus, versus, TestGame};
+use crate::weather::{Calendar, Weather};
+use crate::TOWER_WINDOW_HEIGHT;
+use crossterm::event::KeyCode;
+use crossterm::style::Color;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+impl TestGame {
+    fn assert_snapshot(&self, name: &str) {
+        assert_snapshot(&self.gs.frame, name);
+    }
+}
+
+/// Compares `frame` with the snapshot called `name`, or rewrites the
+/// snapshot if `UPDATE_SNAPSHOTS` is set.
+fn assert_snapshot(frame: &FrameBuffer, name: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+    let actual = frame.snapshot();
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        expected == actual,
+        "{name} doesn't match {}, run with UPDATE_SNAPSHOTS=1 if that was \
+         intended. The screen is now:\n{actual}",
+        path.display()
+    );
+}
+
+#[test]
+fn floor_shows_its_occupancy() {
+    let mut game = TestGame::new();
+    game.build(LayerType::Food).build(LayerType::Apartment).build(LayerType::Apartment);
+    game.gs.layers[2].occupancy = 12;
+    game.gs.layers[2].set_string();
+    game.gs.draw_tower().unwrap();
+
+    let frame = &game.gs.frame;
+    let (_, row) = frame.find("OCCUPANCY: 12").expect("occupancy not shown");
+    // The floor's name is at the top of it, and the floor below is empty
+    assert!(frame.row_text(row - 3).contains(&game.floor_type(LayerType::Apartment).name));
+    assert_eq!(frame.find("OCCUPANCY: 0").map(|(_, y)| y), Some(row + 5));
+}
+
+#[test]
+fn funds_go_down_when_building() {
+    let mut game = TestGame::new();
+    let funds = game.gs.funds;
+    game.build(LayerType::Retail);
+    let expected = format!("FUNDS: {}", funds - game.floor_type(LayerType::Retail).price);
+    assert!(game.gs.frame.find(&expected).is_some(), "{}", game.gs.frame.text());
+}
+
+#[test]
+fn minimap_shows_every_floor() {
+    let mut game = TestGame::new();
+    game.gs.funds = 1_000_000;
+    for _ in 0..25 {
+        game.build(LayerType::Food);
+    }
+    game.gs.layers[0].occupancy = game.gs.layers[0].max_occupancy;
+    game.press(KeyCode::Home);
+
+    let frame = &game.gs.frame;
+    // Floors fill the first column of the minimap, then carry on in the next
+    let (x, y) = frame.find("█").expect("full floor not on the minimap");
+    assert_eq!(y, TOWER_WINDOW_HEIGHT - 1);
+    assert_eq!(frame.cell(x, 0).ch, '·');
+    assert_eq!(frame.cell(x + 1, y - 5).ch, '·');
+    assert_eq!(frame.cell(x + 1, y - 6).ch, ' ');
+    // The floors at the bottom are in view
+    assert_eq!(frame.cell(x, y).bg, Color::DarkGrey);
+    assert_eq!(frame.cell(x, 0).bg, Color::Black);
+}
+
+#[test]
+fn clouds_drift_and_cranes_come_down() {
+    let mut game = TestGame::new();
+    game.build(LayerType::Food);
+    let sky = game.gs.frame.row_text(5);
+    assert!(game.gs.frame.find("[_]").is_some(), "no crane over the new floor");
+
+    for _ in 0..game.gs.animation_fps * 5 {
+        game.gs.animate().unwrap();
+    }
+    assert_ne!(game.gs.frame.row_text(5), sky);
+    assert!(game.gs.frame.find("[_]").is_none(), "the crane is still up");
+    // The funds beside the tower are still there
+    assert!(game.gs.frame.find("CURRENT FUNDS").is_some());
+}
+
+#[test]
+fn snapshot_rain() {
+    let mut game = TestGame::new();
+    game.gs.calendar = Calendar::restore(0, &[Weather::Rain; 4], &mut game.gs.rng);
+    game.build(LayerType::Food).tick();
+    game.assert_snapshot("rain");
+}
+
+#[test]
+fn snapshot_staff() {
+    let mut game = TestGame::new();
+    game.build(LayerType::Food).build(LayerType::Apartment);
+    game.press(KeyCode::Char('S')).type_keys("jjgmJ");
+    game.assert_snapshot("staff");
+}
+
+#[test]
+fn snapshot_tower_empty() {
+    TestGame::new().assert_snapshot("tower_empty");
+}
+
+#[test]
+fn snapshot_tower_three_floors() {
+    let mut game = TestGame::new();
+    game.build(LayerType::Food).build(LayerType::Apartment).build(LayerType::Retail);
+    game.tick().tick();
+    game.assert_snapshot("tower_three_floors");
+}
+
+#[test]
+fn snapshot_compact_view() {
+    let mut game = TestGame::new();
+    game.build(LayerType::Food).build(LayerType::Apartment).build(LayerType::Retail);
+    game.tick().tick();
+    game.press(KeyCode::Char('v'));
+    game.assert_snapshot("compact_view");
+}
+
+#[test]
+fn snapshot_build_menu() {
+    let mut game = TestGame::new();
+    game.press(KeyCode::Char('b'));
+    game.assert_snapshot("build_menu");
+}
+
+#[test]
+fn snapshot_console() {
+    let mut game = TestGame::new();
+    game.type_keys(":bui").press(KeyCode::Tab).type_keys("ap");
+    game.assert_snapshot("console");
+}
+
+#[test]
+fn snapshot_finances() {
+    let mut game = TestGame::new();
+    game.build(LayerType::Apartment).tick();
+    game.press(KeyCode::Char('f'));
+    game.assert_snapshot("finances");
+}
+
+#[test]
+fn snapshot_achievements() {
+    let mut game = TestGame::new();
+    game.press(KeyCode::Char('a'));
+    game.assert_snapshot("achievements");
+}
+
+#[test]
+fn snapshot_help() {
+    let mut game = TestGame::new();
+    game.press(KeyCode::Char('?'));
+    game.assert_snapshot("help");
+}
+
+#[test]
+fn help_pages_through_every_control_and_rule() {
+    let mut game = TestGame::new();
+    game.press(KeyCode::Char('?'));
+    assert!(game.gs.frame.find("CONTROLS (page 1 of 2)").is_some());
+    assert!(game.gs.frame.find("Tear down the selected floor").is_some());
+    assert!(game.gs.frame.find("Borrow $").is_none(), "controls spilled past the page");
+
+    game.press(KeyCode::Tab);
+    let frame = &game.gs.frame;
+    assert!(frame.find("CONTROLS (page 2 of 2)").is_some());
+    assert!(frame.find("Borrow $").is_some());
+    assert!(frame.find("Show this screen").is_some(), "the last control is missing");
+    assert!(frame.find("- The weather changes every day").is_some());
+
+    game.press(KeyCode::Tab);
+    assert!(game.gs.frame.find("CONTROLS (page 1 of 2)").is_some());
+    game.press(KeyCode::Esc).press(KeyCode::Char('?'));
+    assert!(game.gs.frame.find("CONTROLS (page 1 of 2)").is_some());
+}
+
+#[test]
+fn tab_turns_the_page_of_the_controls_panel() {
+    let mut game = TestGame::new();
+    let mut pool = CommandPoolBuilder::new("test");
+    for c in 'a'..='z' {
+        let action = format!("control_{c}");
+        let description = format!("Control {c} with a longish description");
+        let command = Command::new(|_| Ok(()));
+        pool = pool.on_key_press(action, &[KeyBinding::char(c)], description, command);
+    }
+    let mut pools = CommandPoolArrayBuilder::new(Keymap::empty());
+    let id = pools.add_pool(pool.build());
+    game.gs.command_pool_array = pools.with_initial_pool(id);
+    game.gs.draw_command_pool().unwrap();
+
+    let page_count = game.gs.command_pool().page_count();
+    assert!(page_count > 1, "26 controls fit on one page");
+    let panel = |game: &TestGame| {
+        (TOWER_WINDOW_HEIGHT..crate::WINDOW_HEIGHT)
+            .map(|y| game.gs.frame.row_text(y) + "\n")
+            .collect::<String>()
+    };
+    let first_page = panel(&game);
+    assert!(first_page.contains("a: Control a"), "{first_page}");
+    assert!(!first_page.contains("z: Control z"), "{first_page}");
+    assert!(first_page.contains(&format!("Tab: more… 1/{page_count}")), "{first_page}");
+
+    game.press(KeyCode::Tab);
+    let second_page = panel(&game);
+    assert!(!second_page.contains("a: Control a"), "{second_page}");
+    assert!(second_page.contains(&format!("Tab: more… 2/{page_count}")), "{second_page}");
+    for _ in 1..page_count {
+        game.press(KeyCode::Tab);
+    }
+    assert_eq!(panel(&game), first_page, "Tab doesn't wrap around to the first page");
+}
+
+#[test]
+fn snapshot_high_scores() {
+    let mut game = TestGame::new();
+    game.press(KeyCode::Char('h'));
+    game.assert_snapshot("high_scores");
+}
+
+#[test]
+fn snapshot_new_game() {
+    let mut game = TestGame::with_data_files(&[(
+        "rules/tough.rules",
+        "# Hard, but broke\nbase = hard\nstarting_funds = 1234\n",
+    )]);
+    game.gs.show_new_game().unwrap();
+    game.assert_snapshot("new_game");
+}
+
+#[test]
+fn snapshot_messages() {
+    let mut game = TestGame::new();
+    game.build(LayerType::Food);
+    game.gs.layers[0].occupancy = 20;
+    game.gs.debt_collection_timer = 1;
+    game.tick();
+    game.press(KeyCode::Char('m'));
+    game.assert_snapshot("messages");
+}
+
+#[test]
+fn versus_players_build_their_own_towers() {
+    let game = TestGame::new();
+    let mut versus = versus(&game, 1);
+    press_versus(&mut versus, KeyCode::Char('1'));
+    press_versus(&mut versus, KeyCode::Char('9'));
+    press_versus(&mut versus, KeyCode::Up);
+    assert_eq!(versus.players[0].layers[0].style, LayerType::Food);
+    assert_eq!(versus.players[1].layers[0].style, LayerType::Retail);
+    assert_eq!(versus.players[1].selected_floor, Some(0));
+    versus.tick().unwrap();
+    assert_snapshot(&versus.frame, "versus");
+}
+
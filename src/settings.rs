@@ -0,0 +1,108 @@
+use crate::storage::DataDir;
+use std::fs;
+use std::io;
+
+const SETTINGS_FILE: &str = "settings.conf";
+/// Most frames of scenery animation drawn each second.
+pub const MAX_ANIMATION_FPS: u32 = 60;
+
+/// Game options read from `settings.conf` in the user data directory. Each
+/// line looks like
+///
+/// ```text
+/// undo_depth = 20
+/// ```
+///
+/// and anything left out keeps its default.
+pub struct Settings {
+    /// Most player actions remembered for undo. 0 turns undo off.
+    pub undo_depth: usize,
+    /// Ticks after an action during which it can still be undone.
+    pub undo_grace_ticks: u64,
+    /// Frames of scenery animation drawn each second, at most
+    /// `MAX_ANIMATION_FPS`. 0 turns it off.
+    pub animation_fps: u32,
+    errors: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            undo_depth: 20,
+            undo_grace_ticks: 2,
+            animation_fps: 8,
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    /// Loads the settings in `data_dir`. A missing file is the same as an
+    /// empty one.
+    pub fn load(data_dir: &DataDir) -> Self {
+        match data_dir.file(SETTINGS_FILE).and_then(fs::read_to_string) {
+            Ok(contents) => Settings::parse(&contents),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Settings::default(),
+            Err(e) => {
+                let mut settings = Settings::default();
+                settings.errors.push(format!("{SETTINGS_FILE}: {e}"));
+                settings
+            }
+        }
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut settings = Settings::default();
+        for (line_number, line) in (1..).zip(contents.lines()) {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((name, value)) = line.split_once('=') else {
+                settings
+                    .errors
+                    .push(format!("{SETTINGS_FILE}:{line_number}: expected `name = value`"));
+                continue;
+            };
+            let (name, value) = (name.trim(), value.trim());
+            let parsed = match name {
+                "undo_depth" => value.parse().map(|v| settings.undo_depth = v).is_ok(),
+                "undo_grace_ticks" => value.parse().map(|v| settings.undo_grace_ticks = v).is_ok(),
+                "animation_fps" => value.parse().map(|v| settings.animation_fps = v).is_ok(),
+                _ => {
+                    settings
+                        .errors
+                        .push(format!("{SETTINGS_FILE}:{line_number}: unknown setting `{name}`"));
+                    continue;
+                }
+            };
+            if !parsed {
+                settings.errors.push(format!(
+                    "{SETTINGS_FILE}:{line_number}: `{value}` is not a valid {name}"
+                ));
+            } else if settings.animation_fps > MAX_ANIMATION_FPS {
+                settings.animation_fps = MAX_ANIMATION_FPS;
+                settings.errors.push(format!(
+                    "{SETTINGS_FILE}:{line_number}: animation_fps is at most {MAX_ANIMATION_FPS}"
+                ));
+            }
+        }
+        settings
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn animation_is_capped_at_sixty_frames_a_second() {
+        let settings = Settings::parse("animation_fps = 2000000000\n");
+        assert_eq!(settings.animation_fps, MAX_ANIMATION_FPS);
+        assert_eq!(settings.errors(), ["settings.conf:1: animation_fps is at most 60"]);
+    }
+}
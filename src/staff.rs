@@ -0,0 +1,330 @@
+use crate::layer::Layer;
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, queue, terminal};
+use rand::Rng;
+use std::cell::Cell;
+use std::io::{self, Write};
+
+/// Cleanliness each occupant of a floor without a janitor takes away a tick.
+const DIRT_PER_OCCUPANT: f64 = 0.002;
+/// Cleanliness a janitor brings back to a floor each tick.
+const CLEANING_PER_TICK: f64 = 0.1;
+/// Chance each tick of a floor breaking down, without and with a maintenance
+/// crew looking after it.
+const BREAKDOWN_CHANCE: [f64; 2] = [0.01, 0.002];
+/// Chance each tick of a broken down floor being fixed, without and with a
+/// maintenance crew. Without one, it's up to the occupants to find someone.
+const REPAIR_CHANCE: [f64; 2] = [0.05, 0.5];
+/// Chance each tick of a break-in on a full floor, without and with
+/// security. Emptier floors are less of a target.
+const CRIME_CHANCE: [f64; 2] = [0.02, 0.004];
+/// Share of a floor's occupants who move out after a break-in.
+const CRIME_MOVE_OUT: f64 = 0.1;
+/// Most staff in one role for each floor of the tower, counting one more
+/// floor so staff can be hired before the first is built.
+const MAX_STAFF_PER_FLOOR: usize = 4;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum StaffRole {
+    Janitor,
+    Security,
+    Maintenance,
+}
+
+impl StaffRole {
+    pub const ALL: [StaffRole; 3] =
+        [StaffRole::Janitor, StaffRole::Security, StaffRole::Maintenance];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StaffRole::Janitor => "Janitors",
+            StaffRole::Security => "Security guards",
+            StaffRole::Maintenance => "Maintenance crew",
+        }
+    }
+
+    /// Identifier used for the role in save files.
+    pub fn id(self) -> &'static str {
+        match self {
+            StaffRole::Janitor => "janitors",
+            StaffRole::Security => "guards",
+            StaffRole::Maintenance => "maintenance",
+        }
+    }
+
+    /// What each member of staff is paid per tick.
+    pub fn wage(self) -> i32 {
+        match self {
+            StaffRole::Janitor => 2,
+            StaffRole::Security => 3,
+            StaffRole::Maintenance => 4,
+        }
+    }
+
+    /// Floors each member of staff can look after.
+    pub fn floors_covered(self) -> usize {
+        match self {
+            StaffRole::Janitor => 3,
+            StaffRole::Security => 4,
+            StaffRole::Maintenance => 5,
+        }
+    }
+
+    /// Most staff in this role a tower of `floors` floors can employ.
+    pub fn max_headcount(self, floors: usize) -> usize {
+        (floors + 1) * MAX_STAFF_PER_FLOOR
+    }
+
+    fn duty(self) -> &'static str {
+        match self {
+            StaffRole::Janitor => "keep floors clean",
+            StaffRole::Security => "stop break-ins",
+            StaffRole::Maintenance => "prevent and fix breakdowns",
+        }
+    }
+}
+
+/// Something that went wrong on a floor during a tick.
+pub enum Incident {
+    BrokeDown { floor: usize },
+    BreakIn { floor: usize, moved_out: usize },
+}
+
+impl Incident {
+    pub fn message(&self) -> String {
+        match self {
+            Incident::BrokeDown { floor } => {
+                format!("The lifts on floor {} broke down!", floor + 1)
+            }
+            Incident::BreakIn { floor, moved_out } => format!(
+                "Break-in on floor {}! {moved_out} occupants moved out.",
+                floor + 1
+            ),
+        }
+    }
+}
+
+/// The people the player employs to look after the tower. Staff look after
+/// floors from the ground up, so with too few the top floors go without.
+#[derive(Clone, Debug, Default)]
+pub struct Staff {
+    headcount: [usize; 3],
+}
+
+impl Staff {
+    pub fn headcount(&self, role: StaffRole) -> usize {
+        self.headcount[role as usize]
+    }
+
+    pub fn set_headcount(&mut self, role: StaffRole, headcount: usize) {
+        self.headcount[role as usize] = headcount;
+    }
+
+    /// Everyone's wages for a tick.
+    pub fn wages(&self) -> i32 {
+        StaffRole::ALL
+            .into_iter()
+            .map(|role| role.wage() * self.headcount(role) as i32)
+            .sum()
+    }
+
+    /// Whether `floor` is looked after by someone in `role`.
+    pub fn covers(&self, role: StaffRole, floor: usize) -> bool {
+        floor < self.headcount(role) * role.floors_covered()
+    }
+
+    /// Cleans, breaks down, repairs and burgles floors for a tick, returning
+    /// anything that went wrong. Occupants scared off go back to
+    /// `tenant_pool`, if the city has one.
+    pub fn tend(
+        &self,
+        layers: &mut [Layer],
+        tenant_pool: Option<&Cell<usize>>,
+        rng: &mut impl Rng,
+    ) -> Vec<Incident> {
+        let mut incidents = Vec::new();
+        for (floor, layer) in layers.iter_mut().enumerate() {
+            let [cleaned, guarded, maintained] =
+                StaffRole::ALL.map(|role| self.covers(role, floor) as usize);
+
+            if cleaned == 1 {
+                layer.cleanliness = (layer.cleanliness + CLEANING_PER_TICK).min(1.0);
+            } else {
+                let dirt = DIRT_PER_OCCUPANT * layer.occupancy as f64;
+                layer.cleanliness = (layer.cleanliness - dirt).max(0.0);
+            }
+
+            if layer.broken_down {
+                layer.broken_down = !rng.gen_bool(REPAIR_CHANCE[maintained]);
+            } else if rng.gen_bool(BREAKDOWN_CHANCE[maintained]) {
+                layer.broken_down = true;
+                incidents.push(Incident::BrokeDown { floor });
+            }
+
+            let fullness = layer.occupancy as f64 / layer.max_occupancy.max(1) as f64;
+            if rng.gen_bool(CRIME_CHANCE[guarded] * fullness) {
+                let moved_out = ((layer.occupancy as f64 * CRIME_MOVE_OUT).ceil() as usize)
+                    .min(layer.occupancy);
+                layer.set_occupancy(layer.occupancy - moved_out, tenant_pool);
+                layer.set_string();
+                incidents.push(Incident::BreakIn { floor, moved_out });
+            }
+        }
+        incidents
+    }
+}
+
+pub fn staff_draw(mut writer: impl Write, staff: &Staff, layers: &[Layer]) -> io::Result<()> {
+    const TOP_ROW: u16 = 3;
+    const FLOORS_ROW: u16 = TOP_ROW + 7;
+
+    queue!(
+        writer,
+        cursor::MoveTo(WINDOW_WIDTH, TOWER_WINDOW_HEIGHT),
+        terminal::Clear(terminal::ClearType::FromCursorUp),
+        style::SetColors(Colors {
+            foreground: Some(Color::White),
+            background: Some(Color::Black),
+        }),
+        cursor::MoveTo(0, TOP_ROW),
+        style::Print(format!(
+            "{:<18}{:>9}{:>7}{:>9}  DUTIES",
+            "STAFF", "HEADCOUNT", "WAGES", "FLOORS"
+        )),
+    )?;
+    for (row, role) in (TOP_ROW + 1..).zip(StaffRole::ALL) {
+        let headcount = staff.headcount(role);
+        queue!(
+            writer,
+            cursor::MoveTo(0, row),
+            style::Print(format!(
+                "{:<18}{:>9}{:>7}{:>9}  {}",
+                role.name(),
+                headcount,
+                format!("${}", role.wage() * headcount as i32),
+                (headcount * role.floors_covered()).min(layers.len()),
+                role.duty()
+            )),
+        )?;
+    }
+    queue!(
+        writer,
+        cursor::MoveTo(0, TOP_ROW + 5),
+        style::Print(format!("Wages: ${} per tick", staff.wages())),
+        cursor::MoveTo(0, FLOORS_ROW),
+        style::Print("FLOOR  TYPE                CLEAN  SECURITY  MAINTENANCE  CONDITION"),
+    )?;
+
+    // The top floors are the first to go without, so show those
+    let rows = (TOWER_WINDOW_HEIGHT - FLOORS_ROW - 2) as usize;
+    let shown = layers.len().min(rows);
+    for (row, floor) in (FLOORS_ROW + 1..).zip((layers.len() - shown..layers.len()).rev()) {
+        let layer = &layers[floor];
+        let [cleaned, guarded, maintained] = StaffRole::ALL
+            .map(|role| if staff.covers(role, floor) { "yes" } else { "no" });
+        let mut condition = format!("{:.0}% clean", layer.cleanliness * 100.0);
+        if layer.broken_down {
+            condition += ", broken down";
+        }
+        let color = if layer.broken_down || layer.cleanliness < 0.5 {
+            Color::Red
+        } else {
+            Color::White
+        };
+        queue!(
+            writer,
+            cursor::MoveTo(0, row),
+            style::SetColors(Colors {
+                foreground: Some(color),
+                background: Some(Color::Black),
+            }),
+            style::Print(format!(
+                "{:>5}  {:<18.18}  {cleaned:<5}  {guarded:<8}  {maintained:<11}  {condition}",
+                floor + 1,
+                layer.floor_type().name,
+            )),
+        )?;
+    }
+    if layers.len() > shown {
+        queue!(
+            writer,
+            cursor::MoveTo(0, FLOORS_ROW + 1 + shown as u16),
+            style::SetColors(Colors {
+                foreground: Some(Color::DarkGrey),
+                background: Some(Color::Black),
+            }),
+            style::Print(format!("...and {} floors below", layers.len() - shown)),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::LayerType;
+    use crate::test_game::TestGame;
+    use crossterm::event::KeyCode;
+
+    #[test]
+    fn janitors_keep_their_floors_clean() {
+        let mut game = TestGame::new();
+        game.gs.funds = 1_000_000;
+        for _ in 0..4 {
+            game.build(LayerType::Food);
+        }
+        for layer in &mut game.gs.layers {
+            layer.occupancy = 20;
+        }
+        game.press(KeyCode::Char('S')).press(KeyCode::Char('j'));
+        for _ in 0..10 {
+            game.tick();
+        }
+
+        // One janitor looks after the bottom three floors
+        let layers = &game.gs.layers;
+        assert_eq!(layers[2].cleanliness, 1.0);
+        assert!(layers[3].cleanliness < 1.0);
+        let wages = StaffRole::Janitor.wage();
+        assert!(game.gs.frame.find(&format!("Janitors                  1     ${wages}")).is_some());
+    }
+
+    #[test]
+    fn staff_are_hired_up_to_what_the_tower_can_employ() {
+        let mut game = TestGame::new();
+        game.build(LayerType::Food);
+        let max_guards = StaffRole::Security.max_headcount(1);
+        game.gs.change_staff(StaffRole::Security, i32::MAX).unwrap();
+        assert_eq!(game.gs.staff.headcount(StaffRole::Security), max_guards);
+        game.gs.change_staff(StaffRole::Security, 1).unwrap();
+        assert_eq!(game.gs.staff.headcount(StaffRole::Security), max_guards);
+        assert!(game.gs.message.contains("can't employ more"), "{}", game.gs.message);
+        game.gs.change_staff(StaffRole::Security, i32::MIN).unwrap();
+        assert_eq!(game.gs.staff.headcount(StaffRole::Security), 0);
+    }
+
+    #[test]
+    fn every_breakdown_goes_in_the_log() {
+        let mut game = TestGame::new();
+        game.gs.funds = 1_000_000;
+        for _ in 0..40 {
+            game.build(LayerType::Food);
+        }
+        let (mut breakdowns, mut most_at_once) = (0, 0);
+        for _ in 0..20 {
+            let was_broken: Vec<bool> =
+                game.gs.layers.iter().map(|layer| layer.broken_down).collect();
+            game.tick();
+            let broke =
+                game.gs.layers.iter().zip(was_broken).filter(|(l, was)| l.broken_down && !was);
+            let count = broke.count();
+            breakdowns += count;
+            most_at_once = most_at_once.max(count);
+        }
+
+        assert!(most_at_once > 1, "no tick had more than one breakdown");
+        let logged = game.gs.message_log.lines().filter(|(_, line)| line.contains("broke down"));
+        assert_eq!(logged.count(), breakdowns);
+    }
+}
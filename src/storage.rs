@@ -0,0 +1,56 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Returns the directory user data (achievements, run history, ...) is kept
+/// in, creating it if needed.
+pub fn data_dir() -> io::Result<PathBuf> {
+    let dir = if let Some(dir) = env::var_os("XDG_DATA_HOME") {
+        PathBuf::from(dir).join("text_game")
+    } else if let Some(dir) = env::var_os("APPDATA") {
+        PathBuf::from(dir).join("text_game")
+    } else if let Some(home) = env::var_os("HOME") {
+        PathBuf::from(home).join(".local/share/text_game")
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not find a directory for user data",
+        ));
+    };
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Where a game keeps achievements, run history, saves, settings and the
+/// other files it reads and writes.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DataDir {
+    /// The user data directory, as found by `data_dir`.
+    User,
+    /// A directory of the game's own.
+    At(PathBuf),
+}
+
+impl DataDir {
+    /// `TEXT_GAME_DATA_DIR` if it is set, otherwise the user data directory.
+    pub fn from_env() -> Self {
+        env::var_os("TEXT_GAME_DATA_DIR").map_or(DataDir::User, |dir| DataDir::At(dir.into()))
+    }
+
+    /// The directory, created if needed.
+    pub fn path(&self) -> io::Result<PathBuf> {
+        match self {
+            DataDir::User => data_dir(),
+            DataDir::At(dir) => {
+                fs::create_dir_all(dir)?;
+                Ok(dir.clone())
+            }
+        }
+    }
+
+    /// The file called `name` in the directory.
+    pub fn file(&self, name: &str) -> io::Result<PathBuf> {
+        Ok(self.path()?.join(name))
+    }
+}
@@ -0,0 +1,174 @@
+use crate::layer::Layer;
+use crate::rules::{percent_of, Rules};
+use rand::Rng;
+
+/// Chance at each inspection of the tower being reassessed.
+const REASSESSMENT_CHANCE: f64 = 0.3;
+/// Penalty added to taxes left unpaid at an inspection, due at the next.
+const LATE_PENALTY_PERCENT: i32 = 25;
+/// Share of its assessed value a floor seized for back taxes is sold for.
+const SEIZURE_SALE_PERCENT: i32 = 50;
+
+/// What the tax office thinks of the tower and what it is still owed.
+#[derive(Clone, Debug)]
+pub struct Taxes {
+    /// The tower's assessed value as a percentage of what its floors cost
+    /// to build. Full, busy towers are worth more.
+    pub assessment_percent: i32,
+    /// Taxes and penalties left unpaid at the last inspection.
+    pub arrears: i32,
+}
+
+impl Default for Taxes {
+    fn default() -> Self {
+        Taxes {
+            assessment_percent: 100,
+            arrears: 0,
+        }
+    }
+}
+
+/// The taxes due at an inspection, one line per item.
+pub struct TaxBill {
+    pub items: Vec<(String, i32)>,
+    /// Whether the bill includes taxes left over from the last inspection,
+    /// in which case the city won't wait any longer.
+    pub overdue: bool,
+}
+
+impl TaxBill {
+    pub fn total(&self) -> i32 {
+        self.items.iter().fold(0, |total, (_, amount)| total.saturating_add(*amount))
+    }
+}
+
+impl Taxes {
+    /// What the tax office thinks `layer` is worth, going by what it costs
+    /// to build under `rules`.
+    pub fn assessed_value(&self, layer: &Layer, rules: &Rules) -> i32 {
+        percent_of(rules.price(layer.floor_type().price), self.assessment_percent)
+    }
+
+    /// Works out the taxes due at an inspection on a tower of `layers`
+    /// that took `revenue` in rent this period. The tower is sometimes
+    /// reassessed first, which is noted on the bill.
+    pub fn bill(
+        &mut self,
+        layers: &[Layer],
+        revenue: i32,
+        rules: &Rules,
+        rng: &mut impl Rng,
+    ) -> TaxBill {
+        let mut items = Vec::new();
+        if !layers.is_empty() && rng.gen_bool(REASSESSMENT_CHANCE) {
+            let occupancy: usize = layers.iter().map(|layer| layer.occupancy).sum();
+            let capacity: usize = layers.iter().map(|layer| layer.max_occupancy).sum();
+            let fullness = occupancy as f64 / capacity.max(1) as f64;
+            let old = self.assessment_percent;
+            self.assessment_percent = (60.0 + 80.0 * fullness).round() as i32;
+            items.push((
+                format!("Reassessed at {}% of build cost (was {old}%)", self.assessment_percent),
+                0,
+            ));
+        }
+
+        let value = layers
+            .iter()
+            .fold(0, |value: i32, layer| value.saturating_add(self.assessed_value(layer, rules)));
+        items.push((
+            format!("Property tax: {}% of ${value}", rules.property_tax_percent),
+            percent_of(value, rules.property_tax_percent),
+        ));
+        items.push((
+            format!("Income tax: {}% of ${revenue} rent", rules.income_tax_percent),
+            percent_of(revenue.max(0), rules.income_tax_percent),
+        ));
+        let overdue = self.arrears > 0;
+        if overdue {
+            let penalty = percent_of(self.arrears, LATE_PENALTY_PERCENT);
+            items.push((format!("Back taxes: ${}", self.arrears), self.arrears));
+            items.push((format!("Late penalty: {LATE_PENALTY_PERCENT}%"), penalty));
+        }
+        TaxBill { items, overdue }
+    }
+
+    /// What the city gets for `layer` when seizing it for back taxes.
+    pub fn seizure_sale(&self, layer: &Layer, rules: &Rules) -> i32 {
+        percent_of(self.assessed_value(layer, rules), SEIZURE_SALE_PERCENT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::{FloorType, FloorTypes, LayerType};
+    use crate::ledger::TransactionCategory;
+    use crate::test_game::TestGame;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn taxes_are_billed_at_inspection() {
+        let mut game = TestGame::new();
+        game.gs.funds = 100_000;
+        game.build(LayerType::Food);
+        game.gs.layers[0].occupancy = 20;
+        game.gs.debt_collection_timer = 1;
+        game.tick();
+
+        let gs = &game.gs;
+        let ledger = &gs.ledger;
+        let rent = ledger.period_total(0, TransactionCategory::Rent);
+        let value = gs.taxes.assessed_value(&gs.layers[0], &gs.rules);
+        let property = value * gs.rules.property_tax_percent / 100;
+        let income = rent * gs.rules.income_tax_percent / 100;
+        assert_eq!(ledger.period_total(0, TransactionCategory::Taxes), -(property + income));
+        assert_eq!(gs.taxes.arrears, 0);
+        assert!(gs.message_log.lines().any(|(_, line)| line.contains("TAX BILL")));
+        assert!(gs.frame.find("Taxes paid").is_some());
+    }
+
+    #[test]
+    fn taxes_on_the_dearest_floors_are_as_much_as_the_funds_hold() {
+        let game = TestGame::new();
+        let floor_types = game.gs.mods.floor_types();
+        let floor_types = FloorTypes::new(
+            floor_types
+                .all()
+                .map(|style| FloorType {
+                    price: i32::MAX,
+                    ..floor_types.get(style).clone()
+                })
+                .collect(),
+        );
+        let layers: Vec<_> =
+            (0..3).map(|_| Layer::new(&floor_types, LayerType::Food, 10, 20)).collect();
+        let mut rules = Rules::default();
+        rules.set("price_percent", 1_000.0).unwrap();
+        rules.set("property_tax_percent", 100.0).unwrap();
+        let mut taxes = Taxes {
+            assessment_percent: 140,
+            arrears: i32::MAX,
+        };
+
+        let bill = taxes.bill(&layers, i32::MAX, &rules, &mut StdRng::seed_from_u64(0));
+        assert!(bill.items.iter().all(|&(_, amount)| amount >= 0), "{:?}", bill.items);
+        assert_eq!(bill.total(), i32::MAX);
+        assert_eq!(taxes.seizure_sale(&layers[0], &rules), i32::MAX / 2);
+    }
+
+    #[test]
+    fn unpaid_back_taxes_cost_floors_not_the_game() {
+        let mut game = TestGame::new();
+        game.gs.funds = 1_000_000;
+        game.build(LayerType::Food).build(LayerType::Food);
+        game.gs.funds = 0;
+        game.gs.taxes.arrears = 5_000;
+        game.gs.debt_collection_timer = 1;
+        game.tick();
+
+        assert!(!game.gs.game_over);
+        assert!(game.gs.layers.len() < 2);
+        assert!(game.gs.message_log.lines().any(|(_, line)| line.contains("seized floor 2")));
+    }
+}
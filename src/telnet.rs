@@ -0,0 +1,468 @@
+use crate::mods::Mods;
+use crate::storage::DataDir;
+use crate::{WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, execute, terminal};
+use futures::channel::mpsc::{self, Sender};
+use futures::prelude::*;
+use std::cell::Cell;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::rc::Rc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::{self, LocalSet};
+
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0:2323";
+/// Most games served at once, so one box can't be swamped.
+const MAX_SESSIONS: usize = 16;
+/// Most frames waiting to be sent to a client before it is given up on as
+/// too slow to keep up.
+const MAX_QUEUED_FRAMES: usize = 64;
+
+// Telnet commands and the options we use, from RFC 854, 857, 858 and 1073
+const IAC: u8 = 255;
+const DONT: u8 = 254;
+const DO: u8 = 253;
+const WONT: u8 = 252;
+const WILL: u8 = 251;
+const SB: u8 = 250;
+/// Interrupt process, which some clients send for Ctrl-C.
+const IP: u8 = 244;
+const SE: u8 = 240;
+const ECHO: u8 = 1;
+const SUPPRESS_GO_AHEAD: u8 = 3;
+/// Negotiate about window size.
+const NAWS: u8 = 31;
+
+/// What we ask of every client: we do the echoing (that is, none), send
+/// keys as they are pressed rather than a line at a time, and tell us the
+/// window size whenever it changes.
+pub const NEGOTIATION: [u8; 9] =
+    [IAC, WILL, ECHO, IAC, WILL, SUPPRESS_GO_AHEAD, IAC, DO, NAWS];
+
+/// Where `Telnet` is in the byte stream.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum State {
+    Data,
+    /// After an IAC.
+    Command,
+    /// After an IAC and WILL, WONT, DO or DONT, waiting for the option.
+    Option(u8),
+    /// Inside a subnegotiation.
+    Sub,
+    /// After an IAC inside a subnegotiation.
+    SubCommand,
+}
+
+/// Turns what a telnet client sends into the events the game plays on,
+/// answering whatever it asks along the way.
+pub struct Telnet {
+    state: State,
+    /// Keys typed since the last call to `feed`.
+    data: Vec<u8>,
+    /// The subnegotiation being read.
+    sub: Vec<u8>,
+    /// What to send back to the client.
+    pub replies: Vec<u8>,
+}
+
+impl Telnet {
+    pub fn new() -> Self {
+        Telnet {
+            state: State::Data,
+            data: Vec::new(),
+            sub: Vec::new(),
+            replies: Vec::new(),
+        }
+    }
+
+    /// The key presses and window size changes in `bytes`.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<Event> {
+        let mut events = Vec::new();
+        for &byte in bytes {
+            self.state = match (self.state, byte) {
+                (State::Data, IAC) => State::Command,
+                (State::Data, byte) => {
+                    self.data.push(byte);
+                    State::Data
+                }
+                (State::Command, IAC) => {
+                    self.data.push(IAC);
+                    State::Data
+                }
+                (State::Command, IP) => {
+                    events.extend(self.keys());
+                    let ctrl_c = KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL);
+                    events.push(Event::Key(ctrl_c));
+                    State::Data
+                }
+                (State::Command, WILL | WONT | DO | DONT) => State::Option(byte),
+                (State::Command, SB) => {
+                    self.sub.clear();
+                    State::Sub
+                }
+                (State::Command, _) => State::Data,
+                (State::Option(command), option) => {
+                    self.answer(command, option);
+                    State::Data
+                }
+                (State::Sub, IAC) => State::SubCommand,
+                (State::Sub, byte) | (State::SubCommand, byte @ IAC) => {
+                    self.sub.push(byte);
+                    State::Sub
+                }
+                (State::SubCommand, SE) => {
+                    if let [NAWS, w1, w2, h1, h2] = self.sub[..] {
+                        events.extend(self.keys());
+                        let size = (u16::from_be_bytes([w1, w2]), u16::from_be_bytes([h1, h2]));
+                        events.push(Event::Resize(size.0, size.1));
+                    }
+                    State::Data
+                }
+                (State::SubCommand, _) => State::Data,
+            };
+        }
+        events.extend(self.keys());
+        events
+    }
+
+    /// Agrees to what we asked for and turns down everything else.
+    fn answer(&mut self, command: u8, option: u8) {
+        let ours = match command {
+            DO => [ECHO, SUPPRESS_GO_AHEAD].contains(&option),
+            WILL => option == NAWS,
+            // Nothing to say to being told what won't happen
+            _ => return,
+        };
+        if !ours {
+            let refusal = if command == DO { WONT } else { DONT };
+            self.replies.extend([IAC, refusal, option]);
+        }
+    }
+
+    /// The keys typed since last time. An escape sequence split across two
+    /// reads comes out as Esc followed by its characters, but terminals
+    /// send each key in one go.
+    fn keys(&mut self) -> Vec<Event> {
+        let text = String::from_utf8_lossy(&self.data).into_owned();
+        self.data.clear();
+        let mut keys = Vec::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let (code, modifiers) = match c {
+                // Telnet ends lines with CR LF or CR NUL
+                '\r' => {
+                    chars.next_if(|&c| c == '\n' || c == '\0');
+                    (KeyCode::Enter, KeyModifiers::NONE)
+                }
+                '\n' => (KeyCode::Enter, KeyModifiers::NONE),
+                '\t' => (KeyCode::Tab, KeyModifiers::NONE),
+                '\x7f' | '\x08' => (KeyCode::Backspace, KeyModifiers::NONE),
+                '\x1b' => match chars.next() {
+                    Some('[' | 'O') => {
+                        let mut sequence = String::new();
+                        for c in chars.by_ref() {
+                            sequence.push(c);
+                            if ('@'..='~').contains(&c) && c != '[' {
+                                break;
+                            }
+                        }
+                        match escape_sequence(&sequence) {
+                            Some(code) => (code, KeyModifiers::NONE),
+                            None => continue,
+                        }
+                    }
+                    Some(c) => (KeyCode::Char(c), KeyModifiers::ALT),
+                    None => (KeyCode::Esc, KeyModifiers::NONE),
+                },
+                '\x01'..='\x1a' => {
+                    let letter = (b'a' + c as u8 - 1) as char;
+                    (KeyCode::Char(letter), KeyModifiers::CONTROL)
+                }
+                c if c.is_control() => continue,
+                c => (KeyCode::Char(c), KeyModifiers::NONE),
+            };
+            keys.push(Event::Key(KeyEvent::new(code, modifiers)));
+        }
+        keys
+    }
+}
+
+/// The key sent as `ESC [` or `ESC O` followed by `sequence`.
+fn escape_sequence(sequence: &str) -> Option<KeyCode> {
+    let last = sequence.chars().last()?;
+    let number = &sequence[..sequence.len() - last.len_utf8()];
+    let code = match last {
+        'A' => KeyCode::Up,
+        'B' => KeyCode::Down,
+        'C' => KeyCode::Right,
+        'D' => KeyCode::Left,
+        'H' => KeyCode::Home,
+        'F' => KeyCode::End,
+        'Z' => KeyCode::BackTab,
+        'P' => KeyCode::F(1),
+        'Q' => KeyCode::F(2),
+        'R' => KeyCode::F(3),
+        'S' => KeyCode::F(4),
+        '~' => match number.split(';').next()?.parse::<u8>().ok()? {
+            1 | 7 => KeyCode::Home,
+            2 => KeyCode::Insert,
+            3 => KeyCode::Delete,
+            4 | 8 => KeyCode::End,
+            5 => KeyCode::PageUp,
+            6 => KeyCode::PageDown,
+            n @ 11..=15 => KeyCode::F(n - 10),
+            n @ 17..=21 => KeyCode::F(n - 11),
+            n @ 23..=24 => KeyCode::F(n - 12),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Sends what the game draws to a client, in one message per frame, except
+/// while its window is too small to show the game. A client that falls too
+/// far behind ends the game with an error.
+struct ClientWriter {
+    buffer: Vec<u8>,
+    sender: Sender<Vec<u8>>,
+    fits: Rc<Cell<bool>>,
+}
+
+impl Write for ClientWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let bytes = std::mem::take(&mut self.buffer);
+        if self.fits.get() && !bytes.is_empty() {
+            match self.sender.try_send(bytes) {
+                Err(e) if e.is_full() => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "too slow to keep up"));
+                }
+                // The connection closing ends the game soon enough
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Asks the player for a bigger window.
+fn too_small(width: u16, height: u16) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    execute!(
+        bytes,
+        style::SetColors(Colors::new(Color::White, Color::Black)),
+        terminal::Clear(terminal::ClearType::All),
+        cursor::MoveTo(0, 0),
+        style::Print(format!(
+            "Make your window at least {WINDOW_WIDTH}x{WINDOW_HEIGHT} to play. \
+             It is {width}x{height}."
+        )),
+    )?;
+    Ok(bytes)
+}
+
+/// Plays a game of its own with the client on `stream`, keeping its files
+/// in `data_dir`, until they quit or hang up.
+pub async fn session(stream: TcpStream, mods: Rc<Mods>, data_dir: DataDir) -> io::Result<()> {
+    let _ = stream.set_nodelay(true);
+    let (mut reader, mut writer) = stream.into_split();
+    let (mut output, mut outgoing) = mpsc::channel::<Vec<u8>>(MAX_QUEUED_FRAMES);
+    let (events, event_stream) = mpsc::unbounded::<io::Result<Event>>();
+    // Until the client says how big its window is, hope for the best
+    let fits = Rc::new(Cell::new(true));
+
+    let sending = task::spawn_local(async move {
+        while let Some(bytes) = outgoing.next().await {
+            writer.write_all(&bytes).await?;
+        }
+        writer.shutdown().await
+    });
+
+    let mut setup = NEGOTIATION.to_vec();
+    execute!(
+        setup,
+        terminal::EnterAlternateScreen,
+        terminal::Clear(terminal::ClearType::All),
+        terminal::DisableLineWrap,
+        cursor::Hide,
+    )?;
+    let _ = output.try_send(setup);
+
+    let (mut input_output, input_fits) = (output.clone(), Rc::clone(&fits));
+    let receiving = task::spawn_local(async move {
+        let mut telnet = Telnet::new();
+        let mut buffer = [0; 1024];
+        // A read error means the client is gone, same as hanging up
+        while let Ok(n @ 1..) = reader.read(&mut buffer).await {
+            let decoded = telnet.feed(&buffer[..n]);
+            let replies = std::mem::take(&mut telnet.replies);
+            // A client too slow for these is soon too slow for the game
+            if !replies.is_empty() {
+                let _ = input_output.try_send(replies);
+            }
+            for event in decoded {
+                if let Event::Resize(width, height) = event {
+                    let fits = width >= WINDOW_WIDTH && height >= WINDOW_HEIGHT;
+                    input_fits.set(fits);
+                    if !fits {
+                        let _ = input_output.try_send(too_small(width, height)?);
+                    }
+                }
+                if events.unbounded_send(Ok(event)).is_err() {
+                    return io::Result::Ok(());
+                }
+            }
+        }
+        Ok(())
+    });
+
+    let out = ClientWriter {
+        buffer: Vec::new(),
+        sender: output.clone(),
+        fits,
+    };
+    let result = crate::play(out, mods, data_dir, event_stream).await;
+    receiving.abort();
+
+    let mut teardown = Vec::new();
+    execute!(teardown, terminal::LeaveAlternateScreen, cursor::Show)?;
+    let _ = output.try_send(teardown);
+    drop(output);
+    // The client may already be gone, in which case there's no one to tell
+    let _ = sending.await;
+    result
+}
+
+/// Serves a game of its own to everyone who connects to `address`, until
+/// the server is stopped. Each game keeps its files in a directory of its
+/// own, removed when the player leaves.
+pub async fn serve(mods: Rc<Mods>, address: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(address).await?;
+    eprintln!("Serving games on {}. Press Ctrl-C to stop.", listener.local_addr()?);
+    let sessions = Rc::new(Cell::new(0));
+    let mut next_session = 0;
+    LocalSet::new()
+        .run_until(async move {
+            loop {
+                let (mut stream, peer) = listener.accept().await?;
+                if sessions.get() >= MAX_SESSIONS {
+                    let _ = stream.write_all(b"The server is full. Try again later.\r\n").await;
+                    continue;
+                }
+                eprintln!("{peer} connected");
+                sessions.set(sessions.get() + 1);
+                let (mods, sessions) = (Rc::clone(&mods), Rc::clone(&sessions));
+                let dir = env::temp_dir()
+                    .join(format!("text_game_session_{}_{next_session}", std::process::id()));
+                next_session += 1;
+                task::spawn_local(async move {
+                    match session(stream, mods, DataDir::At(dir.clone())).await {
+                        Ok(()) => eprintln!("{peer} left"),
+                        Err(e) => eprintln!("{peer} left: {e}"),
+                    }
+                    let _ = fs::remove_dir_all(dir);
+                    sessions.set(sessions.get() - 1);
+                });
+            }
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::FrameBuffer;
+    use crate::telnet;
+    use crate::test_game::TestGame;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent::new(code, modifiers))
+    }
+
+    #[test]
+    fn decodes_keys() {
+        let mut telnet = Telnet::new();
+        let none = KeyModifiers::NONE;
+        assert_eq!(
+            telnet.feed(b"b\r\0\r\n\x1b[A\x1b[6~\x1bOP\x02\x7f\x1b"),
+            vec![
+                key(KeyCode::Char('b'), none),
+                key(KeyCode::Enter, none),
+                key(KeyCode::Enter, none),
+                key(KeyCode::Up, none),
+                key(KeyCode::PageDown, none),
+                key(KeyCode::F(1), none),
+                key(KeyCode::Char('b'), KeyModifiers::CONTROL),
+                key(KeyCode::Backspace, none),
+                key(KeyCode::Esc, none),
+            ]
+        );
+        assert_eq!(telnet.feed("é".as_bytes()), vec![key(KeyCode::Char('é'), none)]);
+        // Cut short or unknown, so ignored
+        assert_eq!(telnet.feed(b"\x1b["), vec![]);
+        assert_eq!(telnet.feed("\x1b[1é".as_bytes()), vec![]);
+    }
+
+    #[test]
+    fn negotiates_window_size_and_turns_down_other_options() {
+        let mut telnet = Telnet::new();
+        let bytes = [
+            IAC, WILL, NAWS, IAC, SB, NAWS, 0, 132, 0, 40, IAC, SE, b'x', IAC, DO, ECHO, IAC,
+            WILL, 24, IAC, DO, 5,
+        ];
+        assert_eq!(
+            telnet.feed(&bytes),
+            vec![Event::Resize(132, 40), key(KeyCode::Char('x'), KeyModifiers::NONE)]
+        );
+        assert_eq!(telnet.replies, [IAC, DONT, 24, IAC, WONT, 5]);
+
+        // A width of 255 is doubled up so it isn't taken for an IAC
+        let bytes = [IAC, SB, NAWS, 0, IAC, IAC, 0, 30, IAC, SE, IAC, IP];
+        assert_eq!(
+            telnet.feed(&bytes),
+            vec![Event::Resize(255, 30), key(KeyCode::Char('c'), KeyModifiers::CONTROL)]
+        );
+    }
+
+    #[tokio::test]
+    async fn telnet_client_plays_a_game_of_its_own() {
+        let game = TestGame::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+        let mods = Rc::clone(&game.gs.mods);
+
+        let local = tokio::task::LocalSet::new();
+        let session = local.spawn_local(telnet::session(stream, mods, game.gs.data_dir.clone()));
+        let received = local
+            .run_until(async {
+                // Too small at first, then big enough, then Normal rules and
+                // Ctrl-C
+                client.write_all(&[255, 250, 31, 0, 80, 0, 24, 255, 240]).await.unwrap();
+                client.write_all(&[255, 250, 31, 0, 120, 0, 30, 255, 240]).await.unwrap();
+                client.write_all(b"2\x03").await.unwrap();
+                let mut received = Vec::new();
+                client.read_to_end(&mut received).await.unwrap();
+                session.await.unwrap().unwrap();
+                received
+            })
+            .await;
+
+        assert_eq!(received[..telnet::NEGOTIATION.len()], telnet::NEGOTIATION);
+        let text = String::from_utf8_lossy(&received);
+        assert!(text.contains("Make your window at least 120x30 to play. It is 80x24."));
+        let mut screen = FrameBuffer::new();
+        io::Write::write_all(&mut screen, &received[telnet::NEGOTIATION.len()..]).unwrap();
+        assert!(screen.find("NEW GAME").is_none(), "{}", screen.text());
+        assert!(screen.find("b: Build").is_some(), "{}", screen.text());
+    }
+}
@@ -0,0 +1,118 @@
+//! A game to play in tests, without a terminal and with a data directory
+//! of its own.
+
+use crate::frame::FrameBuffer;
+use crate::game_state::{init_game_state, GameState};
+use crate::layer::{FloorType, LayerType};
+use crate::mods::Mods;
+use crate::rules::Rules;
+use crate::storage::DataDir;
+use crate::versus::Versus;
+use crate::weather::{Calendar, Weather};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static NEXT_DATA_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary data directory, removed when dropped.
+pub struct TempDataDir(pub PathBuf);
+
+impl TempDataDir {
+    pub fn new() -> Self {
+        let dir = env::temp_dir().join(format!(
+            "text_game_test_{}_{}",
+            std::process::id(),
+            NEXT_DATA_DIR.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        TempDataDir(dir)
+    }
+}
+
+impl Drop for TempDataDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A new game with no saved achievements, history or settings, drawn as it
+/// is when the game starts.
+pub struct TestGame {
+    pub gs: GameState,
+    pub data_dir: TempDataDir,
+}
+
+impl TestGame {
+    pub fn new() -> Self {
+        TestGame::with_data_files(&[])
+    }
+
+    /// A new game with `files`, given as paths in the data directory and
+    /// their contents, already saved.
+    pub fn with_data_files(files: &[(&str, &str)]) -> Self {
+        let data_dir = TempDataDir::new();
+        for (path, contents) in files {
+            let path = data_dir.0.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        let mods = Rc::new(Mods::from_sources(vec![], vec![]));
+        let dir = DataDir::At(data_dir.0.clone());
+        let mut gs = init_game_state(FrameBuffer::new(), mods, Rules::default(), dir);
+        gs.seed = 0;
+        gs.rng = StdRng::seed_from_u64(0);
+        gs.calendar = Calendar::restore(0, &[Weather::Sunny; 4], &mut gs.rng);
+        gs.draw_command_pool().unwrap();
+        gs.draw_tower().unwrap();
+        TestGame { gs, data_dir }
+    }
+
+    pub fn press(&mut self, code: KeyCode) -> &mut Self {
+        crate::handle_key_event(&mut self.gs, KeyEvent::new(code, KeyModifiers::NONE)).unwrap();
+        self
+    }
+
+    pub fn type_keys(&mut self, keys: &str) -> &mut Self {
+        for c in keys.chars() {
+            self.press(KeyCode::Char(c));
+        }
+        self
+    }
+
+    pub fn tick(&mut self) -> &mut Self {
+        crate::handle_time_tick(&mut self.gs).unwrap();
+        self
+    }
+
+    pub fn floor_type(&self, style: LayerType) -> &FloorType {
+        self.gs.mods.floor_types().get(style)
+    }
+
+    pub fn build(&mut self, style: LayerType) -> &mut Self {
+        let key = self.floor_type(style).key;
+        self.press(KeyCode::Char('b')).press(KeyCode::Char(key))
+    }
+}
+
+/// A versus game, with the data directory of `game` to itself.
+pub fn versus(game: &TestGame, collections: u32) -> Versus {
+    let mods = Rc::clone(&game.gs.mods);
+    let mut versus = Versus::new(mods, Rules::default(), collections, game.gs.data_dir.clone());
+    for gs in &mut versus.players {
+        gs.seed = 0;
+        gs.rng = StdRng::seed_from_u64(0);
+        gs.calendar = Calendar::restore(0, &[Weather::Sunny; 4], &mut gs.rng);
+    }
+    versus
+}
+
+pub fn press_versus(versus: &mut Versus, code: KeyCode) {
+    versus.handle_key(KeyEvent::new(code, KeyModifiers::NONE)).unwrap();
+}
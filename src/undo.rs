@@ -0,0 +1,206 @@
+use crate::action::Action;
+use crate::layer::Layer;
+use std::collections::VecDeque;
+
+/// What it takes to put things back the way they were before an action.
+pub enum Reversal {
+    /// Remove the floor built on top of the tower and refund the `cost`
+    /// paid for it.
+    Build { cost: i32 },
+    /// Put a demolished floor back where it was, with the occupants it had
+    /// if they are still in the city.
+    Demolish { floor: usize, layer: Layer },
+    /// Set the rent on a floor back to what it was.
+    SetRent { floor: usize, rent: usize },
+    /// Pay back a loan straight away.
+    TakeLoan(i32),
+}
+
+pub struct UndoEntry {
+    pub action: Action,
+    pub reversal: Reversal,
+    pub tick: u64,
+    pub period: u32,
+    /// The tower's star rating when the action was done.
+    pub stars: u8,
+}
+
+/// Player actions that can still be undone, and undone actions that can be
+/// redone.
+///
+/// An action can only be undone in the debt collection period it was done
+/// in and for `grace_ticks` ticks afterwards, so undo can't be used to get
+/// money back after the debt collector has been, or to rewrite the past.
+/// Nor can it be undone once the tower has earned another star, since stars
+/// and what they unlock are never taken away.
+pub struct UndoHistory {
+    undo: VecDeque<UndoEntry>,
+    redo: Vec<Action>,
+    depth: usize,
+    grace_ticks: u64,
+}
+
+impl UndoHistory {
+    pub fn new(depth: usize, grace_ticks: u64) -> Self {
+        UndoHistory {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            depth,
+            grace_ticks,
+        }
+    }
+
+    pub fn record(&mut self, entry: UndoEntry) {
+        self.undo.push_back(entry);
+        while self.undo.len() > self.depth {
+            self.undo.pop_front();
+        }
+    }
+
+    fn can_undo(&self, entry: &UndoEntry, tick: u64, period: u32, stars: u8) -> bool {
+        entry.period == period && tick - entry.tick <= self.grace_ticks && entry.stars == stars
+    }
+
+    /// Takes the most recent action off the history if it can still be
+    /// undone, or returns why not.
+    pub fn pop_undo(
+        &mut self,
+        tick: u64,
+        period: u32,
+        stars: u8,
+    ) -> Result<UndoEntry, &'static str> {
+        match self.undo.back() {
+            None => Err("Nothing to undo"),
+            Some(entry) if entry.stars != stars => {
+                Err("The tower has earned a star since then, so that can't be undone")
+            }
+            Some(entry) if !self.can_undo(entry, tick, period, stars) => {
+                Err("Too late to undo that")
+            }
+            Some(_) => Ok(self.undo.pop_back().unwrap()),
+        }
+    }
+
+    /// Forgets actions that can no longer be undone.
+    pub fn expire(&mut self, tick: u64, period: u32, stars: u8) {
+        while let Some(entry) = self.undo.front() {
+            if self.can_undo(entry, tick, period, stars) {
+                break;
+            }
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn push_redo(&mut self, action: Action) {
+        self.redo.push(action);
+    }
+
+    pub fn pop_redo(&mut self) -> Option<Action> {
+        self.redo.pop()
+    }
+
+    /// Forgets everything, e.g. when another game is loaded.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Called when the player does something new, which makes the undone
+    /// actions meaningless to redo.
+    pub fn clear_redo(&mut self) {
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::LayerType;
+    use crate::test_game::TestGame;
+    use crossterm::event::KeyCode;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn actions_can_only_be_undone_for_a_few_ticks() {
+        let mut game = TestGame::new();
+        game.gs.undo_history = UndoHistory::new(10, 2);
+        game.build(LayerType::Food).tick().tick();
+        game.press(KeyCode::Char('u'));
+        assert!(game.gs.layers.is_empty(), "{}", game.gs.message);
+
+        game.build(LayerType::Food).tick().tick().tick();
+        game.press(KeyCode::Char('u'));
+        assert_eq!(game.gs.layers.len(), 1);
+        assert_eq!(game.gs.message, "Nothing to undo", "the expired build was kept");
+    }
+
+    #[test]
+    fn actions_cannot_be_undone_after_the_debt_collector_has_been() {
+        let mut game = TestGame::new();
+        game.build(LayerType::Food);
+        game.gs.period += 1;
+        game.press(KeyCode::Char('u'));
+        assert_eq!(game.gs.layers.len(), 1);
+        assert_eq!(game.gs.message, "Too late to undo that");
+    }
+
+    #[test]
+    fn builds_cannot_be_undone_once_they_earned_a_star() {
+        let mut game = TestGame::new();
+        game.build(LayerType::Food);
+        game.gs.stars += 1;
+        game.press(KeyCode::Char('u'));
+        assert_eq!(game.gs.layers.len(), 1);
+        assert!(game.gs.message.contains("earned a star"), "{}", game.gs.message);
+    }
+
+    #[test]
+    fn undone_actions_can_be_redone() {
+        let mut game = TestGame::new();
+        let funds = game.gs.funds;
+        game.build(LayerType::Food).build(LayerType::Retail);
+        let cost = funds - game.gs.funds;
+        game.press(KeyCode::Char('u')).press(KeyCode::Char('u'));
+        assert_eq!(game.gs.funds, funds);
+        game.gs.redo().unwrap();
+        game.gs.redo().unwrap();
+        assert_eq!(game.gs.layers[1].style, LayerType::Retail);
+        assert_eq!(game.gs.funds, funds - cost);
+        game.gs.redo().unwrap();
+        assert_eq!(game.gs.message, "Nothing to redo");
+    }
+
+    #[test]
+    fn tenants_of_floors_taken_away_go_back_to_the_city() {
+        let mut game = TestGame::new();
+        let pool = Rc::new(Cell::new(0));
+        game.gs.tenant_pool = Some(Rc::clone(&pool));
+        game.build(LayerType::Food).build(LayerType::Food);
+        game.gs.layers[0].occupancy = 10;
+        game.gs.layers[1].occupancy = 5;
+
+        Action::Demolish(0).perform(&mut game.gs).unwrap();
+        assert_eq!(pool.get(), 10);
+        // Only those who haven't moved into another tower come back
+        pool.set(4);
+        game.gs.undo().unwrap();
+        assert_eq!((game.gs.layers[0].occupancy, pool.get()), (4, 0));
+
+        game.gs.undo().unwrap();
+        assert_eq!(game.gs.layers.len(), 1);
+        assert_eq!(pool.get(), 5);
+    }
+
+    #[test]
+    fn undoing_a_build_refunds_what_it_cost() {
+        let mut game = TestGame::new();
+        let funds = game.gs.funds;
+        game.build(LayerType::Retail);
+        // A mod changing the prices afterwards doesn't change the refund
+        game.gs.rules.price_percent = 200;
+        game.press(KeyCode::Char('u'));
+        assert!(game.gs.layers.is_empty());
+        assert_eq!(game.gs.funds, funds);
+    }
+}
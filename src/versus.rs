@@ -0,0 +1,517 @@
+use crate::action::Action;
+use crate::command::*;
+use crate::frame::FrameBuffer;
+use crate::game_state::{init_game_state, GameState, LOAN_AMOUNT};
+use crate::help::wrap;
+use crate::keymap::{KeyBinding, Keymap};
+use crate::layer::{FloorTypes, TowerView};
+use crate::mods::Mods;
+use crate::rating::stars_text;
+use crate::rules::Rules;
+use crate::storage::DataDir;
+use crate::{TOWER_WINDOW_HEIGHT, WINDOW_HEIGHT, WINDOW_WIDTH};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, execute, queue, terminal};
+use futures::prelude::*;
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::pin::pin;
+use std::rc::Rc;
+use std::time::Duration;
+
+/// Debt collections played before the richer player wins, unless someone
+/// goes bankrupt first.
+pub const DEFAULT_COLLECTIONS: u32 = 5;
+/// People in the city the two towers compete for.
+const CITY_POPULATION: usize = 300;
+/// Columns of the screen each player gets.
+const HALF_WIDTH: u16 = WINDOW_WIDTH / 2;
+/// Columns of a player's frame copied across: the tower and its minimap.
+const TOWER_WIDTH: u16 = 35;
+const PANEL_WIDTH: usize = (HALF_WIDTH - TOWER_WIDTH - 1) as usize;
+const PLAYER_COLORS: [Color; 2] = [Color::Cyan, Color::Magenta];
+
+/// How a versus game ended.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    /// The player went bankrupt, so the other one won.
+    Bankrupt(usize),
+    /// The player was the richer one after the last collection.
+    Richer(usize),
+    Tie,
+}
+
+impl Outcome {
+    fn message(self) -> String {
+        match self {
+            Outcome::Bankrupt(player) => format!(
+                "PLAYER {} WENT BANKRUPT. PLAYER {} WINS!",
+                player + 1,
+                2 - player
+            ),
+            Outcome::Richer(player) => {
+                format!("PLAYER {} IS THE RICHER TYCOON AND WINS!", player + 1)
+            }
+            Outcome::Tie => "IT'S A TIE!".to_string(),
+        }
+    }
+}
+
+/// Two players on one terminal, each with a tower, funds and debt collector
+/// of their own, racing on the same clock for the same city's tenants.
+///
+/// Each player's game draws into a frame of its own, and the towers are
+/// copied from those side by side.
+pub struct Versus {
+    pub players: [GameState; 2],
+    /// Each player's controls, from the `player1` and `player2` menus of the
+    /// key bindings.
+    controls: CommandPoolArray,
+    control_ids: [CommandPoolId; 2],
+    pub frame: FrameBuffer,
+    /// Collections played before the richer player wins.
+    collections: u32,
+    /// People in the city who haven't moved in anywhere.
+    tenants: Rc<Cell<usize>>,
+    pub outcome: Option<Outcome>,
+    pub running: bool,
+    /// Player whose keys are pressed on another terminal and arrive as
+    /// actions, so they are ignored here.
+    pub remote_player: Option<usize>,
+    /// Why the game is on hold, shown in place of the status line. The
+    /// clock stops until it is cleared.
+    pub notice: Option<String>,
+}
+
+impl Versus {
+    pub fn new(mods: Rc<Mods>, rules: Rules, collections: u32, data_dir: DataDir) -> Self {
+        let tenants = Rc::new(Cell::new(CITY_POPULATION));
+        let players = [0, 1].map(|_| {
+            let (mods, rules, data_dir) = (Rc::clone(&mods), rules.clone(), data_dir.clone());
+            let mut gs = init_game_state(FrameBuffer::new(), mods, rules, data_dir);
+            gs.tower_view = TowerView::Compact;
+            gs.tenant_pool = Some(Rc::clone(&tenants));
+            gs
+        });
+
+        let mut builder = CommandPoolArrayBuilder::new(Keymap::load(&data_dir));
+        let player1 = builder.add_pool(player_controls(0, &rules, mods.floor_types()));
+        let player2 = builder.add_pool(player_controls(1, &rules, mods.floor_types()));
+        let mut versus = Versus {
+            players,
+            controls: builder.with_initial_pool(player1),
+            control_ids: [player1, player2],
+            frame: FrameBuffer::new(),
+            collections,
+            tenants,
+            outcome: None,
+            running: true,
+            remote_player: None,
+            notice: None,
+        };
+        versus.sync_weather();
+        versus
+    }
+
+    /// Sends a key press to whichever player it belongs to.
+    pub fn handle_key(&mut self, ke: KeyEvent) -> io::Result<()> {
+        let quit = ke.code == KeyCode::Esc
+            || (ke.code == KeyCode::Char('c') && ke.modifiers == KeyModifiers::CONTROL);
+        if quit {
+            self.running = false;
+            return Ok(());
+        }
+        if self.outcome.is_some() || ke.kind == KeyEventKind::Release {
+            return Ok(());
+        }
+        for (player, id) in self.control_ids.into_iter().enumerate() {
+            if self.remote_player == Some(player) {
+                continue;
+            }
+            let Some(command) = self.controls.get(id).get_command(ke.code, ke.modifiers) else {
+                continue;
+            };
+            if ke.kind == KeyEventKind::Press || command.is_triggered_on_repeat() {
+                command.execute(&mut self.players[player])?;
+            }
+            break;
+        }
+        self.draw()
+    }
+
+    /// Performs one of the actions in `player`'s controls, named as in the
+    /// key bindings. Unknown actions are ignored.
+    pub fn perform(&mut self, player: usize, action: &str) -> io::Result<()> {
+        if self.outcome.is_some() {
+            return Ok(());
+        }
+        let pool = self.controls.get(self.control_ids[player]);
+        if let Some(command) = pool.command_for_action(action) {
+            command.execute(&mut self.players[player])?;
+        }
+        self.draw()
+    }
+
+    /// Moves both games on a tick, then sees if either player has won.
+    pub fn tick(&mut self) -> io::Result<()> {
+        if self.outcome.is_some() || self.notice.is_some() {
+            return Ok(());
+        }
+        self.sync_weather();
+        // Take turns at getting first pick of the city's tenants
+        let first = (self.players[0].tick % 2) as usize;
+        for player in [first, 1 - first] {
+            crate::handle_time_tick(&mut self.players[player])?;
+        }
+        self.outcome = self.check_outcome();
+        self.draw()
+    }
+
+    pub fn animate(&mut self) -> io::Result<()> {
+        for gs in &mut self.players {
+            gs.animate()?;
+        }
+        self.draw()
+    }
+
+    /// Both towers share the same sky.
+    fn sync_weather(&mut self) {
+        self.players[1].calendar = self.players[0].calendar.clone();
+    }
+
+    fn check_outcome(&self) -> Option<Outcome> {
+        match [0, 1].map(|player| self.players[player].game_over) {
+            [true, true] => return Some(Outcome::Tie),
+            [true, false] => return Some(Outcome::Bankrupt(0)),
+            [false, true] => return Some(Outcome::Bankrupt(1)),
+            [false, false] => {}
+        }
+        if self.players.iter().any(|gs| gs.period < self.collections) {
+            return None;
+        }
+        let [a, b] = [0, 1].map(|player| net_worth(&self.players[player]));
+        Some(match a.cmp(&b) {
+            std::cmp::Ordering::Greater => Outcome::Richer(0),
+            std::cmp::Ordering::Less => Outcome::Richer(1),
+            std::cmp::Ordering::Equal => Outcome::Tie,
+        })
+    }
+
+    pub fn draw(&mut self) -> io::Result<()> {
+        queue!(
+            self.frame,
+            style::SetColors(Colors::new(Color::White, Color::Black)),
+            terminal::Clear(terminal::ClearType::All),
+        )?;
+        for player in 0..2 {
+            let left = player as u16 * HALF_WIDTH;
+            self.players[player].draw_tower()?;
+            let gs = &self.players[player];
+            self.frame.copy_from(&gs.frame, (0, 0), (TOWER_WIDTH, TOWER_WINDOW_HEIGHT), (left, 0));
+            self.draw_panel(player, left + TOWER_WIDTH)?;
+            self.draw_controls(player, left)?;
+        }
+        for row in 0..WINDOW_HEIGHT {
+            queue!(
+                self.frame,
+                cursor::MoveTo(HALF_WIDTH - 1, row),
+                style::SetColors(Colors::new(Color::DarkGrey, Color::Black)),
+                style::Print('│'),
+            )?;
+        }
+
+        let status = match (self.outcome, &self.notice) {
+            (Some(outcome), _) => format!("{}  Esc: quit", outcome.message()),
+            (None, Some(notice)) => format!("{notice}  Esc: quit"),
+            (None, None) => format!(
+                "{} people looking for a home. Go bankrupt and you lose, or be the richer \
+                 after {} collections to win. Esc: quit",
+                self.tenants.get(),
+                self.collections
+            ),
+        };
+        let color = if self.outcome.is_some() || self.notice.is_some() {
+            Color::Yellow
+        } else {
+            Color::White
+        };
+        let width = WINDOW_WIDTH as usize;
+        queue!(
+            self.frame,
+            cursor::MoveTo(0, TOWER_WINDOW_HEIGHT),
+            style::SetColors(Colors::new(color, Color::Black)),
+            style::Print(format!("{status:^width$}")),
+        )
+    }
+
+    /// The player's funds, debt collector and latest message, beside their
+    /// tower.
+    fn draw_panel(&mut self, player: usize, column: u16) -> io::Result<()> {
+        let gs = &self.players[player];
+        let occupants: usize = gs.layers.iter().map(|layer| layer.occupancy).sum();
+        let mut lines = vec![
+            format!("FUNDS: ${}", gs.funds),
+            format!("LOAN: ${}", gs.loan),
+            format!("BACK TAXES: ${}", gs.taxes.arrears),
+            format!("NET WORTH: ${}", net_worth(gs)),
+            stars_text(gs.stars),
+            format!("FLOORS: {}", gs.layers.len()),
+            format!("TENANTS: {occupants}"),
+            format!("COLLECTOR IN: {}", gs.debt_collection_timer),
+            format!("COLLECTION: {} OF {}", gs.period.min(self.collections), self.collections),
+            String::new(),
+        ];
+        lines.extend(wrap(&gs.message, PANEL_WIDTH));
+        let rows = TOWER_WINDOW_HEIGHT as usize - 1;
+        lines.truncate(rows);
+
+        queue!(
+            self.frame,
+            cursor::MoveTo(column, 0),
+            style::SetColors(Colors::new(PLAYER_COLORS[player], Color::Black)),
+            style::Print(format!("PLAYER {}", player + 1)),
+            style::SetForegroundColor(Color::White),
+        )?;
+        for (row, line) in (1..).zip(lines) {
+            let line: String = line.chars().take(PANEL_WIDTH).collect();
+            queue!(self.frame, cursor::MoveTo(column, row), style::Print(line))?;
+        }
+        Ok(())
+    }
+
+    /// The player's keys, under their tower.
+    fn draw_controls(&mut self, player: usize, left: u16) -> io::Result<()> {
+        const COLUMN_WIDTH: u16 = 29;
+        const TOP_ROW: u16 = TOWER_WINDOW_HEIGHT + 2;
+        let rows = WINDOW_HEIGHT - TOP_ROW;
+        let pool = self.controls.get(self.control_ids[player]);
+        let mut entries = Vec::new();
+        for (keys, description, _) in pool.panel_bindings() {
+            let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+            entries.push((keys.join("/"), description.to_string()));
+        }
+        for (i, (keys, description)) in (0..).zip(entries) {
+            let column = left + 1 + i / rows * COLUMN_WIDTH;
+            let entry: String =
+                format!("{keys:>5}: {description}").chars().take(COLUMN_WIDTH as usize).collect();
+            queue!(
+                self.frame,
+                cursor::MoveTo(column, TOP_ROW + i % rows),
+                style::SetColors(Colors::new(PLAYER_COLORS[player], Color::Black)),
+                style::Print(entry),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// What the player would have if they paid everything they owe.
+fn net_worth(gs: &GameState) -> i32 {
+    gs.funds - gs.loan - gs.taxes.arrears
+}
+
+/// `player`'s keys, as bound by the key bindings, for sending their
+/// actions to a game running somewhere else.
+pub fn remote_controls(player: usize) -> CommandPoolArray {
+    let mut builder = CommandPoolArrayBuilder::new(Keymap::load(&DataDir::from_env()));
+    let controls = player_controls(player, &Rules::default(), &FloorTypes::default());
+    let id = builder.add_pool(controls);
+    builder.with_initial_pool(id)
+}
+
+/// A player's controls: selecting floors, building the first six types of
+/// floor, changing rent and borrowing.
+fn player_controls(player: usize, rules: &Rules, floor_types: &FloorTypes) -> CommandPool {
+    const BUILD_ACTIONS: [&str; 6] =
+        ["build_1", "build_2", "build_3", "build_4", "build_5", "build_6"];
+
+    let (name, [up, down], build, [rent_up, rent_down], loan) = if player == 0 {
+        (
+            "player1",
+            [KeyBinding::char('w'), KeyBinding::char('s')],
+            ['1', '2', '3', '4', '5', '6'],
+            [KeyBinding::char('e'), KeyBinding::char('q')],
+            KeyBinding::char('f'),
+        )
+    } else {
+        (
+            "player2",
+            [KeyBinding::key(KeyCode::Up), KeyBinding::key(KeyCode::Down)],
+            ['7', '8', '9', '0', '-', '='],
+            [KeyBinding::char(']'), KeyBinding::char('[')],
+            KeyBinding::char('p'),
+        )
+    };
+    let mut builder = CommandPoolBuilder::new(name)
+        .without_help()
+        .on_key_press(
+            "select_up",
+            &[up],
+            "Floor up",
+            Command::with_triggered_on_repeat(|gs| gs.select_next_floor(1), true)
+                .with_help("Select the floor above."),
+        )
+        .on_key_press(
+            "select_down",
+            &[down],
+            "Floor down",
+            Command::with_triggered_on_repeat(|gs| gs.select_next_floor(-1), true)
+                .with_help("Select the floor below."),
+        );
+    for ((key, action), style) in build.into_iter().zip(BUILD_ACTIONS).zip(floor_types.all()) {
+        let floor_type = floor_types.get(style);
+        let label = floor_type.label.to_lowercase();
+        builder = builder.on_key_press(
+            action,
+            &[KeyBinding::char(key)],
+            format!("{} ${}", floor_type.label, rules.price(floor_type.price)),
+            Command::new(move |gs| Action::Build(style).perform(gs))
+                .with_help(format!("Build {label} on top of the tower.")),
+        );
+    }
+    builder
+        .on_key_press(
+            "rent_up",
+            &[rent_up],
+            "Raise rent",
+            Command::with_triggered_on_repeat(|gs| gs.adjust_selected_rent(10), true)
+                .with_help("Raise rent on the selected floor by 10%."),
+        )
+        .on_key_press(
+            "rent_down",
+            &[rent_down],
+            "Lower rent",
+            Command::with_triggered_on_repeat(|gs| gs.adjust_selected_rent(-10), true)
+                .with_help("Lower rent on the selected floor by 10%."),
+        )
+        .on_key_press(
+            "loan",
+            &[loan],
+            "Take loan",
+            Command::new(|gs| Action::TakeLoan(LOAN_AMOUNT).perform(gs))
+                .with_help(format!("Borrow ${LOAN_AMOUNT}.")),
+        )
+        .build()
+}
+
+/// Plays a versus game in the terminal until a player quits.
+pub async fn run(mut out: impl Write, mods: Rc<Mods>, collections: u32) -> io::Result<()> {
+    let mut versus = Versus::new(mods, Rules::default(), collections, DataDir::from_env());
+    let mut event_stream = event::EventStream::new();
+    let mut heartbeat = tokio::time::interval(Duration::from_secs(1));
+    let fps = versus.players[0].animation_fps;
+    let mut animation = (fps > 0).then(|| {
+        let mut animation = tokio::time::interval(Duration::from_secs(1) / fps);
+        animation.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        animation
+    });
+
+    versus.draw()?;
+    while versus.running {
+        let mut event_fut = event_stream.next().fuse();
+        let mut heartbeat_fut = pin!(heartbeat.tick().fuse());
+        let mut animation_fut = pin!(async {
+            match &mut animation {
+                Some(animation) => animation.tick().await,
+                None => future::pending().await,
+            }
+        }
+        .fuse());
+        futures::select! {
+            event = event_fut => match event {
+                Some(Ok(Event::Key(ke))) => versus.handle_key(ke)?,
+                Some(Ok(Event::Resize(..))) => versus.frame.invalidate(),
+                None => break,
+                Some(Err(e)) => return Err(e),
+                _ => {}
+            },
+            _ = heartbeat_fut => versus.tick()?,
+            _ = animation_fut => versus.animate()?,
+        };
+        queue!(out, terminal::BeginSynchronizedUpdate)?;
+        versus.frame.render(&mut out)?;
+        execute!(out, terminal::EndSynchronizedUpdate)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::LayerType;
+    use crate::staff::Incident;
+    use crate::test_game::{press_versus, versus, TestGame};
+    use crossterm::event::KeyCode;
+
+    #[test]
+    fn versus_richer_player_wins_after_the_last_collection() {
+        let game = TestGame::new();
+        let mut versus = versus(&game, 1);
+        press_versus(&mut versus, KeyCode::Char('9'));
+        for gs in &mut versus.players {
+            gs.debt_collection_timer = 1;
+        }
+        versus.tick().unwrap();
+        assert_eq!(versus.outcome, Some(Outcome::Richer(0)));
+        assert!(versus.frame.find("PLAYER 1 IS THE RICHER TYCOON AND WINS!").is_some());
+    }
+
+    #[test]
+    fn versus_bankrupt_player_loses() {
+        let game = TestGame::new();
+        let mut versus = versus(&game, 5);
+        press_versus(&mut versus, KeyCode::Char('1'));
+        press_versus(&mut versus, KeyCode::Char('2'));
+        for gs in &mut versus.players {
+            gs.debt_collection_timer = 1;
+        }
+        versus.tick().unwrap();
+        assert_eq!(versus.outcome, Some(Outcome::Bankrupt(0)));
+    }
+
+    #[test]
+    fn versus_towers_share_the_city_tenants() {
+        let game = TestGame::new();
+        let mut versus = versus(&game, 5);
+        press_versus(&mut versus, KeyCode::Char('1'));
+        press_versus(&mut versus, KeyCode::Char('7'));
+        versus.players[0].tenant_pool.as_ref().unwrap().set(3);
+        for _ in 0..30 {
+            versus.tick().unwrap();
+        }
+        let occupants: usize = versus.players.iter().map(|gs| gs.layers[0].occupancy).sum();
+        assert!(occupants <= 3, "{occupants} moved in from a city of 3");
+    }
+
+    #[test]
+    fn versus_tenants_who_leave_go_back_to_the_city() {
+        let game = TestGame::new();
+        let mut versus = versus(&game, 5);
+        let gs = &mut versus.players[0];
+        gs.funds = 1_000_000;
+        for _ in 0..10 {
+            gs.build_layer(LayerType::Food).unwrap();
+        }
+        let pool = Rc::clone(gs.tenant_pool.as_ref().unwrap());
+        for layer in &mut gs.layers {
+            layer.set_occupancy(layer.max_occupancy, Some(&pool));
+        }
+        let city =
+            |gs: &GameState| pool.get() + gs.layers.iter().map(|l| l.occupancy).sum::<usize>();
+        let population = city(gs);
+
+        let break_in = (0..1000).find(|_| {
+            let incidents = gs.staff.tend(&mut gs.layers, Some(&pool), &mut gs.rng);
+            incidents.iter().any(|incident| matches!(incident, Incident::BreakIn { .. }))
+        });
+        assert!(break_in.is_some(), "no break-in");
+        assert_eq!(city(gs), population);
+
+        gs.funds = 0;
+        gs.taxes.arrears = 1_000_000;
+        gs.collect_taxes().unwrap();
+        assert!(gs.layers.is_empty(), "the floors weren't seized");
+        assert_eq!(city(gs), population);
+    }
+}
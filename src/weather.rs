@@ -0,0 +1,308 @@
+use crate::layer::{LayerType, INFO_MSG_WIDTH};
+use crate::WINDOW_WIDTH;
+use crossterm::style::{self, Color, Colors};
+use crossterm::{cursor, queue};
+use rand::Rng;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+/// Ticks in a day of the game calendar. The weather changes once a day.
+pub const TICKS_PER_DAY: u64 = 10;
+const DAYS_PER_SEASON: u64 = 5;
+/// Days after today shown in the forecast.
+const FORECAST_DAYS: usize = 3;
+/// Columns and rows of sky above the roof that weather is drawn in.
+const SKY_WIDTH: usize = 26;
+const SKY_HEIGHT: usize = 7;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Weather {
+    Sunny,
+    Rain,
+    Snow,
+    Heatwave,
+}
+
+impl Weather {
+    const ALL: [Weather; 4] = [Weather::Sunny, Weather::Rain, Weather::Snow, Weather::Heatwave];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weather::Sunny => "Sunny",
+            Weather::Rain => "Rain",
+            Weather::Snow => "Snow",
+            Weather::Heatwave => "Heatwave",
+        }
+    }
+
+    /// Identifier used for the weather in save files.
+    pub fn id(self) -> &'static str {
+        match self {
+            Weather::Sunny => "sunny",
+            Weather::Rain => "rain",
+            Weather::Snow => "snow",
+            Weather::Heatwave => "heatwave",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Weather::ALL.into_iter().find(|weather| weather.id() == id)
+    }
+
+    /// What the weather does to the tower, shown when it changes.
+    pub fn description(self) -> &'static str {
+        match self {
+            Weather::Sunny => "The sun is out. Business as usual.",
+            Weather::Rain => "Rain! Food courts fill up with people coming in out of the wet.",
+            Weather::Snow => "It's snowing! Fewer shoppers make it out to retail stores.",
+            Weather::Heatwave => "Heatwave! Air conditioning costs $1 per apartment tenant a tick.",
+        }
+    }
+
+    /// How much busier than usual floors of type `style` are. Busier floors
+    /// are quicker to fill up and slower to empty.
+    pub fn traffic(self, style: LayerType) -> f64 {
+        match (self, style) {
+            (Weather::Rain, LayerType::Food) => 1.5,
+            (Weather::Snow, LayerType::Retail) => 0.5,
+            _ => 1.0,
+        }
+    }
+
+    /// What the weather, which changes every day, does to floors of type
+    /// `style`, for the rules in help.
+    pub fn rule(style: LayerType) -> Option<&'static str> {
+        match style {
+            LayerType::Food => Some(
+                "The weather changes every day. Rain brings diners to food courts, which fill \
+                 up faster and empty slower.",
+            ),
+            LayerType::Retail => Some(
+                "Snow keeps shoppers away from retail stores, which fill up slower and empty \
+                 faster.",
+            ),
+            LayerType::Apartment => {
+                Some("Heatwaves run up apartment utility bills by $1 per tenant a tick.")
+            }
+            _ => None,
+        }
+    }
+
+    /// What each occupant of a floor of type `style` costs in utilities
+    /// each tick.
+    pub fn utility_cost(self, style: LayerType) -> i32 {
+        match (self, style) {
+            (Weather::Heatwave, LayerType::Apartment) => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            Weather::Sunny => Color::Yellow,
+            Weather::Rain => Color::Blue,
+            Weather::Snow => Color::White,
+            Weather::Heatwave => Color::Red,
+        }
+    }
+
+    /// The weather in the sky above the roof, `phase` steps into its
+    /// animation. Spaces are clear sky.
+    pub fn sky_art(self, phase: usize) -> String {
+        let mut sky = vec![vec![' '; SKY_WIDTH]; SKY_HEIGHT];
+        let sun = |sky: &mut Vec<Vec<char>>| {
+            for (row, line) in [r"\ | /", "- O -", r"/ | \"].iter().enumerate() {
+                for (column, ch) in line.chars().enumerate() {
+                    sky[row][SKY_WIDTH - 5 + column] = ch;
+                }
+            }
+        };
+        match self {
+            Weather::Sunny => sun(&mut sky),
+            Weather::Heatwave => {
+                sun(&mut sky);
+                // The air shimmers
+                for row in [4, 6] {
+                    for (column, ch) in sky[row].iter_mut().enumerate() {
+                        if (column + row + phase).is_multiple_of(4) {
+                            *ch = '~';
+                        }
+                    }
+                }
+            }
+            Weather::Rain | Weather::Snow => {
+                let (flake, spacing) = match self {
+                    Weather::Rain => ('\'', 7),
+                    _ => ('*', 11),
+                };
+                // Each row shows what the row above it showed a step ago, so
+                // the drops fall
+                for (row, line) in sky.iter_mut().enumerate() {
+                    for (column, ch) in line.iter_mut().enumerate() {
+                        let fallen = (row as i64 - phase as i64).rem_euclid(SKY_HEIGHT as i64);
+                        if (column as i64 * 3 + fallen * 5) % spacing == 0 {
+                            *ch = flake;
+                        }
+                    }
+                }
+            }
+        }
+        sky.into_iter()
+            .map(|line| line.into_iter().collect::<String>() + "\n")
+            .collect()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    pub fn of_day(day: u64) -> Self {
+        match day / DAYS_PER_SEASON % 4 {
+            0 => Season::Spring,
+            1 => Season::Summer,
+            2 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Season::Spring => "Spring",
+            Season::Summer => "Summer",
+            Season::Autumn => "Autumn",
+            Season::Winter => "Winter",
+        }
+    }
+
+    /// Chances out of 100 of each kind of weather, in the order of
+    /// `Weather::ALL`.
+    fn odds(self) -> [u32; 4] {
+        match self {
+            Season::Spring => [50, 45, 5, 0],
+            Season::Summer => [55, 20, 0, 25],
+            Season::Autumn => [45, 50, 5, 0],
+            Season::Winter => [35, 15, 50, 0],
+        }
+    }
+
+    fn random_weather(self, rng: &mut impl Rng) -> Weather {
+        let mut roll = rng.gen_range(0..100);
+        for (weather, odds) in Weather::ALL.into_iter().zip(self.odds()) {
+            if roll < odds {
+                return weather;
+            }
+            roll -= odds;
+        }
+        Weather::Sunny
+    }
+}
+
+/// The day of the game calendar, its weather and the forecast for the days
+/// after.
+#[derive(Clone)]
+pub struct Calendar {
+    day: u64,
+    /// Today's weather followed by the forecast.
+    days: VecDeque<Weather>,
+}
+
+impl Calendar {
+    /// The first day of a new game.
+    pub fn new(rng: &mut impl Rng) -> Self {
+        let days = (0..=FORECAST_DAYS as u64)
+            .map(|day| Season::of_day(day).random_weather(rng))
+            .collect();
+        Calendar { day: 0, days }
+    }
+
+    /// The calendar on the day `tick` falls on, as saved with `weather`
+    /// (today's weather then the forecast). Days missing from the save are
+    /// made up.
+    pub fn restore(tick: u64, weather: &[Weather], rng: &mut impl Rng) -> Self {
+        let day = tick / TICKS_PER_DAY;
+        let mut days: VecDeque<Weather> = weather.iter().copied().collect();
+        days.truncate(FORECAST_DAYS + 1);
+        while days.len() <= FORECAST_DAYS {
+            days.push_back(Season::of_day(day + days.len() as u64).random_weather(rng));
+        }
+        Calendar { day, days }
+    }
+
+    pub fn season(&self) -> Season {
+        Season::of_day(self.day)
+    }
+
+    pub fn today(&self) -> Weather {
+        self.days[0]
+    }
+
+    /// Today's weather followed by the forecast.
+    pub fn days(&self) -> impl Iterator<Item = Weather> + '_ {
+        self.days.iter().copied()
+    }
+
+    /// Moves on to the day `tick` falls on, returning whether the weather
+    /// changed.
+    pub fn advance(&mut self, tick: u64, rng: &mut impl Rng) -> bool {
+        let yesterday = self.today();
+        while self.day < tick / TICKS_PER_DAY {
+            self.day += 1;
+            self.days.pop_front();
+            let forecast_day = self.day + FORECAST_DAYS as u64;
+            self.days.push_back(Season::of_day(forecast_day).random_weather(rng));
+        }
+        self.today() != yesterday
+    }
+}
+
+/// Shows the date, today's weather and the forecast under the funds.
+pub fn weather_draw(mut writer: impl Write, calendar: &Calendar) -> io::Result<()> {
+    const WIDTH: usize = (WINDOW_WIDTH - INFO_MSG_WIDTH) as usize;
+    let forecast: Vec<&str> = calendar.days().skip(1).map(|weather| weather.name()).collect();
+    let line = format!(
+        "WEATHER: {} ({}, DAY {})  FORECAST: {}",
+        calendar.today().name().to_uppercase(),
+        calendar.season().name().to_uppercase(),
+        calendar.day + 1,
+        forecast.join(", ").to_uppercase()
+    );
+    queue!(
+        writer,
+        cursor::MoveTo(INFO_MSG_WIDTH, 2),
+        style::SetColors(Colors::new(calendar.today().color(), Color::Black)),
+        style::Print(format!("{line:<WIDTH$}")),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledger::TransactionCategory;
+    use crate::test_game::TestGame;
+
+    #[test]
+    fn heatwaves_charge_apartments_for_air_conditioning() {
+        let mut game = TestGame::new();
+        game.gs.calendar = Calendar::restore(0, &[Weather::Heatwave; 4], &mut game.gs.rng);
+        game.build(LayerType::Apartment);
+        game.gs.layers[0].occupancy = 10;
+        game.tick();
+
+        let upkeep: i32 = game
+            .gs
+            .ledger
+            .transactions()
+            .iter()
+            .filter(|t| t.category == TransactionCategory::Upkeep)
+            .map(|t| t.amount)
+            .sum();
+        assert_eq!(upkeep, -(game.gs.layers[0].occupancy as i32));
+        assert!(game.gs.frame.find("WEATHER: HEATWAVE").is_some());
+    }
+}